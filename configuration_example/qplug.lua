@@ -2,12 +2,15 @@
 return {
 	-- [[ Your info ]] --
 	-- Must be a table called "me". Any unimplemented data will just be ignored. 
-	-- This data will be used to populate the plugin info.lua table
-	me = { name = "Your name", email = "Your email" },
+	-- This data will be used to populate the plugin info.lua table (company becomes the Manufacturer)
+	-- and the copyright notices added to the template's init.lua and LICENSE.
+	me = { name = "Your name", email = "Your email", company = "Your company" },
 
 	-- [[ External Template ]] --
 	-- You can define your own template. This can either be an absolute path on your computer. Or a git repo hosted on the internet.
 	-- NOTE: Address must be https:// if you want to fetch from github. If using a local path, please point it to the parent directory. 
+	-- Git templates can pin a branch, tag or commit and use a subfolder: "https://host/repo.git#v2.1:templates/tcp"
+	-- The template's own git history is never copied into your plugin.
 	-- If not defined or set to nil, the builtin template will be used. 
 
 	external_template = nil,
@@ -16,6 +19,16 @@ return {
 	-- external_template = "https://github.com/qsys-plugins/ExamplePlugin"
 	-- external_template = "https://bitbucket.org/qsc-communities/basicpluginframework/src/main/"
 
+	-- [[ License ]] --
+	-- A bundled SPDX id (MIT, ISC, BSD-2-Clause, BSD-3-Clause, 0BSD) or the path to your own license file.
+	license = "MIT",
+	-- Prepend an SPDX-License-Identifier line to every module and the compiled plugin.
+	license_header = true,
+
+	-- [[ Target Q-SYS Version ]] --
+	-- Oldest Q-SYS Designer release the plugins need to run on.
+	target_qsys = "9.4",
+
 	-- [[ External Build/Compile Tool ]] --
 	-- Assign to nil if you want use the builtin build tool.
 	-- Optionally, you can create your own. If so, it must a function that takes no arguments. 
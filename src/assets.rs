@@ -6,6 +6,10 @@ use crate::files::find_file_recursively;
 
 pub static TEMPLATE_DIR: Dir<'_> = include_dir!("$CARGO_MANIFEST_DIR/src/assets/pluginframework");
 pub static DEFINITIONS_DIR: Dir<'_> = include_dir!("$CARGO_MANIFEST_DIR/src/assets/definitions");
+pub static LICENSES_DIR: Dir<'_> = include_dir!("$CARGO_MANIFEST_DIR/src/assets/licenses");
+pub static CONFIG_TEMPLATE: &str = include_str!("assets/config/qplug.lua");
+pub static RUNTIME_EMULATOR: &str = include_str!("assets/emulator/runtime.lua");
+pub static SPEC_HELPERS: &str = include_str!("assets/emulator/spec.lua");
 
 //FIXME: This can recurse into the parent directory when creating a plugin, and grab the wrong
 //info.lua file. If only used outside of the 'new' function - it's okay.
@@ -0,0 +1,47 @@
+-- QPlug configuration.
+-- Generated by `qplug config init`. Every setting is optional; delete or comment out anything you don't need.
+return {
+	-- [[ Your info ]] --
+	-- Must be a table called "me". Any unimplemented data will just be ignored.
+	-- This data will be used to populate the plugin info.lua table
+	--   name    = "Your name",
+	--   email   = "you@example.com",
+	--   company = "Your company",
+	me = __ME__,
+
+	-- [[ External Template ]] --
+	-- You can define your own template. This can either be an absolute path on your computer. Or a git repo hosted on the internet.
+	-- NOTE: Address must be https:// if you want to fetch from github. If using a local path, please point it to the parent directory.
+	-- Git templates can pin a branch, tag or commit and use a subfolder: "https://host/repo.git#v2.1:templates/tcp"
+	-- The template's own git history is never copied into your plugin.
+	-- If not defined or set to nil, the builtin template will be used.
+	-- The QPLUG_TEMPLATE environment variable overrides this setting.
+	external_template = nil,
+	-- external_template = "/home/me/Documents/some/folder/template/",
+	-- external_template = "https://github.com/qsys-plugins/BasePlugin",
+
+	-- [[ License ]] --
+	-- License written into new plugins instead of the template's own: a bundled SPDX id
+	-- (MIT, ISC, BSD-2-Clause, BSD-3-Clause, 0BSD) or the path to your own license file.
+	-- license_header = true prepends an SPDX-License-Identifier line to every module and the compiled plugin.
+	license = nil,
+	-- license = "MIT",
+	-- license = "/home/me/Documents/eula.txt",
+	license_header = false,
+
+	-- [[ Target Q-SYS Version ]] --
+	-- Oldest Q-SYS Designer release your plugins need to run on. Newer APIs are marked deprecated
+	-- in the definitions and `qplug compile` warns where you use them. Usually set per project in `.qplug`.
+	target_qsys = nil,
+	-- target_qsys = "9.4",
+
+	-- [[ External Build/Compile Tool ]] --
+	-- Assign to nil if you want use the builtin build tool.
+	-- Optionally, you can create your own. If so, it must a function that takes no arguments.
+	--  Here's an example using the original from QSC
+	build_tool = nil,
+	-- build_tool = function()
+	-- 	local cmd = ".\\plugincompile|PLUGCC.exe . .\\plugin.lua"
+	-- 	os.execute(cmd)
+	-- end,
+}
@@ -1,11 +1,6 @@
 ---@meta
 ---@diagnostic disable: missing-return, unused-local
 
----@enum UserControls
-local UserControls = {
-	-- Some Controls
-}
-
 --- Represents a control object in Q-SYS.
 --- @see Controls
 ---@class Control
@@ -27,6 +22,22 @@ local UserControls = {
 ---@alias controlArray table<number, Control>
 ---@alias controlDictArrays table<string, controlArray>
 
+---A control declared with `ControlType = "Button"`.
+---@class ButtonControl : Control
+
+---A control declared with `ControlType = "Knob"`.
+---@class KnobControl : Control
+
+---A control declared with `ControlType = "Indicator"`.
+---@class IndicatorControl : Control
+
+---A control declared with `ControlType = "Text"`.
+---@class TextControl : Control
+
+---The controls declared by the plugin's GetControls. `qplug defs controls` (also run by
+---`qplug compile`) fills this in, so misspelled control names are flagged.
+---@class PluginControls
+
 ---Q-Sys Control table.
----@type controlDict | controlArray | controlDictArrays
+---@type PluginControls
 Controls = {}
@@ -0,0 +1,36 @@
+---@meta
+---@diagnostic disable: missing-return, unused-local
+
+---A plugin property, as passed to the design-time callbacks in `props` and at runtime in `Properties`.
+---@class PluginProperty
+---@field Name string
+---@field Type "string" | "integer" | "double" | "boolean" | "enum"
+---@field Value string | number | boolean
+---@field Choices string[]? Choices of an enum property
+---@field Min number? Minimum of an integer or double property
+---@field Max number? Maximum of an integer or double property
+---@field Header string? Group the property under this header in Designer
+---@field IsHidden boolean? Hide the property in Designer. Set it in RectifyProperties.
+---@field IsReadOnly boolean? Show the property in Designer but don't allow changes
+
+---@class StringProperty : PluginProperty
+---@field Value string
+
+---@class IntegerProperty : PluginProperty
+---@field Value integer
+
+---@class DoubleProperty : PluginProperty
+---@field Value number
+
+---@class BooleanProperty : PluginProperty
+---@field Value boolean
+
+---The plugin's properties by name. `qplug defs properties` (also run by `qplug compile`) fills
+---this in from GetProperties, typing enum values from their Choices.
+---@class PluginProperties
+---@field plugin_show_debug BooleanProperty Set by Designer when "Show Debug" is checked
+---@field page_index IntegerProperty Set by Designer for GetControlLayout: the page being shown
+
+---The plugin's properties at runtime.
+---@type PluginProperties
+Properties = {}
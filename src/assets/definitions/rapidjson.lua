@@ -51,6 +51,7 @@ function rapidjson.array() end
 ---Ai Generated
 ---Creates a SchemaDocument from Document or a Lua table or a string contains a JSON schema.
 ---@param doc? SchemaDocument|table|string The the JSON schema stored in rapidjson.Document object, or a Lua table representation of a JSON schema, or a string contains a JSON schema.
+---@since 9.8
 function rapidjson.SchemaDocument(doc) end
 
 ---Ai Generated
@@ -60,6 +61,7 @@ function rapidjson.SchemaDocument(doc) end
 SchemaValidator = {}
 ---Creates a SchemaValidator from a Schema Document
 ---@param sd? SchemaDocument The SchemaDocument to create the validator. SchemaDocument can be shared by schema validators.
+---@since 9.8
 function rapidjson.SchemaValidator(sd) end
 
 ---Ai Generated
@@ -131,5 +131,3 @@ function TcpSocket:ReadLine(EOL, custom) end
 ---@param str string the string/pattern to find
 ---@param start_pos number? optional index where to start to search, default value is 1
 function TcpSocket:Search(str, start_pos) end
-
-sock = TcpSocket:New()
@@ -35,7 +35,7 @@ function Timer:Start(time) end
 ---Stops the timer
 function Timer:Stop() end
 
----Supply a named function to call after a given delay in milliseconds.
+---Supply a named function to call after a given delay in seconds.
 ---@param function_pass fun() to execute after time
----@param time number time interval in MILLISECONDS, when the time expire the function_pass is executed
+---@param time number time interval in SECONDS, when the time expire the function_pass is executed
 function Timer.CallAfter(function_pass, time) end
@@ -47,6 +47,7 @@
 ---@field Error fun(self: WebSocket, error: string) Callback function for when WebSocket errors.
 ---@field Closed fun(self: WebSocket) Callback function for when WebSocket is closed.
 ---@field Ping fun(self: WebSocket) Callback function for when WebSocket pings.
+---@since 9.4
 WebSocket = {}
 
 ---Connect to host with given protocol (ws or wss) to URL and port with optional sub-protocol.
@@ -0,0 +1,282 @@
+-- The Q-SYS runtime as `qplug run` and `qplug test` emulate it: Controls, Timer and a virtual
+-- clock that only moves when told to. Loaded after the definitions, so these replace their stubs.
+
+-- 2024-01-01 00:00:00 UTC, so os.time() is the same on every run.
+local EPOCH = 1704067200
+
+local now = 0
+local queue = {}
+local scheduled = 0
+
+local function schedule(delay, fn)
+	scheduled = scheduled + 1
+	table.insert(queue, { at = now + math.max(delay, 0), order = scheduled, fn = fn })
+end
+
+---------------
+-- The clock --
+---------------
+
+local real_time, real_date = os.time, os.date
+
+os.clock = function()
+	return now
+end
+
+os.time = function(t)
+	if t ~= nil then
+		return real_time(t)
+	end
+	return EPOCH + math.floor(now)
+end
+
+os.date = function(format, t)
+	return real_date(format, t or os.time())
+end
+
+-----------
+-- Timer --
+-----------
+
+local timer_methods = {}
+local timer_meta = { __index = timer_methods }
+
+Timer = {}
+
+function Timer.New()
+	return setmetatable({ running = false, generation = 0 }, timer_meta)
+end
+
+function Timer.CallAfter(fn, delay)
+	schedule(delay, fn)
+end
+
+function timer_methods:Start(interval)
+	self:Stop()
+	self.running = true
+	-- An interval of 0 would fire forever without the clock moving.
+	self.interval = math.max(interval, 0.001)
+	local generation = self.generation
+	local function tick()
+		if not self.running or self.generation ~= generation then
+			return
+		end
+		schedule(self.interval, tick)
+		if self.EventHandler then
+			self.EventHandler(self)
+		end
+	end
+	schedule(self.interval, tick)
+end
+
+function timer_methods:Stop()
+	self.running = false
+	self.generation = self.generation + 1
+end
+
+function timer_methods:IsRunning()
+	return self.running
+end
+
+--------------
+-- Controls --
+--------------
+
+local function format_value(value)
+	if value == math.floor(value) and math.abs(value) < 2 ^ 53 then
+		return string.format("%d", value)
+	end
+	return string.format("%g", value)
+end
+
+-- A control keeps Value, String, Boolean and Position in step the way a Core does. Setting them
+-- from the script doesn't call the EventHandler; `qplug.set` does.
+local function new_control(name, declared)
+	local min = declared.Min or 0
+	-- Text controls and indicators hold their own String rather than showing the value.
+	local is_text = declared.ControlType == "Text"
+		or declared.IndicatorType == "Text"
+		or declared.IndicatorType == "Status"
+	local max = declared.Max or 1
+	local state = {
+		Name = name,
+		Type = declared.ControlType or "Button",
+		ButtonType = declared.ButtonType,
+		IndicatorType = declared.IndicatorType,
+		Min = declared.Min,
+		Max = declared.Max,
+		Value = 0,
+		String = "",
+		Boolean = false,
+		Position = 0,
+		Choices = declared.Choices or {},
+		Legend = "",
+		Color = "",
+		IsDisabled = false,
+		IsInvisible = false,
+		IsIndeterminate = false,
+	}
+
+	local function set_value(value)
+		state.Value = value
+		state.Boolean = value ~= 0
+		if not is_text then
+			state.String = format_value(value)
+		end
+		if max > min then
+			state.Position = math.min(math.max((value - min) / (max - min), 0), 1)
+		end
+	end
+
+	local setters = {
+		Value = function(value)
+			set_value(assert(tonumber(value), "Value must be a number"))
+		end,
+		String = function(value)
+			state.String = tostring(value)
+			local number = tonumber(value)
+			if number and not is_text then
+				set_value(number)
+			end
+		end,
+		Boolean = function(value)
+			set_value(value and 1 or 0)
+		end,
+		Position = function(value)
+			set_value(min + (max - min) * math.min(math.max(value, 0), 1))
+		end,
+	}
+
+	local control = {}
+	function control:Trigger()
+		if state.EventHandler then
+			state.EventHandler(self)
+		end
+	end
+
+	set_value(declared.ControlType == "Knob" and min or 0)
+	return setmetatable(control, {
+		__index = state,
+		__newindex = function(_, key, value)
+			local setter = setters[key]
+			if setter then
+				setter(value)
+			else
+				state[key] = value
+			end
+		end,
+		__tostring = function()
+			return string.format(
+				"%s: Value = %s, String = %q, Boolean = %s",
+				name,
+				format_value(state.Value),
+				state.String,
+				tostring(state.Boolean)
+			)
+		end,
+	})
+end
+
+local function find_control(control)
+	if type(control) ~= "string" then
+		return control
+	end
+	local found = Controls[control]
+	if found == nil then
+		-- `Gain 2` for the second of a control with a Count, as in GetControlLayout.
+		local base, index = control:match("^(.-) (%d+)$")
+		found = base and Controls[base] and Controls[base][tonumber(index)]
+	end
+	if found == nil or getmetatable(found) == nil then
+		error(string.format("no control named %q", control), 3)
+	end
+	return found
+end
+
+-----------
+-- qplug --
+-----------
+
+-- Helpers for scripts, the REPL and specs.
+qplug = {}
+
+---Change a control the way a user would: set it and call its EventHandler. A number sets
+---Value, a string String and a boolean Boolean.
+function qplug.set(control, value)
+	control = find_control(control)
+	local kind = type(value)
+	if kind == "number" then
+		control.Value = value
+	elseif kind == "string" then
+		control.String = value
+	elseif kind == "boolean" then
+		control.Boolean = value
+	else
+		error("a control is set to a number, string or boolean, not a " .. kind, 2)
+	end
+	control:Trigger()
+end
+
+---Press a button: down and up for a momentary, flip a toggle, fire a trigger.
+function qplug.press(control)
+	control = find_control(control)
+	if control.ButtonType == "Toggle" then
+		qplug.set(control, not control.Boolean)
+	elseif control.ButtonType == "Trigger" then
+		control:Trigger()
+	else
+		qplug.set(control, true)
+		qplug.set(control, false)
+	end
+end
+
+-- The index of the earliest event due by `target`. Events due at the same time run in the order
+-- they were scheduled.
+local function next_due(target)
+	local best
+	for i, event in ipairs(queue) do
+		local current = queue[best]
+		if event.at <= target and (current == nil or event.at < current.at) then
+			best = i
+		elseif current and event.at == current.at and event.order < current.order then
+			best = i
+		end
+	end
+	return best
+end
+
+---Move the clock forward, running every timer and CallAfter due on the way in order.
+function qplug.advance(seconds)
+	local target = now + seconds
+	local index = next_due(target)
+	while index do
+		local event = table.remove(queue, index)
+		now = event.at
+		event.fn()
+		index = next_due(target)
+	end
+	now = target
+end
+
+---Seconds since the plugin started.
+function qplug.now()
+	return now
+end
+
+-- Called by qplug with the plugin's properties and its GetControls list.
+return function(properties, controls)
+	Properties = properties
+	Controls = {}
+	for _, declared in ipairs(controls) do
+		local count = declared.Count or 1
+		if count > 1 then
+			local array = {}
+			for i = 1, count do
+				array[i] = new_control(declared.Name .. " " .. i, declared)
+			end
+			Controls[declared.Name] = array
+		else
+			Controls[declared.Name] = new_control(declared.Name, declared)
+		end
+	end
+end
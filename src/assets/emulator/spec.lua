@@ -0,0 +1,317 @@
+-- `describe`, `it` and `assert` for `qplug test`, after busted. A spec file is run once to collect
+-- its blocks, then each `it` runs with the `before_each` and `after_each` of the blocks around it.
+
+local real_assert = assert
+
+-------------
+-- Formats --
+-------------
+
+local function format(value, seen)
+	if type(value) == "string" then
+		return string.format("%q", value)
+	end
+	if type(value) ~= "table" or getmetatable(value) and getmetatable(value).__tostring then
+		return tostring(value)
+	end
+	seen = seen or {}
+	if seen[value] then
+		return "<cycle>"
+	end
+	seen[value] = true
+	local keys = {}
+	for key in pairs(value) do
+		table.insert(keys, key)
+	end
+	table.sort(keys, function(a, b)
+		if type(a) == type(b) and (type(a) == "number" or type(a) == "string") then
+			return a < b
+		end
+		return type(a) < type(b)
+	end)
+	local parts = {}
+	-- The array part without keys, as it was written.
+	local length = 0
+	while value[length + 1] ~= nil do
+		length = length + 1
+		table.insert(parts, format(value[length], seen))
+	end
+	for _, key in ipairs(keys) do
+		local in_array = math.type(key) == "integer" and key >= 1 and key <= length
+		if not in_array then
+			local shown = type(key) == "string" and key:match("^[%a_][%w_]*$") and key
+				or "[" .. format(key, seen) .. "]"
+			table.insert(parts, shown .. " = " .. format(value[key], seen))
+		end
+	end
+	seen[value] = nil
+	if #parts == 0 then
+		return "{}"
+	end
+	return "{ " .. table.concat(parts, ", ") .. " }"
+end
+
+local function same(a, b)
+	if a == b then
+		return true
+	end
+	if type(a) ~= "table" or type(b) ~= "table" then
+		return false
+	end
+	for key, value in pairs(a) do
+		if not same(value, b[key]) then
+			return false
+		end
+	end
+	for key in pairs(b) do
+		if a[key] == nil then
+			return false
+		end
+	end
+	return true
+end
+
+------------
+-- assert --
+------------
+
+-- Each check returns whether it holds, and what to say if it should have and didn't, or
+-- shouldn't have and did.
+local checks = {
+	equal = function(expected, actual)
+		return expected == actual,
+			string.format("expected %s, got %s", format(expected), format(actual)),
+			string.format("expected anything but %s", format(expected))
+	end,
+	same = function(expected, actual)
+		return same(expected, actual),
+			string.format("expected %s, got %s", format(expected), format(actual)),
+			string.format("expected anything but %s", format(expected))
+	end,
+	near = function(expected, actual, tolerance)
+		return math.abs(expected - actual) <= tolerance,
+			string.format("expected %s within %s, got %s", expected, tolerance, actual),
+			string.format("expected %s further than %s from %s", actual, tolerance, expected)
+	end,
+	truthy = function(value)
+		return not not value,
+			string.format("expected a truthy value, got %s", format(value)),
+			string.format("expected a falsy value, got %s", format(value))
+	end,
+	falsy = function(value)
+		return not value,
+			string.format("expected a falsy value, got %s", format(value)),
+			string.format("expected a truthy value, got %s", format(value))
+	end,
+	is_true = function(value)
+		return value == true,
+			string.format("expected true, got %s", format(value)),
+			"expected anything but true"
+	end,
+	is_false = function(value)
+		return value == false,
+			string.format("expected false, got %s", format(value)),
+			"expected anything but false"
+	end,
+	is_nil = function(value)
+		return value == nil,
+			string.format("expected nil, got %s", format(value)),
+			"expected a value, got nil"
+	end,
+	matches = function(pattern, value)
+		return type(value) == "string" and value:match(pattern) ~= nil,
+			string.format("expected %s to match %q", format(value), pattern),
+			string.format("expected %s not to match %q", format(value), pattern)
+	end,
+	has_error = function(fn, expected)
+		local ok, err = pcall(fn)
+		if ok then
+			return false, "expected an error, got none", "expected no error"
+		end
+		if expected == nil then
+			return true, "", string.format("expected no error, got %s", tostring(err))
+		end
+		-- Compare without the location error() adds.
+		local message = type(err) == "string" and err:gsub("^[^:]*:%d+: ", "") or err
+		return same(message, expected),
+			string.format("expected error %s, got %s", format(expected), format(message)),
+			string.format("expected any error but %s", format(expected))
+	end,
+}
+checks.equals = checks.equal
+checks.are_equal = checks.equal
+checks.are_same = checks.same
+checks.error = checks.has_error
+checks.errors = checks.has_error
+checks.match = checks.matches
+
+local function make(check, negated)
+	return function(...)
+		local holds, failure, negated_failure = check(...)
+		if negated then
+			if holds then
+				error(negated_failure, 2)
+			end
+		elseif not holds then
+			error(failure, 2)
+		end
+	end
+end
+
+local function assertions(negated)
+	local table_ = {}
+	for name, check in pairs(checks) do
+		table_[name] = make(check, negated)
+	end
+	-- `assert.is_nil` and `assert.is.Nil` both read well, so accept either.
+	table_["true"] = table_.is_true
+	table_["false"] = table_.is_false
+	table_.Nil = table_.is_nil
+	return table_
+end
+
+local positive = assertions(false)
+local negative = assertions(true)
+positive.is_not_nil = negative.is_nil
+positive.is_not_true = negative.is_true
+positive.is_not_false = negative.is_false
+positive.are_not_equal = negative.equal
+positive.are_not_same = negative.same
+
+assert = setmetatable({}, {
+	__index = function(_, key)
+		if key == "is" or key == "are" or key == "has" then
+			return positive
+		elseif key == "is_not" or key == "are_not" or key == "has_no" or key == "no" then
+			return negative
+		end
+		return positive[key]
+	end,
+	-- Plain assert(value, message) still works.
+	__call = function(_, ...)
+		return real_assert(...)
+	end,
+})
+
+---------------------
+-- describe and it --
+---------------------
+
+local root = { children = {}, before = {}, after = {} }
+local current = root
+-- The line the function `level` calls up is at. qplug passes this in, as specs run without `debug`.
+local line_at
+
+function describe(name, fn)
+	local block = { name = name, children = {}, before = {}, after = {}, parent = current }
+	table.insert(current.children, block)
+	current = block
+	fn()
+	current = block.parent
+end
+
+local function add_test(name, fn, pending)
+	table.insert(current.children, {
+		name = name,
+		fn = fn,
+		pending = pending,
+		line = line_at(3),
+		parent = current,
+	})
+end
+
+function it(name, fn)
+	add_test(name, fn, fn == nil)
+end
+
+function pending(name)
+	add_test(name, nil, true)
+end
+
+function before_each(fn)
+	table.insert(current.before, fn)
+end
+
+function after_each(fn)
+	table.insert(current.after, fn)
+end
+
+-- Outermost first.
+local function blocks_around(test)
+	local blocks = {}
+	local block = test.parent
+	while block do
+		table.insert(blocks, 1, block)
+		block = block.parent
+	end
+	return blocks
+end
+
+local function run_test(test)
+	local blocks = blocks_around(test)
+	local function body()
+		for _, block in ipairs(blocks) do
+			for _, fn in ipairs(block.before) do
+				fn()
+			end
+		end
+		test.fn()
+	end
+	local function teardown()
+		for i = #blocks, 1, -1 do
+			for _, fn in ipairs(blocks[i].after) do
+				fn()
+			end
+		end
+	end
+
+	local ok, err = pcall(body)
+	local torn_down, teardown_err = pcall(teardown)
+	if ok and not torn_down then
+		ok, err = false, teardown_err
+	end
+	return ok, err
+end
+
+local function full_name(test)
+	local names = {}
+	for _, block in ipairs(blocks_around(test)) do
+		if block.name then
+			table.insert(names, block.name)
+		end
+	end
+	table.insert(names, test.name)
+	return table.concat(names, " ")
+end
+
+local function collect(block, tests)
+	for _, child in ipairs(block.children) do
+		if child.children then
+			collect(child, tests)
+		else
+			table.insert(tests, child)
+		end
+	end
+	return tests
+end
+
+-- Called by qplug with the spec file loaded as a function. Returns one result per `it`.
+return function(spec, lines)
+	line_at = lines
+	spec()
+	local results = {}
+	for _, test in ipairs(collect(root, {})) do
+		local result = { name = full_name(test), line = test.line }
+		if test.pending then
+			result.status = "pending"
+		else
+			local ok, err = run_test(test)
+			result.status = ok and "passed" or "failed"
+			if not ok then
+				result.message = type(err) == "string" and err or format(err)
+			end
+		end
+		table.insert(results, result)
+	end
+	return results
+end
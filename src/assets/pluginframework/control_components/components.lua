@@ -1,4 +1,5 @@
 -- Optional function to define components used within the plugin
+---@param props PluginProperties
 function GetComponents(props)
 	local components = {}
 	table.insert(components, {
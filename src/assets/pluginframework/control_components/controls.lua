@@ -1,3 +1,4 @@
+---@param props PluginProperties
 function GetControls(props)
 	local ctrls = {}
 	table.insert(ctrls, {
@@ -1,4 +1,5 @@
 -- Optional function to define wiring of components used within the plugin
+---@param props PluginProperties
 function GetWiring(props)
 	local wiring = {}
 	table.insert(wiring, { "Audio Output", "main_mixer Output 1" })
@@ -1,3 +1,4 @@
+---@param props PluginProperties
 function GetControlLayout(props)
 	local layout = {}
 	local graphics = {}
@@ -1,7 +1,9 @@
+---@param props PluginProperties
 function GetPins(props)
-	local pins = {
+	local pins = {}
+	table.insert(pins, {
 		Name = "Audio Output",
 		Direction = "output",
-	}
+	})
 	return pins
 end
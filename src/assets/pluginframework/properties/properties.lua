@@ -1,9 +1,10 @@
 function GetProperties()
-	local props = {
+	local props = {}
+	table.insert(props, {
 		Name = "Debug Print",
 		Type = "enum",
 		Choices = { "None", "Tx/Rx", "Tx", "Rx", "Function Calls", "All" },
 		Value = "None",
-	}
+	})
 	return props
 end
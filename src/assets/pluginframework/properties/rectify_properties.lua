@@ -1,3 +1,5 @@
+---@param props PluginProperties
+---@return PluginProperties
 function RectifyProperties(props)
 	if props.plugin_show_debug.Value == false then
 		props["Debug Print"].IsHidden = true
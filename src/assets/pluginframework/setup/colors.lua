@@ -1,4 +1,5 @@
 -- Define the color of the plugin object in the design
+---@param props PluginProperties
 function GetColor(props)
 	return { 102, 102, 102 }
 end
@@ -1,4 +1,5 @@
 -- Optional function to define model if plugin supports more than one model
+---@param props PluginProperties
 function GetModel(props)
 	local model = {}
 	if props.Model ~= nil and props.Model.Value ~= "" then
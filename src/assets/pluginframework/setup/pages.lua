@@ -1,5 +1,6 @@
 -- Optional function used if plugin has multiple pages
 PageNames = { "Control", "Setup" } --List the pages within the plugin
+---@param props PluginProperties
 function GetPages(props)
 	local pages = {}
 	for ix, name in ipairs(PageNames) do
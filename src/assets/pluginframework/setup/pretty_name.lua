@@ -1,4 +1,5 @@
 -- The name that will initially display when dragged into a design
+---@param props PluginProperties
 function GetPrettyName(props)
-	return "My First Plugin, version " .. PluginInfo.Version
+	return "{{ name }}, version " .. PluginInfo.Version
 end
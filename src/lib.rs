@@ -4,4 +4,6 @@ pub mod modules;
 pub use modules::cli;
 pub use modules::config;
 pub use modules::files;
+pub use modules::license;
 pub use modules::lua;
+pub use modules::template;
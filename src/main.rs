@@ -3,9 +3,10 @@ use clap_complete::{generate, Shell};
 use mlua::Lua;
 use qplug::assets::INFO_LUA;
 use qplug::cli;
-use qplug::config::{Config, UserConfig, UserEnv};
+use qplug::config::{Config, ConfigOverrides, UserConfig, UserEnv};
 use qplug::lua::api::load_api;
 use std::io::{self};
+use std::path::PathBuf;
 
 fn create_lua_env() -> Lua {
     Lua::new()
@@ -20,7 +21,27 @@ fn main() {
 
     load_api(&lua_env);
 
-    let user_config = UserConfig::new(&lua_env);
+    let matches = cli::cli().get_matches();
+
+    let overrides = ConfigOverrides::from_env().with_cli(
+        matches.get_one::<PathBuf>("Config File").cloned(),
+        *matches.get_one::<bool>("Disable Config").unwrap(),
+    );
+
+    // Config commands must run before the config is loaded - it may not exist yet.
+    if let Some(("config", sub_matches)) = matches.subcommand() {
+        match sub_matches.subcommand() {
+            Some(("init", init_matches)) => {
+                let force = init_matches.get_one::<bool>("Force").unwrap();
+                cli::subcommands::config::init(*force, &overrides);
+            }
+            Some(("show", _)) => cli::subcommands::config::show(&overrides),
+            _ => unreachable!(),
+        }
+        return;
+    }
+
+    let user_config = UserConfig::with_overrides(&lua_env, &overrides);
     let config = Config::from_user_config(&user_config);
 
     let env = UserEnv {
@@ -28,8 +49,6 @@ fn main() {
         config: &config,
     };
 
-    let matches = cli::cli().get_matches();
-
     match matches.subcommand() {
         Some(("new", sub_matches)) => {
             let name = sub_matches.get_one::<String>("Name");
@@ -40,7 +59,23 @@ fn main() {
             let no_template = sub_matches
                 .get_one::<bool>("Disable Template Creation")
                 .unwrap();
-            cli::subcommands::new::create_plugin(name, no_git, no_template, no_defs, env);
+            let options = cli::subcommands::new::NewOptions {
+                author: sub_matches.get_one::<String>("Author").cloned(),
+                description: sub_matches.get_one::<String>("Description").cloned(),
+                manufacturer: sub_matches.get_one::<String>("Manufacturer").cloned(),
+                id: sub_matches.get_one::<uuid::Uuid>("Id").copied(),
+                yes: *sub_matches.get_one::<bool>("Yes").unwrap(),
+                from_qplug: sub_matches.get_one::<PathBuf>("From Qplug").cloned(),
+                license: sub_matches.get_one::<String>("License").cloned(),
+                license_header: *sub_matches.get_one::<bool>("License Header").unwrap(),
+            };
+            cli::subcommands::new::create_plugin(name, no_git, no_template, no_defs, &options, env);
+        }
+        Some(("init", sub_matches)) => {
+            let path = sub_matches.get_one::<PathBuf>("Path");
+            let defs = *sub_matches.get_one::<bool>("Add Lua Definitions").unwrap();
+            let luarc = *sub_matches.get_one::<bool>("Add Luarc").unwrap();
+            cli::subcommands::init::init(path, defs, luarc, env.config.target_qsys.as_ref());
         }
         Some(("build", sub_matches)) => {
             //TODO: Look into allowing builds for custom flat qplug files. (no info.lua file)
@@ -58,14 +93,113 @@ fn main() {
             cli::subcommands::copy::copy_to_plugin_directory().expect("Could not copy plugin");
         }
         Some(("compile", _sub_matches)) => {
-            cli::subcommands::compile::compile();
+            cli::subcommands::compile::compile(
+                &env.config.paths,
+                env.config.spdx_header_id().as_deref(),
+                env.config.target_qsys.as_ref(),
+            );
+        }
+        Some(("lint", _sub_matches)) => cli::subcommands::lint::lint(&env.config.paths),
+        Some(("inspect", sub_matches)) => cli::subcommands::inspect::inspect(
+            &env.config.paths,
+            sub_matches.get_one::<PathBuf>("File"),
+            *sub_matches.get_one::<bool>("Json").unwrap(),
+        ),
+        Some(("preview", sub_matches)) => cli::subcommands::preview::preview(
+            &env.config.paths,
+            sub_matches.get_one::<PathBuf>("File"),
+            sub_matches.get_one::<PathBuf>("Out").unwrap(),
+        ),
+        Some(("run", sub_matches)) => cli::subcommands::run::run(
+            &env.config.paths,
+            sub_matches.get_one::<PathBuf>("File"),
+            sub_matches.get_one::<PathBuf>("Props"),
+            sub_matches.get_one::<PathBuf>("Script"),
+        ),
+        Some(("test", sub_matches)) => {
+            let files: Vec<PathBuf> = sub_matches
+                .get_many::<PathBuf>("Files")
+                .map(|files| files.cloned().collect())
+                .unwrap_or_default();
+            cli::subcommands::test::test(
+                &env.config.paths,
+                &files,
+                sub_matches.get_one::<PathBuf>("Props"),
+                sub_matches
+                    .get_one::<cli::subcommands::test::ReportFormat>("Format")
+                    .unwrap()
+                    .to_owned(),
+            );
         }
         Some(("check", sub_matches)) => {
             let check_option = sub_matches
                 .get_one::<cli::subcommands::check::CheckOption>("Check Option")
                 .unwrap();
-            cli::subcommands::check::check(check_option.to_owned());
+            cli::subcommands::check::check(check_option.to_owned(), &overrides, &env.config.paths);
+        }
+        Some(("generate", sub_matches)) => {
+            use cli::subcommands::generate::{
+                ControlKind, ControlSpec, Generator, PinDirection, PropertyKind,
+            };
+            let (kind, kind_matches) = sub_matches.subcommand().unwrap();
+            let name = kind_matches.get_one::<String>("Name").unwrap().to_owned();
+            let generator = match kind {
+                "control" => Generator::Control(ControlSpec {
+                    name,
+                    kind: *kind_matches.get_one::<ControlKind>("Control Type").unwrap(),
+                    count: *kind_matches.get_one::<u32>("Count").unwrap(),
+                    page: kind_matches.get_one::<String>("Page").cloned(),
+                    layout: !kind_matches.get_one::<bool>("Disable Layout").unwrap(),
+                    handler: *kind_matches.get_one::<bool>("Handler").unwrap(),
+                }),
+                "property" => Generator::Property {
+                    name,
+                    kind: *kind_matches
+                        .get_one::<PropertyKind>("Property Type")
+                        .unwrap(),
+                    choices: kind_matches
+                        .get_many::<String>("Choices")
+                        .map(|c| c.cloned().collect())
+                        .unwrap_or_default(),
+                },
+                "pin" => Generator::Pin {
+                    name,
+                    direction: *kind_matches.get_one::<PinDirection>("Direction").unwrap(),
+                },
+                "page" => Generator::Page(name),
+                "module" => Generator::Module(name),
+                _ => unreachable!(),
+            };
+            cli::subcommands::generate::generate(generator, &env.config.paths);
         }
+        Some(("defs", sub_matches)) => match sub_matches.subcommand() {
+            Some(("setup", setup_matches)) => {
+                let force = *setup_matches.get_one::<bool>("Force").unwrap();
+                let dot_luarc = *setup_matches.get_one::<bool>("Dot Luarc").unwrap();
+                cli::subcommands::defs::setup(
+                    force,
+                    dot_luarc,
+                    &env.config.paths,
+                    env.config.target_qsys.as_ref(),
+                );
+            }
+            Some(("update", update_matches)) => {
+                cli::subcommands::defs::update(
+                    *update_matches.get_one::<bool>("Force").unwrap(),
+                    env.config.target_qsys.as_ref(),
+                );
+            }
+            Some(("controls", _)) => cli::subcommands::defs::controls(&env.config.paths),
+            Some(("properties", _)) => cli::subcommands::defs::properties(&env.config.paths),
+            _ => unreachable!(),
+        },
+        Some(("template", sub_matches)) => match sub_matches.subcommand() {
+            Some(("update", update_matches)) => {
+                let url = update_matches.get_one::<String>("Url");
+                cli::subcommands::template::update(url, env.config);
+            }
+            _ => unreachable!(),
+        },
         Some(("completions", sub_matches)) => {
             let shell = sub_matches.get_one::<Shell>("shell").unwrap();
             let mut app = Command::new(APP_NAME);
@@ -3,8 +3,16 @@ use clap::{
     Command,
 };
 use clap_complete::Shell;
-use subcommands::{build::VersionType, check::CheckOption};
+use std::path::PathBuf;
+use subcommands::{
+    build::VersionType,
+    check::CheckOption,
+    generate::{ControlKind, PinDirection, PropertyKind},
+    test::ReportFormat,
+};
+use uuid::Uuid;
 
+pub mod prompt;
 pub mod subcommands;
 
 const STYLES: styling::Styles = styling::Styles::styled()
@@ -25,6 +33,22 @@ pub fn cli() -> Command {
         .author(crate_authors!())
         .subcommand_required(true)
         .arg_required_else_help(true)
+        .arg(
+            Arg::new("Config File")
+                .long("config")
+                .global(true)
+                .value_name("PATH")
+                .value_parser(value_parser!(PathBuf))
+                .help("Use this config file instead of the global one. Overrides QPLUG_CONFIG.")
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("Disable Config")
+                .long("no-config")
+                .global(true)
+                .help("Ignore all global config files (--config, QPLUG_CONFIG, ~/.config/qplug/qplug.lua, ~/.qplug.lua).")
+                .action(ArgAction::SetTrue),
+        )
         // New
         .subcommand(
             Command::new("new")
@@ -49,6 +73,90 @@ pub fn cli() -> Command {
                         .long("no-template")
                         .default_value("false")
                         .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("Author")
+                        .long("author")
+                        .help("Plugin author. Defaults to `me.name` from the config.")
+                        .action(ArgAction::Set),
+                )
+                .arg(
+                    Arg::new("Description")
+                        .long("description")
+                        .help("Plugin description.")
+                        .action(ArgAction::Set),
+                )
+                .arg(
+                    Arg::new("Manufacturer")
+                        .long("manufacturer")
+                        .help("Plugin manufacturer. Defaults to `me.company` from the config.")
+                        .action(ArgAction::Set),
+                )
+                .arg(
+                    Arg::new("Id")
+                        .long("id")
+                        .value_parser(value_parser!(Uuid))
+                        .help("Plugin id. Defaults to a new random UUID.")
+                        .action(ArgAction::Set),
+                )
+                .arg(
+                    Arg::new("From Qplug")
+                        .long("from-qplug")
+                        .value_name("FILE")
+                        .value_parser(value_parser!(PathBuf))
+                        .help("Split an existing flat .qplug file into modules instead of using a template.")
+                        .conflicts_with_all([
+                            "Author",
+                            "Description",
+                            "Manufacturer",
+                            "Id",
+                            "License",
+                            "License Header",
+                        ])
+                        .action(ArgAction::Set),
+                )
+                .arg(
+                    Arg::new("License")
+                        .long("license")
+                        .value_name("SPDX-ID|PATH")
+                        .help("License to write, e.g. MIT, or the path to your own license file. Overrides `license` from the config.")
+                        .action(ArgAction::Set),
+                )
+                .arg(
+                    Arg::new("License Header")
+                        .long("license-header")
+                        .help("Prepend an SPDX-License-Identifier header to every Lua module.")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("Yes")
+                        .long("yes")
+                        .short('y')
+                        .help("Accept the defaults instead of prompting for anything not given.")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        // Init
+        .subcommand(
+            Command::new("init")
+                .about("Adopt an existing plugin directory without touching its sources.")
+                .arg(
+                    Arg::new("Path")
+                        .value_parser(value_parser!(PathBuf))
+                        .help("Plugin directory. Defaults to the current directory.")
+                        .action(ArgAction::Set),
+                )
+                .arg(
+                    Arg::new("Add Lua Definitions")
+                        .long("defs")
+                        .help("Add the Q-Sys Lua definitions.")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("Add Luarc")
+                        .long("luarc")
+                        .help("Add a .luarc.json for the Lua language server.")
+                        .action(ArgAction::SetTrue),
                 ),
         )
         // Build
@@ -82,6 +190,87 @@ pub fn cli() -> Command {
             Command::new("compile")
                 .about("Compile the plugin. Do not increment versioning or copy to plugin folder."),
         )
+        .subcommand(
+            Command::new("lint")
+                .about("Report undefined globals and Q-SYS incompatibilities in the plugin."),
+        )
+        .subcommand(
+            Command::new("inspect")
+                .about("Run the design-time callbacks and print what Designer would see.")
+                .arg(
+                    Arg::new("File")
+                        .value_parser(value_parser!(PathBuf))
+                        .help("A compiled .qplug to inspect instead of the project's plugin_src."),
+                )
+                .arg(
+                    Arg::new("Json")
+                        .long("json")
+                        .help("Print the results as JSON.")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("preview")
+                .about("Render each page of GetControlLayout to an SVG.")
+                .arg(
+                    Arg::new("File")
+                        .value_parser(value_parser!(PathBuf))
+                        .help("A compiled .qplug to preview instead of the project's plugin_src."),
+                )
+                .arg(
+                    Arg::new("Out")
+                        .long("out")
+                        .short('o')
+                        .value_parser(value_parser!(PathBuf))
+                        .default_value("preview")
+                        .help("Directory to write the SVGs to."),
+                ),
+        )
+        .subcommand(
+            Command::new("run")
+                .about("Run the plugin's runtime code with emulated Controls and Timers.")
+                .arg(
+                    Arg::new("File")
+                        .value_parser(value_parser!(PathBuf))
+                        .help("A compiled .qplug to run instead of the project's plugin_src."),
+                )
+                .arg(
+                    Arg::new("Props")
+                        .long("props")
+                        .value_parser(value_parser!(PathBuf))
+                        .help("JSON file of property values, e.g. {\"Input Count\": 4}."),
+                )
+                .arg(
+                    Arg::new("Script")
+                        .long("script")
+                        .value_parser(value_parser!(PathBuf))
+                        .help("Lua script to run against the plugin. Without one, Lua is read from stdin."),
+                ),
+        )
+        .subcommand(
+            Command::new("test")
+                .about("Run the *_spec.lua files under tests/ against the emulated runtime.")
+                .arg(
+                    Arg::new("Files")
+                        .value_parser(value_parser!(PathBuf))
+                        .num_args(0..)
+                        .help("Spec files to run instead of every *_spec.lua under tests/."),
+                )
+                .arg(
+                    Arg::new("Props")
+                        .long("props")
+                        .value_parser(value_parser!(PathBuf))
+                        .help("JSON file of property values, e.g. {\"Input Count\": 4}."),
+                )
+                .arg(
+                    Arg::new("Format")
+                        .long("format")
+                        .value_parser(value_parser!(ReportFormat))
+                        .ignore_case(true)
+                        .default_value("human")
+                        .help("How to report the results. `junit` prints JUnit XML for CI."),
+                ),
+        )
         .subcommand(Command::new("check")
             .about("check if current directory is a valid plugin.")
             .arg(Arg::new("Check Option")
@@ -90,6 +279,168 @@ pub fn cli() -> Command {
                 .default_value("qplug")
             )
         )
+        // Generate
+        .subcommand(
+            Command::new("generate")
+                .about("Add controls, properties, pins, pages or modules to the plugin.")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("control")
+                        .about("Add a control to GetControls and lay it out in GetControlLayout.")
+                        .arg(Arg::new("Name").required(true))
+                        .arg(
+                            Arg::new("Control Type")
+                                .long("type")
+                                .value_parser(value_parser!(ControlKind))
+                                .ignore_case(true)
+                                .default_value("button"),
+                        )
+                        .arg(
+                            Arg::new("Count")
+                                .long("count")
+                                .value_parser(value_parser!(u32).range(1..))
+                                .default_value("1"),
+                        )
+                        .arg(
+                            Arg::new("Page")
+                                .long("page")
+                                .help("Page to lay the control out on. Defaults to the first page.")
+                                .action(ArgAction::Set),
+                        )
+                        .arg(
+                            Arg::new("Disable Layout")
+                                .long("no-layout")
+                                .help("Do not add the control to GetControlLayout.")
+                                .action(ArgAction::SetTrue),
+                        )
+                        .arg(
+                            Arg::new("Handler")
+                                .long("handler")
+                                .help("Stub an EventHandler in runtime.lua.")
+                                .action(ArgAction::SetTrue),
+                        ),
+                )
+                .subcommand(
+                    Command::new("property")
+                        .about("Add a property to GetProperties.")
+                        .arg(Arg::new("Name").required(true))
+                        .arg(
+                            Arg::new("Property Type")
+                                .long("type")
+                                .value_parser(value_parser!(PropertyKind))
+                                .ignore_case(true)
+                                .default_value("string"),
+                        )
+                        .arg(
+                            Arg::new("Choices")
+                                .long("choices")
+                                .help("Comma separated choices for enum properties.")
+                                .value_delimiter(',')
+                                .action(ArgAction::Append),
+                        ),
+                )
+                .subcommand(
+                    Command::new("pin")
+                        .about("Add a pin to GetPins.")
+                        .arg(Arg::new("Name").required(true))
+                        .arg(
+                            Arg::new("Direction")
+                                .long("direction")
+                                .value_parser(value_parser!(PinDirection))
+                                .ignore_case(true)
+                                .default_value("input"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("page")
+                        .about("Add a page to PageNames and GetControlLayout.")
+                        .arg(Arg::new("Name").required(true)),
+                )
+                .subcommand(
+                    Command::new("module")
+                        .about("Create a module under plugin_src and require it from init.lua.")
+                        .arg(
+                            Arg::new("Name")
+                                .required(true)
+                                .help("Module name, e.g. `network.tcp`."),
+                        ),
+                ),
+        )
+        // Defs
+        .subcommand(
+            Command::new("defs")
+                .about("Manage the Q-Sys Lua definitions.")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("setup")
+                        .about("Add the definitions if missing and write a .luarc.json for lua-language-server.")
+                        .arg(
+                            Arg::new("Force")
+                                .long("force")
+                                .help("Overwrite an existing .luarc.json.")
+                                .action(ArgAction::SetTrue),
+                        )
+                        .arg(
+                            Arg::new("Dot Luarc")
+                                .long("dot-luarc")
+                                .help("Also write the settings to .luarc for editors that don't read .luarc.json.")
+                                .action(ArgAction::SetTrue),
+                        ),
+                )
+                .subcommand(
+                    Command::new("update")
+                        .about("Refresh the project's definitions from this qplug release. Locally modified files are kept.")
+                        .arg(
+                            Arg::new("Force")
+                                .long("force")
+                                .help("Also replace definitions you have modified.")
+                                .action(ArgAction::SetTrue),
+                        ),
+                )
+                .subcommand(
+                    Command::new("controls")
+                        .about("Declare the plugin's controls for lua-language-server by running GetControls. Also runs on compile."),
+                )
+                .subcommand(
+                    Command::new("properties")
+                        .about("Declare the plugin's properties for lua-language-server by running GetProperties. Also runs on compile."),
+                ),
+        )
+        // Config
+        .subcommand(
+            Command::new("config")
+                .about("Manage the qplug user config.")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("init")
+                        .about("Create a commented user config file.")
+                        .arg(
+                            Arg::new("Force")
+                                .long("force")
+                                .help("Overwrite an existing config file.")
+                                .action(ArgAction::SetTrue),
+                        ),
+                )
+                .subcommand(
+                    Command::new("show")
+                        .about("Show every config layer that applies to the current directory."),
+                ),
+        )
+        // Template
+        .subcommand(
+            Command::new("template")
+                .about("Manage git-hosted templates.")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("update")
+                        .about("Refresh the offline cache of git templates.")
+                        .arg(
+                            Arg::new("Url")
+                                .help("Template to refresh. Defaults to the configured template and every cached one.")
+                                .action(ArgAction::Set),
+                        ),
+                ),
+        )
         //Generate shell completion
         .subcommand(
             Command::new("completions")
@@ -0,0 +1,40 @@
+use std::io::{self, BufRead, IsTerminal, Write};
+
+/// Print `message` and read a single trimmed line from stdin.
+pub fn prompt(message: &str) -> io::Result<String> {
+    println!("{message}");
+    io::stdout().flush()?;
+    read_answer(&mut io::stdin().lock())
+}
+
+/// Read a trimmed line. Running out of input is an error, so a prompt that re-asks until it gets
+/// a valid answer can't loop forever on a closed stdin.
+fn read_answer(input: &mut impl BufRead) -> io::Result<String> {
+    let mut answer = String::new();
+    if input.read_line(&mut answer)? == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "stdin was closed before an answer was given",
+        ));
+    }
+    Ok(answer.trim().to_string())
+}
+
+/// Whether someone is at stdin to answer prompts. False in pipes and CI.
+pub fn is_interactive() -> bool {
+    io::stdin().is_terminal()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_answer() {
+        let mut input = io::Cursor::new("  yes \n\n");
+        assert_eq!(read_answer(&mut input).unwrap(), "yes");
+        assert_eq!(read_answer(&mut input).unwrap(), "");
+        let err = read_answer(&mut input).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -1,11 +1,12 @@
 use clap::ValueEnum;
 use mlua::{Lua, UserData};
-use std::path::PathBuf;
+use std::{path::PathBuf, process::exit};
 
 use crate::config::UserEnv;
+use crate::files::find_project_dir;
 use crate::lua::info::PluginInfo;
 
-use super::copy::copy_to_plugin_directory;
+use super::{copy::copy_to_plugin_directory, lint::compat_problems};
 
 //TODO: Refactor this to a more central location.
 #[derive(ValueEnum, Clone, Debug)]
@@ -19,11 +20,36 @@ pub enum VersionType {
 impl UserData for VersionType {}
 
 pub fn build(version: VersionType, info_path: PathBuf, user_env: UserEnv) {
+    // A release that can't run on a Core is never what anyone wants. Dev builds only warn, from
+    // compile.
+    if !matches!(version, VersionType::Dev) {
+        check_release_compat(&user_env);
+    }
     update_version(version, info_path, user_env.lua);
     (user_env.config.build_tool)();
     copy_to_plugin_directory().expect("Could not copy plugin");
 }
 
+fn check_release_compat(user_env: &UserEnv) {
+    let Some(root_path) = find_project_dir(None) else {
+        return;
+    };
+    let problems = match compat_problems(&root_path, &user_env.config.paths) {
+        Ok(problems) => problems,
+        Err(e) => {
+            eprintln!("Could not read the plugin: {}", e);
+            exit(1);
+        }
+    };
+    if !problems.is_empty() {
+        for (location, problem) in &problems {
+            eprintln!("{}: {}", location, problem);
+        }
+        eprintln!("Release builds must run on Q-SYS. Fix the problems above or build with `dev`.");
+        exit(1);
+    }
+}
+
 fn update_version(version: VersionType, info_path: PathBuf, lua: &Lua) {
     let mut info = PluginInfo::from_file(&info_path, lua).expect("Error getting plugin info.");
     info = info.update_version(version).expect("Update failed.");
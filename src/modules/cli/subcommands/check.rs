@@ -1,6 +1,25 @@
+use std::process::exit;
+
 use clap::ValueEnum;
+use serde_json::{Map, Value as JsonValue};
+
+use super::inspect::PluginSource;
+use crate::{
+    assets::DEFINITIONS_DIR,
+    config::{resolve_config_file, ConfigOverrides, ProjectPaths},
+    files::find_project_dir,
+    lua::{
+        error_message,
+        parser::quote_string,
+        sweep::{
+            check_combination, combination_count, combinations, property_list, property_space,
+            Problem, PropertySpace,
+        },
+    },
+};
 
-use crate::{config::find_config_file, files::find_project_dir};
+/// The most property combinations `check props` runs. Past this it samples them.
+const MAX_COMBINATIONS: usize = 256;
 
 #[derive(ValueEnum, Clone, Debug)]
 #[clap(rename_all = "lower")]
@@ -8,9 +27,10 @@ pub enum CheckOption {
     Version,
     Qplug,
     Config,
+    Props,
 }
 
-pub fn check(check_option: CheckOption) {
+pub fn check(check_option: CheckOption, overrides: &ConfigOverrides, paths: &ProjectPaths) {
     match check_option {
         CheckOption::Version => println!("Qplug version: {}", env!("CARGO_PKG_VERSION")),
         CheckOption::Qplug => {
@@ -20,9 +40,124 @@ pub fn check(check_option: CheckOption) {
                 None => println!("Not a Qplug plugin. You may want to try `qplug init` or navigating to a qplug directory."),
             }
         }
-        CheckOption::Config => match find_config_file() {
+        CheckOption::Config => match resolve_config_file(overrides) {
             Some(f) => println!("Config file found! {:?}", f),
             None => println!("No config file found. You may want to try `qplug new`"),
         },
+        CheckOption::Props => check_props(paths),
+    }
+}
+
+/// Run the design-time callbacks for combinations of the plugin's property values and report
+/// each problem once, with the combinations that cause it.
+fn check_props(paths: &ProjectPaths) {
+    let plugin = PluginSource::load(paths, None);
+    let fail = |e: mlua::Error| -> ! {
+        eprintln!("error: {}", plugin.relocate(&error_message(&e)));
+        exit(1);
+    };
+
+    let list =
+        property_list(&DEFINITIONS_DIR, &plugin.source, &plugin.chunk).unwrap_or_else(|e| fail(e));
+    let space = property_space(&list);
+    let total = combination_count(&space);
+    let tried = combinations(&space, MAX_COMBINATIONS);
+
+    let mut found: Vec<(Problem, Vec<&Map<String, JsonValue>>)> = Vec::new();
+    for values in &tried {
+        let problems = check_combination(&DEFINITIONS_DIR, &plugin.source, &plugin.chunk, values)
+            .unwrap_or_else(|e| fail(e));
+        for problem in problems {
+            let problem = match problem {
+                Problem::Error { callback, message } => Problem::Error {
+                    callback,
+                    message: plugin.relocate(&message),
+                },
+                malformed => malformed,
+            };
+            match found.iter_mut().find(|(seen, _)| *seen == problem) {
+                Some((_, combinations)) => combinations.push(values),
+                None => found.push((problem, vec![values])),
+            }
+        }
+    }
+
+    if tried.len() < total {
+        println!(
+            "Checked {} of {} property combinations.",
+            tried.len(),
+            total
+        );
+    } else {
+        println!("Checked {} property combinations.", tried.len());
+    }
+    for (problem, combinations) in &found {
+        println!("\n{}", problem);
+        println!("  with {}", describe_combination(&space, combinations[0]));
+        match combinations.len() - 1 {
+            0 => {}
+            1 => println!("  and 1 more combination"),
+            n => println!("  and {} more combinations", n),
+        }
+    }
+
+    match found.len() {
+        0 => println!("No problems found."),
+        1 => {
+            eprintln!("\n1 problem found.");
+            exit(1);
+        }
+        n => {
+            eprintln!("\n{} problems found.", n);
+            exit(1);
+        }
+    }
+}
+
+/// The values in `combination` that aren't the defaults, like `Count = 0, Model = "X2"`.
+fn describe_combination(space: &PropertySpace, combination: &Map<String, JsonValue>) -> String {
+    let changed: Vec<String> = space
+        .iter()
+        .filter_map(|(name, values)| {
+            let value = combination.get(name)?;
+            if Some(value) == values.first() {
+                return None;
+            }
+            let shown = match value {
+                JsonValue::String(s) => quote_string(s),
+                other => other.to_string(),
+            };
+            Some(format!("{} = {}", name, shown))
+        })
+        .collect();
+    if changed.is_empty() {
+        "the defaults".to_string()
+    } else {
+        changed.join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_describe_combination() {
+        let space: PropertySpace = vec![
+            ("Count".to_string(), vec![json!(2), json!(0)]),
+            ("Model".to_string(), vec![json!("X1"), json!("X2")]),
+        ];
+        let combination = json!({ "Count": 0, "Model": "X2" });
+        assert_eq!(
+            describe_combination(&space, combination.as_object().unwrap()),
+            "Count = 0, Model = \"X2\""
+        );
+        let defaults = json!({ "Count": 2, "Model": "X1" });
+        assert_eq!(
+            describe_combination(&space, defaults.as_object().unwrap()),
+            "the defaults"
+        );
     }
 }
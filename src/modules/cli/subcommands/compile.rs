@@ -1,13 +1,55 @@
-use crate::{files::find_project_dir, lua::parser::merge_lua_files};
+use std::{fs, path::Path, process::exit};
 
-pub fn compile() {
+use super::{
+    defs::{tagged_apis, update_generated},
+    lint::compat_problems,
+};
+use crate::{
+    config::ProjectPaths,
+    files::{find_project_dir, walk_files},
+    lua::{
+        compat::Incompatibility,
+        parser::merge_lua_files,
+        since::{coverage_note, newer_api_uses, QsysVersion},
+    },
+};
+
+/// Merge the plugin into a single `.qplug`, with an SPDX header for `spdx_id` if given. With a
+/// `target_qsys`, warn about APIs the target doesn't have. Anything that won't run in the Q-SYS
+/// sandbox is warned about too, except a `require` of a module that is neither the plugin's nor
+/// Q-SYS's, which is usually a typo and fails the compile.
+pub fn compile(paths: &ProjectPaths, spdx_id: Option<&str>, target_qsys: Option<&QsysVersion>) {
     let marker = find_project_dir(None);
-    if marker.is_some() {
-        let root_path = marker.unwrap();
-        let plugin_path = root_path.join("plugin_src");
-        match merge_lua_files(root_path, plugin_path) {
-            Ok(_) => println!("Plugin updated successfully."),
-            Err(e) => println!("Failed to update plugin: {}", e),
+    if let Some(root_path) = marker {
+        let plugin_path = paths.plugin_path(&root_path);
+        let entry_path = paths.entry_path(&root_path);
+        if let Some(target) = target_qsys {
+            warn_newer_apis(&root_path, &plugin_path, target);
+        }
+        if let Ok(problems) = compat_problems(&root_path, paths) {
+            let mut unknown_modules = 0;
+            for (location, problem) in problems {
+                if matches!(problem, Incompatibility::UnknownModule(_)) {
+                    unknown_modules += 1;
+                    eprintln!("error: {}: {}", location, problem);
+                } else {
+                    eprintln!("warning: {}: {}", location, problem);
+                }
+            }
+            if unknown_modules > 0 {
+                eprintln!("Failed to update plugin: a required module was not found.");
+                exit(1);
+            }
+        }
+        match merge_lua_files(root_path.clone(), plugin_path, entry_path, spdx_id) {
+            Ok(_) => {
+                println!("Plugin updated successfully.");
+                update_generated(&root_path, paths);
+            }
+            Err(e) => {
+                eprintln!("Failed to update plugin: {}", e);
+                exit(1);
+            }
         }
     } else {
         println!(
@@ -15,3 +57,31 @@ pub fn compile() {
         );
     }
 }
+
+/// Print a warning for every use of an API newer than `target` in the plugin's sources, then
+/// how much that covers.
+fn warn_newer_apis(root_path: &Path, plugin_path: &Path, target: &QsysVersion) {
+    let apis = tagged_apis(root_path);
+    let mut files: Vec<_> = walk_files(plugin_path)
+        .into_iter()
+        .filter(|file| file.extension().is_some_and(|ext| ext == "lua"))
+        .collect();
+    files.sort();
+    for file in files {
+        let Ok(source) = fs::read_to_string(&file) else {
+            continue;
+        };
+        let display = file.strip_prefix(root_path).unwrap_or(&file);
+        for found in newer_api_uses(&source, &apis, target) {
+            eprintln!(
+                "warning: {}:{}: {} needs Q-SYS {}, this project targets {}",
+                display.display(),
+                found.line,
+                found.api.name,
+                found.api.since,
+                target
+            );
+        }
+    }
+    eprintln!("{}", coverage_note(&apis, target));
+}
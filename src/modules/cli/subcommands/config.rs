@@ -0,0 +1,231 @@
+use std::{fs, io, path::Path, process::exit};
+
+use mlua::{Lua, Table, Value};
+
+use crate::{
+    assets::CONFIG_TEMPLATE,
+    cli::prompt::prompt,
+    config::{
+        config_layers, default_config_path, eval_config, load_config_file, Author, ConfigOverrides,
+        UserConfig, TEMPLATE_ENV,
+    },
+    files::pwd,
+    lua::{
+        error_message,
+        parser::{quote_string, serialize_value},
+    },
+};
+
+/// Scaffold a commented user config. Writes to `--config`/`QPLUG_CONFIG` when given, otherwise
+/// to the first location `find_config_file` searches.
+pub fn init(force: bool, overrides: &ConfigOverrides) {
+    let path = overrides
+        .config_file
+        .clone()
+        .unwrap_or_else(default_config_path);
+
+    if path.exists() && !force {
+        eprintln!(
+            "A config file already exists at {}. Use --force to overwrite it.",
+            path.display()
+        );
+        exit(1);
+    }
+
+    let author = Author {
+        name: ask("Enter your name: "),
+        email: ask("Enter your email: "),
+        company: ask("Enter your company: "),
+    };
+
+    let contents = render_config(&author);
+    if let Err(e) = validate_config(&contents, &path.to_string_lossy()) {
+        eprintln!(
+            "The generated config for {} is invalid: {}",
+            path.display(),
+            e
+        );
+        exit(1);
+    }
+
+    if let Err(e) = write_config(&path, &contents, force) {
+        eprintln!("Failed to write config file {}: {}", path.display(), e);
+        exit(1);
+    }
+
+    println!("Config file created: {}", path.display());
+}
+
+/// Print every config layer that applies here, lowest precedence first.
+pub fn show(overrides: &ConfigOverrides) {
+    let lua = Lua::new();
+    let layers = config_layers(overrides, &pwd());
+
+    if layers.is_empty() && overrides.template.is_none() {
+        println!("No config files found. Built-in defaults are in use.");
+        return;
+    }
+
+    for layer in layers {
+        println!("[{}] {}", layer.kind, layer.path.display());
+        print_settings(&lua, &load_config_file(&lua, &layer.path));
+    }
+
+    if let Some(template) = &overrides.template {
+        println!("[environment] {}", TEMPLATE_ENV);
+        println!("  external_template = {}", quote_string(template));
+    }
+}
+
+fn print_settings(lua: &Lua, table: &Table) {
+    let mut settings: Vec<(String, String)> = table
+        .clone()
+        .pairs::<String, Value>()
+        .flatten()
+        .map(|(key, value)| {
+            let value = match value {
+                Value::Function(_) => "<function>".to_string(),
+                value => serialize_value(lua, &value),
+            };
+            (key, value)
+        })
+        .collect();
+    settings.sort();
+
+    if settings.is_empty() {
+        println!("  (empty)");
+    }
+    for (key, value) in settings {
+        println!("  {} = {}", key, value);
+    }
+}
+
+fn ask(message: &str) -> Option<String> {
+    let answer = prompt(message).expect("Oops, Could not read your answer.");
+    if answer.is_empty() {
+        None
+    } else {
+        Some(answer)
+    }
+}
+
+/// Fill the config template with the user's info. Unanswered fields are left out.
+pub fn render_config(author: &Author) -> String {
+    let fields: Vec<String> = [
+        ("name", &author.name),
+        ("email", &author.email),
+        ("company", &author.company),
+    ]
+    .into_iter()
+    .filter_map(|(key, value)| {
+        value
+            .as_ref()
+            .map(|v| format!("{} = {}", key, quote_string(v)))
+    })
+    .collect();
+
+    CONFIG_TEMPLATE.replace("__ME__", &format!("{{ {} }}", fields.join(", ")))
+}
+
+pub fn write_config(path: &Path, contents: &str, force: bool) -> io::Result<()> {
+    if path.exists() && !force {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "config file already exists",
+        ));
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, contents)
+}
+
+/// Run the config `contents` on their own, without the other config layers, and read them the
+/// way [`UserConfig`] does to make sure they load and every setting has the right type. `name`
+/// is the file they're for, used in errors.
+pub fn validate_config(contents: &str, name: &str) -> Result<(), String> {
+    let lua = Lua::new();
+    let config = eval_config(&lua, contents, name).map_err(|e| error_message(&e))?;
+    let user_config = UserConfig::from_table(&config);
+    user_config.validate()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_render_config_loads() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("qplug/qplug.lua");
+        let author = Author {
+            name: Some("Jane \"JD\" Doe".to_string()),
+            email: Some("jane@example.com".to_string()),
+            company: None,
+        };
+
+        let contents = render_config(&author);
+        validate_config(&contents, "qplug.lua").unwrap();
+        write_config(&path, &contents, false).unwrap();
+
+        let lua = Lua::new();
+        let overrides = ConfigOverrides {
+            config_file: Some(path),
+            ..Default::default()
+        };
+        let user_config = UserConfig::with_overrides(&lua, &overrides);
+        let config = Config::from_user_config(&user_config);
+        assert_eq!(config.me.name.as_deref(), Some("Jane \"JD\" Doe"));
+        assert_eq!(config.me.email.as_deref(), Some("jane@example.com"));
+    }
+
+    #[test]
+    fn test_validate_config_rejects_broken_config() {
+        let err = validate_config("return { me = { name = \"Jane\" }", "qplug.lua").unwrap_err();
+        assert!(err.starts_with("qplug.lua:1:"), "{}", err);
+        assert!(validate_config("error('boom')", "qplug.lua").is_err());
+        assert_eq!(
+            validate_config("return { me = 'Jane' }", "qplug.lua"),
+            Err("`me` must be a table, got string".to_string())
+        );
+        assert!(validate_config("return { me = {} }", "qplug.lua").is_ok());
+    }
+
+    #[test]
+    fn test_validate_config_rejects_wrong_types() {
+        assert_eq!(
+            validate_config("return { build_tool = 'make' }", "qplug.lua"),
+            Err("`build_tool` must be a function, got string".to_string())
+        );
+        assert_eq!(
+            validate_config("return { me = { name = 42 } }", "qplug.lua"),
+            Err("`me.name` must be a string, got integer".to_string())
+        );
+        assert_eq!(
+            validate_config("return { license_header = 'yes' }", "qplug.lua"),
+            Err("`license_header` must be a boolean, got string".to_string())
+        );
+        assert!(validate_config("return { target_qsys = 9.4 }", "qplug.lua").is_err());
+        assert!(validate_config(
+            "return { me = { name = 'Jane' }, license_header = true, target_qsys = '9.4' }",
+            "qplug.lua"
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn test_write_config_refuses_overwrite() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("qplug.lua");
+        fs::write(&path, "return {}").unwrap();
+
+        let err = write_config(&path, "return { me = {} }", false).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "return {}");
+
+        write_config(&path, "return { me = {} }", true).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "return { me = {} }");
+    }
+}
@@ -0,0 +1,880 @@
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    process::exit,
+};
+
+use include_dir::Dir;
+use mlua::{Lua, Table};
+use serde_json::{json, Value};
+
+use crate::{
+    assets::DEFINITIONS_DIR,
+    config::ProjectPaths,
+    files::{find_project_dir, hash, walk_files},
+    lua::{
+        parser::quote_string,
+        plugin::{get_controls, get_properties, load_project, ControlInfo, PropertyInfo},
+        since::{self, annotate_for_target, QsysVersion, TaggedApi},
+    },
+};
+
+use super::new::add_lua_defs;
+
+pub const LUARC_FILE: &str = ".luarc.json";
+/// Plain `.luarc`, for editors that don't look for `.luarc.json`.
+pub const DOT_LUARC_FILE: &str = ".luarc";
+pub const DEFINITIONS: &str = "definitions";
+/// Definitions generated from the project itself, under `definitions/`.
+pub const GENERATED_DIR: &str = "generated";
+
+/// Records which qplug release installed the definitions, and what each file looked like then.
+pub const DEFS_RECORD: &str = ".qplug-defs";
+
+/// Globals Q-SYS hands a plugin that aren't declared in the definitions.
+const QSYS_GLOBALS: [&str; 3] = ["PageNames", "PluginInfo", "Properties"];
+
+fn project_root() -> PathBuf {
+    match find_project_dir(None) {
+        Some(root_path) => root_path,
+        None => {
+            eprintln!("Not a qplug project. Run `qplug new` or `qplug init` first.");
+            exit(1);
+        }
+    }
+}
+
+/// `qplug defs setup`: add the definitions if they're missing and point the language server at
+/// them.
+pub fn setup(force: bool, dot_luarc: bool, paths: &ProjectPaths, target: Option<&QsysVersion>) {
+    let root_path = project_root();
+
+    if root_path.join(DEFINITIONS).exists() {
+        println!("Definitions already present");
+    } else {
+        add_lua_defs(&root_path, target);
+        println!("Definitions initialized");
+    }
+
+    match write_luarc(&root_path, force, dot_luarc) {
+        Ok(written) => {
+            let mut files = vec![LUARC_FILE];
+            if dot_luarc {
+                files.push(DOT_LUARC_FILE);
+            }
+            for file in files.into_iter().map(|file| root_path.join(file)) {
+                if written.contains(&file) {
+                    println!("Wrote {}", file.display());
+                } else {
+                    println!(
+                        "{} already exists, leaving it as is. Use --force to overwrite it.",
+                        file.display()
+                    );
+                }
+            }
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(1);
+        }
+    }
+
+    update_generated(&root_path, paths);
+}
+
+/// `qplug defs update`: bring the project's definitions in line with this qplug release and the
+/// project's `target_qsys`.
+pub fn update(force: bool, target: Option<&QsysVersion>) {
+    let defs_path = project_root().join(DEFINITIONS);
+    if !defs_path.exists() {
+        eprintln!("This project has no definitions. Run `qplug defs setup` to add them.");
+        exit(1);
+    }
+
+    let record = DefsRecord::load(&defs_path).unwrap_or_else(|e| {
+        eprintln!(
+            "Could not read {}: {}",
+            defs_path.join(DEFS_RECORD).display(),
+            e
+        );
+        exit(1);
+    });
+    match &record {
+        Some(record) => println!(
+            "Definitions from qplug {} -> {}",
+            record.version,
+            env!("CARGO_PKG_VERSION")
+        ),
+        None => println!(
+            "No record of which qplug installed these definitions. Files that differ from qplug {} are treated as modified.",
+            env!("CARGO_PKG_VERSION")
+        ),
+    }
+
+    if let Some(target) = target {
+        println!("Marking APIs newer than Q-SYS {} as deprecated.", target);
+    }
+
+    let changes = plan_update(&defs_path, &DEFINITIONS_DIR, target, record.as_ref());
+    if let Err(e) = apply_update(
+        &defs_path,
+        &DEFINITIONS_DIR,
+        target,
+        &changes,
+        record.as_ref(),
+        force,
+    ) {
+        eprintln!("Failed to update definitions: {}", e);
+        exit(1);
+    }
+
+    let mut unchanged = 0;
+    for change in &changes {
+        match change.status {
+            DefStatus::Unchanged => unchanged += 1,
+            DefStatus::Modified if !force => println!(
+                "  kept      {} (modified locally, {} vs bundled; use --force to replace)",
+                change.file, change.diff
+            ),
+            DefStatus::Modified => println!("  replaced  {} ({})", change.file, change.diff),
+            DefStatus::Added => println!("  added     {} ({})", change.file, change.diff),
+            DefStatus::Updated => println!("  updated   {} ({})", change.file, change.diff),
+            DefStatus::Removed => println!("  removed   {}", change.file),
+        }
+    }
+    println!("  unchanged {} files", unchanged);
+}
+
+/// `qplug defs controls`: declare the plugin's own controls for the language server.
+pub fn controls(paths: &ProjectPaths) {
+    let root_path = project_root();
+    match write_control_defs(&root_path, paths) {
+        Ok(file) => println!("Wrote {}", file.display()),
+        Err(e) => {
+            eprintln!("Could not generate control definitions: {}", e);
+            exit(1);
+        }
+    }
+}
+
+/// `qplug defs properties`: declare the plugin's own properties for the language server.
+pub fn properties(paths: &ProjectPaths) {
+    let root_path = project_root();
+    match write_property_defs(&root_path, paths) {
+        Ok(file) => println!("Wrote {}", file.display()),
+        Err(e) => {
+            eprintln!("Could not generate property definitions: {}", e);
+            exit(1);
+        }
+    }
+}
+
+/// Regenerate the project-specific definitions, if the project uses definitions at all. Failures
+/// are reported but never fatal, so a plugin that can't run headless still compiles.
+pub fn update_generated(root_path: &Path, paths: &ProjectPaths) {
+    if !root_path.join(DEFINITIONS).exists() {
+        return;
+    }
+    if let Err(e) = write_control_defs(root_path, paths) {
+        eprintln!("Could not update control definitions: {}", e);
+    }
+    if let Err(e) = write_property_defs(root_path, paths) {
+        eprintln!("Could not update property definitions: {}", e);
+    }
+}
+
+/// Run `GetControls` with the props Designer passes it and write the result to
+/// `definitions/generated/controls.lua`.
+pub fn write_control_defs(
+    root_path: &Path,
+    paths: &ProjectPaths,
+) -> Result<PathBuf, Box<dyn Error>> {
+    let lua = Lua::new();
+    load_project(
+        &lua,
+        &paths.plugin_path(root_path),
+        &paths.entry_path(root_path),
+    )?;
+    let controls = get_controls(&lua)?;
+
+    write_generated(root_path, "controls.lua", &controls_definitions(&controls))
+}
+
+/// Run `GetProperties` and write the result to `definitions/generated/properties.lua`.
+pub fn write_property_defs(
+    root_path: &Path,
+    paths: &ProjectPaths,
+) -> Result<PathBuf, Box<dyn Error>> {
+    let lua = Lua::new();
+    load_project(
+        &lua,
+        &paths.plugin_path(root_path),
+        &paths.entry_path(root_path),
+    )?;
+    let properties = get_properties(&lua)?;
+
+    write_generated(
+        root_path,
+        "properties.lua",
+        &properties_definitions(&properties),
+    )
+}
+
+fn write_generated(
+    root_path: &Path,
+    name: &str,
+    contents: &str,
+) -> Result<PathBuf, Box<dyn Error>> {
+    let dir = root_path.join(DEFINITIONS).join(GENERATED_DIR);
+    fs::create_dir_all(&dir)?;
+    let file = dir.join(name);
+    fs::write(&file, contents)?;
+    Ok(file)
+}
+
+/// LuaLS annotations filling in the `PluginControls` class from the bundled definitions.
+pub fn controls_definitions(controls: &[ControlInfo]) -> String {
+    let mut lines = vec![
+        "---@meta".to_string(),
+        "-- Generated from GetControls by `qplug defs controls`. Changes will be overwritten."
+            .to_string(),
+        String::new(),
+        "---@class PluginControls".to_string(),
+    ];
+
+    for control in controls {
+        let class = match control.control_type.as_str() {
+            "Button" => "ButtonControl",
+            "Knob" => "KnobControl",
+            "Indicator" => "IndicatorControl",
+            "Text" => "TextControl",
+            _ => "Control",
+        };
+        let name = field_name(&control.name);
+        if control.count > 1 {
+            lines.push(format!(
+                "---@field {} {}[] {} {} controls, indexed 1 to {}",
+                name, class, control.count, control.control_type, control.count
+            ));
+        } else {
+            lines.push(format!(
+                "---@field {} {} {} control",
+                name, class, control.control_type
+            ));
+        }
+    }
+
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+/// LuaLS annotations filling in the `PluginProperties` class from the bundled definitions. Enum
+/// properties get an alias of their choices, so comparing against a misspelled choice is flagged.
+pub fn properties_definitions(properties: &[PropertyInfo]) -> String {
+    let mut lines = vec![
+        "---@meta".to_string(),
+        "-- Generated from GetProperties by `qplug defs properties`. Changes will be overwritten."
+            .to_string(),
+        String::new(),
+    ];
+    let mut fields = Vec::new();
+
+    for property in properties {
+        let class = match property.property_type.as_str() {
+            "enum" if !property.choices.is_empty() => {
+                let type_name = type_name(&property.name);
+                let choices: Vec<String> = property
+                    .choices
+                    .iter()
+                    .map(|choice| quote_string(choice))
+                    .collect();
+                lines.push(format!(
+                    "---@alias {}Choice {}",
+                    type_name,
+                    choices.join(" | ")
+                ));
+                lines.push(String::new());
+                lines.push(format!("---@class {}Property : PluginProperty", type_name));
+                lines.push(format!("---@field Value {}Choice", type_name));
+                lines.push(String::new());
+                format!("{}Property", type_name)
+            }
+            "integer" => "IntegerProperty".to_string(),
+            "double" => "DoubleProperty".to_string(),
+            "boolean" => "BooleanProperty".to_string(),
+            "string" | "enum" => "StringProperty".to_string(),
+            _ => "PluginProperty".to_string(),
+        };
+        fields.push(format!(
+            "---@field {} {}",
+            field_name(&property.name),
+            class
+        ));
+    }
+
+    lines.push("---@class PluginProperties".to_string());
+    lines.extend(fields);
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+/// `Debug Print` -> `DebugPrint`, for naming generated types.
+fn type_name(name: &str) -> String {
+    let name: String = name
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            let first = chars.next().unwrap().to_ascii_uppercase();
+            std::iter::once(first).chain(chars).collect::<String>()
+        })
+        .collect();
+    if name.starts_with(|c: char| c.is_ascii_digit()) || name.is_empty() {
+        format!("P{}", name)
+    } else {
+        name
+    }
+}
+
+/// A `---@field` name, quoted when it isn't a plain identifier.
+fn field_name(name: &str) -> String {
+    if is_identifier(name) {
+        name.to_owned()
+    } else {
+        format!("[{}]", quote_string(name))
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DefStatus {
+    /// Same as the bundled copy.
+    Unchanged,
+    /// New in this release.
+    Added,
+    /// Untouched since it was installed, and the bundled copy changed.
+    Updated,
+    /// Edited in the project. Only replaced with `--force`.
+    Modified,
+    /// Untouched since it was installed, and no longer bundled.
+    Removed,
+}
+
+/// Lines added and removed going from the project's copy to the bundled one.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct LineDiff {
+    pub added: usize,
+    pub removed: usize,
+}
+
+impl fmt::Display for LineDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "+{} -{}", self.added, self.removed)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct DefChange {
+    /// Path relative to `definitions/`, with `/` separators.
+    pub file: String,
+    pub status: DefStatus,
+    pub diff: LineDiff,
+}
+
+/// The qplug version the definitions came from and a hash of every file as installed.
+#[derive(Debug, PartialEq)]
+pub struct DefsRecord {
+    pub version: String,
+    pub files: BTreeMap<String, String>,
+}
+
+impl DefsRecord {
+    /// Read `definitions/.qplug-defs`. `None` for projects created before it existed.
+    pub fn load(defs_path: &Path) -> Result<Option<DefsRecord>, Box<dyn Error>> {
+        let path = defs_path.join(DEFS_RECORD);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let lua = Lua::new();
+        let table: Table = lua.load(&fs::read_to_string(&path)?).eval()?;
+        Ok(Some(DefsRecord {
+            version: table.get("version")?,
+            files: table
+                .get::<_, Option<BTreeMap<String, String>>>("files")?
+                .unwrap_or_default(),
+        }))
+    }
+
+    /// Record what `defs_path` holds now, as installed by this qplug release. Files in `kept` were
+    /// edited by the user and keep the hash `previous` had for them, so they still count as edited.
+    pub fn write(defs_path: &Path, previous: Option<&DefsRecord>, kept: &[&str]) -> io::Result<()> {
+        let files: Vec<String> = bundled_files(&DEFINITIONS_DIR, None)
+            .into_keys()
+            .filter_map(|file| {
+                let hash = if kept.contains(&file.as_str()) {
+                    previous?.files.get(&file)?.to_owned()
+                } else {
+                    hash(&fs::read(defs_path.join(&file)).ok()?)
+                };
+                Some(format!("    [{}] = \"{}\",", quote_string(&file), hash))
+            })
+            .collect();
+        fs::write(
+            defs_path.join(DEFS_RECORD),
+            format!(
+                "-- Written by qplug. Tracks which release these definitions came from, so `qplug defs update` can tell your edits apart.\nreturn {{\n  version = {},\n  files = {{\n{}\n  }},\n}}\n",
+                quote_string(env!("CARGO_PKG_VERSION")),
+                files.join("\n")
+            ),
+        )
+    }
+}
+
+/// Compare the project's definitions with `bundled`. Generated and unrecorded files of the
+/// user's own are left out.
+pub fn plan_update(
+    defs_path: &Path,
+    bundled: &Dir,
+    target: Option<&QsysVersion>,
+    record: Option<&DefsRecord>,
+) -> Vec<DefChange> {
+    let bundled = bundled_files(bundled, target);
+    let recorded = |file: &str, contents: &[u8]| {
+        record
+            .and_then(|record| record.files.get(file))
+            .is_some_and(|installed| *installed == hash(contents))
+    };
+
+    let mut changes = Vec::new();
+    for (file, new) in &bundled {
+        let change = match fs::read(defs_path.join(file)) {
+            Err(_) => DefChange {
+                file: file.to_owned(),
+                status: DefStatus::Added,
+                diff: line_diff(b"", new),
+            },
+            Ok(current) if current == *new => DefChange {
+                file: file.to_owned(),
+                status: DefStatus::Unchanged,
+                diff: LineDiff::default(),
+            },
+            Ok(current) => DefChange {
+                file: file.to_owned(),
+                status: if recorded(file, &current) {
+                    DefStatus::Updated
+                } else {
+                    DefStatus::Modified
+                },
+                diff: line_diff(&current, new),
+            },
+        };
+        changes.push(change);
+    }
+
+    // Files an earlier release installed that this one no longer ships.
+    if let Some(record) = record {
+        for file in record.files.keys() {
+            if bundled.contains_key(file) {
+                continue;
+            }
+            let Ok(current) = fs::read(defs_path.join(file)) else {
+                continue;
+            };
+            changes.push(DefChange {
+                file: file.to_owned(),
+                status: if recorded(file, &current) {
+                    DefStatus::Removed
+                } else {
+                    DefStatus::Modified
+                },
+                diff: LineDiff::default(),
+            });
+        }
+    }
+
+    changes
+}
+
+/// Write the planned changes and a fresh record. Locally modified files are only replaced with
+/// `force`.
+pub fn apply_update(
+    defs_path: &Path,
+    bundled: &Dir,
+    target: Option<&QsysVersion>,
+    changes: &[DefChange],
+    record: Option<&DefsRecord>,
+    force: bool,
+) -> io::Result<()> {
+    let bundled = bundled_files(bundled, target);
+    let mut kept = Vec::new();
+    for change in changes {
+        let path = defs_path.join(&change.file);
+        match change.status {
+            DefStatus::Unchanged => {}
+            DefStatus::Modified if !force => kept.push(change.file.as_str()),
+            DefStatus::Removed => fs::remove_file(path)?,
+            DefStatus::Added | DefStatus::Updated | DefStatus::Modified => {
+                // Modified files no longer bundled have nothing to be replaced with.
+                let Some(contents) = bundled.get(&change.file) else {
+                    continue;
+                };
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::write(path, contents)?;
+            }
+        }
+    }
+    DefsRecord::write(defs_path, record, &kept)
+}
+
+/// Every file in `dir`, keyed by its `/`-separated path. With a `target`, APIs newer than it are
+/// marked deprecated.
+fn bundled_files(dir: &Dir, target: Option<&QsysVersion>) -> BTreeMap<String, Vec<u8>> {
+    let mut files = BTreeMap::new();
+    for file in dir.files() {
+        let contents = match (target, file.contents_utf8()) {
+            (Some(target), Some(source)) if file.path().extension().is_some_and(|e| e == "lua") => {
+                annotate_for_target(source, target).into_bytes()
+            }
+            _ => file.contents().to_vec(),
+        };
+        files.insert(file.path().to_string_lossy().replace('\\', "/"), contents);
+    }
+    for sub_dir in dir.dirs() {
+        files.extend(bundled_files(sub_dir, target));
+    }
+    files
+}
+
+/// Copy the bundled definitions to `defs_path`, annotated for `target`, and record where they
+/// came from.
+pub fn install_definitions(defs_path: &Path, target: Option<&QsysVersion>) -> io::Result<()> {
+    for (file, contents) in bundled_files(&DEFINITIONS_DIR, target) {
+        let path = defs_path.join(file);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, contents)?;
+    }
+    DefsRecord::write(defs_path, None, &[])
+}
+
+/// Every API the project's definitions tag with `---@since`. Falls back to the bundled
+/// definitions for projects without their own.
+pub fn tagged_apis(root_path: &Path) -> Vec<TaggedApi> {
+    let defs_path = root_path.join(DEFINITIONS);
+    if !defs_path.exists() {
+        return bundled_files(&DEFINITIONS_DIR, None)
+            .values()
+            .flat_map(|contents| since::tagged_apis(&String::from_utf8_lossy(contents)))
+            .collect();
+    }
+
+    let generated = defs_path.join(GENERATED_DIR);
+    walk_files(&defs_path)
+        .into_iter()
+        .filter(|file| !file.starts_with(&generated))
+        .filter(|file| file.extension().is_some_and(|ext| ext == "lua"))
+        .filter_map(|file| fs::read_to_string(file).ok())
+        .flat_map(|source| since::tagged_apis(&source))
+        .collect()
+}
+
+/// Count the lines added and removed between `old` and `new` (longest common subsequence).
+fn line_diff(old: &[u8], new: &[u8]) -> LineDiff {
+    let old = String::from_utf8_lossy(old);
+    let new = String::from_utf8_lossy(new);
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    let mut lengths = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if old[i] == new[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+    let common = lengths[0][0];
+    LineDiff {
+        added: new.len() - common,
+        removed: old.len() - common,
+    }
+}
+
+/// lua-language-server settings matching the Q-SYS Lua environment.
+pub fn luarc() -> Value {
+    json!({
+        "$schema": "https://raw.githubusercontent.com/LuaLS/vscode-lua/master/setting/schema.json",
+        "runtime.version": "Lua 5.3",
+        // Q-SYS sandboxes plugins, so the file system APIs aren't there. `os` keeps `clock`, `date`,
+        // `difftime` and `time`, so it stays; `qplug lint` flags the rest of it.
+        "runtime.builtin": {
+            "io": "disable",
+        },
+        "workspace.library": [DEFINITIONS],
+        "workspace.checkThirdParty": false,
+        "diagnostics.globals": QSYS_GLOBALS,
+    })
+}
+
+/// Write `.luarc.json` (and `.luarc` when asked) to `root_path` and return the files written.
+/// Existing files are left alone unless `force` is set.
+pub fn write_luarc(root_path: &Path, force: bool, dot_luarc: bool) -> io::Result<Vec<PathBuf>> {
+    let mut files = vec![root_path.join(LUARC_FILE)];
+    if dot_luarc {
+        files.push(root_path.join(DOT_LUARC_FILE));
+    }
+    files.retain(|file| force || !file.exists());
+
+    let contents = serde_json::to_string_pretty(&luarc())? + "\n";
+    for file in &files {
+        fs::write(file, &contents)?;
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{assets::TEMPLATE_DIR, config::Template, files::copy_dir};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_luarc() {
+        let luarc = luarc();
+        assert_eq!(luarc["runtime.version"], "Lua 5.3");
+        assert_eq!(luarc["runtime.builtin"]["io"], "disable");
+        assert!(luarc["runtime.builtin"].get("os").is_none());
+        assert_eq!(luarc["workspace.library"], json!(["definitions"]));
+        assert!(luarc["diagnostics.globals"]
+            .as_array()
+            .unwrap()
+            .contains(&json!("Properties")));
+    }
+
+    #[test]
+    fn test_controls_definitions() {
+        let controls = [
+            ControlInfo {
+                name: "Gain".to_string(),
+                control_type: "Knob".to_string(),
+                count: 4,
+            },
+            ControlInfo {
+                name: "Debug Print".to_string(),
+                control_type: "Text".to_string(),
+                count: 1,
+            },
+        ];
+
+        let defs = controls_definitions(&controls);
+        assert!(defs.starts_with("---@meta\n"));
+        assert!(defs.contains("---@class PluginControls\n"));
+        assert!(defs.contains("---@field Gain KnobControl[] 4 Knob controls, indexed 1 to 4\n"));
+        assert!(defs.contains("---@field [\"Debug Print\"] TextControl Text control\n"));
+    }
+
+    #[test]
+    fn test_properties_definitions() {
+        let properties = [
+            PropertyInfo {
+                name: "Debug Print".to_string(),
+                property_type: "enum".to_string(),
+                choices: vec!["None".to_string(), "Tx/Rx".to_string()],
+            },
+            PropertyInfo {
+                name: "Port".to_string(),
+                property_type: "integer".to_string(),
+                choices: Vec::new(),
+            },
+        ];
+
+        let defs = properties_definitions(&properties);
+        assert!(defs.contains("---@alias DebugPrintChoice \"None\" | \"Tx/Rx\"\n"));
+        assert!(defs.contains(
+            "---@class DebugPrintProperty : PluginProperty\n---@field Value DebugPrintChoice\n"
+        ));
+        assert!(defs.contains(
+            "---@class PluginProperties\n---@field [\"Debug Print\"] DebugPrintProperty\n---@field Port IntegerProperty\n"
+        ));
+    }
+
+    #[test]
+    fn test_type_name() {
+        assert_eq!(type_name("Debug Print"), "DebugPrint");
+        assert_eq!(type_name("tx/rx mode"), "TxRxMode");
+        assert_eq!(type_name("2 Channels"), "P2Channels");
+    }
+
+    #[test]
+    fn test_write_control_defs() {
+        let dir = tempdir().unwrap();
+        let paths = ProjectPaths::default();
+        fs::create_dir_all(paths.plugin_path(dir.path())).unwrap();
+        copy_dir(
+            &Template::InMemoryDir(&TEMPLATE_DIR),
+            &paths.plugin_path(dir.path()),
+        )
+        .unwrap();
+
+        let file = write_control_defs(dir.path(), &paths).unwrap();
+        assert_eq!(file, dir.path().join("definitions/generated/controls.lua"));
+        let defs = fs::read_to_string(file).unwrap();
+        assert!(defs.contains("---@field SendButton ButtonControl Button control\n"));
+
+        let file = write_property_defs(dir.path(), &paths).unwrap();
+        let defs = fs::read_to_string(file).unwrap();
+        assert!(defs.contains("---@field [\"Debug Print\"] DebugPrintProperty\n"));
+    }
+
+    static BUNDLED: Dir = include_dir::include_dir!("$CARGO_MANIFEST_DIR/src/assets/definitions");
+
+    fn install(dir: &Path) {
+        fs::create_dir_all(dir).unwrap();
+        copy_dir(&Template::InMemoryDir(&BUNDLED), dir).unwrap();
+        DefsRecord::write(dir, None, &[]).unwrap();
+    }
+
+    fn status(changes: &[DefChange], file: &str) -> DefStatus {
+        changes.iter().find(|c| c.file == file).unwrap().status
+    }
+
+    #[test]
+    fn test_plan_update() {
+        let dir = tempdir().unwrap();
+        install(dir.path());
+        let mut record = DefsRecord::load(dir.path()).unwrap().unwrap();
+        assert_eq!(record.version, env!("CARGO_PKG_VERSION"));
+
+        // timer.lua as an older release shipped it, controls.lua edited by the user, one file
+        // dropped from the bundle since, and json.lua deleted by the user.
+        fs::write(dir.path().join("timer.lua"), "-- old timer\n").unwrap();
+        record
+            .files
+            .insert("timer.lua".to_string(), hash(b"-- old timer\n"));
+        fs::write(dir.path().join("controls.lua"), "-- mine\n").unwrap();
+        fs::write(dir.path().join("gone.lua"), "-- gone\n").unwrap();
+        record
+            .files
+            .insert("gone.lua".to_string(), hash(b"-- gone\n"));
+        fs::remove_file(dir.path().join("json.lua")).unwrap();
+
+        let changes = plan_update(dir.path(), &BUNDLED, None, Some(&record));
+        assert_eq!(status(&changes, "timer.lua"), DefStatus::Updated);
+        assert_eq!(status(&changes, "controls.lua"), DefStatus::Modified);
+        assert_eq!(status(&changes, "gone.lua"), DefStatus::Removed);
+        assert_eq!(status(&changes, "json.lua"), DefStatus::Added);
+        assert_eq!(status(&changes, "common.lua"), DefStatus::Unchanged);
+
+        apply_update(dir.path(), &BUNDLED, None, &changes, Some(&record), false).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.path().join("controls.lua")).unwrap(),
+            "-- mine\n"
+        );
+        assert!(!dir.path().join("gone.lua").exists());
+        assert!(dir.path().join("json.lua").exists());
+        assert_ne!(
+            fs::read_to_string(dir.path().join("timer.lua")).unwrap(),
+            "-- old timer\n"
+        );
+
+        // The user's edit is still theirs after the update, until --force.
+        let record = DefsRecord::load(dir.path()).unwrap();
+        let changes = plan_update(dir.path(), &BUNDLED, None, record.as_ref());
+        assert_eq!(status(&changes, "controls.lua"), DefStatus::Modified);
+        apply_update(dir.path(), &BUNDLED, None, &changes, record.as_ref(), true).unwrap();
+        assert_ne!(
+            fs::read_to_string(dir.path().join("controls.lua")).unwrap(),
+            "-- mine\n"
+        );
+    }
+
+    #[test]
+    fn test_plan_update_without_record() {
+        let dir = tempdir().unwrap();
+        install(dir.path());
+        fs::remove_file(dir.path().join(DEFS_RECORD)).unwrap();
+        fs::write(dir.path().join("timer.lua"), "-- old timer\n").unwrap();
+
+        let changes = plan_update(dir.path(), &BUNDLED, None, None);
+        assert_eq!(status(&changes, "timer.lua"), DefStatus::Modified);
+    }
+
+    #[test]
+    fn test_install_definitions_for_target() {
+        let dir = tempdir().unwrap();
+        let defs_path = dir.path().join(DEFINITIONS);
+        let target: QsysVersion = "9.2".parse().unwrap();
+        fs::create_dir(&defs_path).unwrap();
+        install_definitions(&defs_path, Some(&target)).unwrap();
+
+        let websocket = fs::read_to_string(defs_path.join("websocket.lua")).unwrap();
+        assert!(websocket.contains(
+            "---@deprecated Added in Q-SYS 9.4. This project targets 9.2.\nWebSocket = {}"
+        ));
+
+        // Annotated copies count as installed, not as edits.
+        let record = DefsRecord::load(&defs_path).unwrap();
+        let changes = plan_update(&defs_path, &BUNDLED, Some(&target), record.as_ref());
+        assert!(changes.iter().all(|c| c.status == DefStatus::Unchanged));
+
+        // Moving the target updates them.
+        let target: QsysVersion = "9.4".parse().unwrap();
+        let changes = plan_update(&defs_path, &BUNDLED, Some(&target), record.as_ref());
+        assert_eq!(status(&changes, "websocket.lua"), DefStatus::Updated);
+
+        let apis = tagged_apis(dir.path());
+        assert!(apis.iter().any(|api| api.name == "WebSocket"));
+    }
+
+    #[test]
+    fn test_line_diff() {
+        assert_eq!(
+            line_diff(b"a\nb\nc\n", b"a\nc\nd\ne\n"),
+            LineDiff {
+                added: 2,
+                removed: 1
+            }
+        );
+        assert_eq!(line_diff(b"", b"a\n").to_string(), "+1 -0");
+    }
+
+    #[test]
+    fn test_write_luarc() {
+        let dir = tempdir().unwrap();
+
+        let files = write_luarc(dir.path(), false, true).unwrap();
+        assert_eq!(
+            files,
+            [dir.path().join(LUARC_FILE), dir.path().join(DOT_LUARC_FILE)]
+        );
+        let written: Value =
+            serde_json::from_str(&fs::read_to_string(dir.path().join(LUARC_FILE)).unwrap())
+                .unwrap();
+        assert_eq!(written, luarc());
+
+        fs::write(dir.path().join(LUARC_FILE), "{}").unwrap();
+        assert!(write_luarc(dir.path(), false, false).unwrap().is_empty());
+        assert_eq!(
+            fs::read_to_string(dir.path().join(LUARC_FILE)).unwrap(),
+            "{}"
+        );
+        assert_eq!(
+            write_luarc(dir.path(), true, false).unwrap(),
+            [dir.path().join(LUARC_FILE)]
+        );
+    }
+}
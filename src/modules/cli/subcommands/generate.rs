@@ -0,0 +1,791 @@
+use std::{
+    error::Error,
+    fs, io,
+    path::{Path, PathBuf},
+    process::exit,
+};
+
+use clap::ValueEnum;
+use regex::Regex;
+
+use crate::{
+    config::ProjectPaths,
+    files::{find_project_dir, walk_files},
+    lua::parser::quote_string,
+};
+
+/// Space between a generated control and what's above it, and from the page's left edge.
+const LAYOUT_MARGIN: i64 = 10;
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+#[clap(rename_all = "lower")]
+pub enum ControlKind {
+    Button,
+    Knob,
+    Indicator,
+    Text,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+#[clap(rename_all = "lower")]
+pub enum PropertyKind {
+    String,
+    Integer,
+    Double,
+    Boolean,
+    Enum,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+#[clap(rename_all = "lower")]
+pub enum PinDirection {
+    Input,
+    Output,
+}
+
+pub struct ControlSpec {
+    pub name: String,
+    pub kind: ControlKind,
+    pub count: u32,
+    /// Lay the control out on this page. Defaults to the first page.
+    pub page: Option<String>,
+    pub layout: bool,
+    pub handler: bool,
+}
+
+pub enum Generator {
+    Control(ControlSpec),
+    Property {
+        name: String,
+        kind: PropertyKind,
+        choices: Vec<String>,
+    },
+    Pin {
+        name: String,
+        direction: PinDirection,
+    },
+    Page(String),
+    Module(String),
+}
+
+pub fn generate(generator: Generator, paths: &ProjectPaths) {
+    let Some(root_path) = find_project_dir(None) else {
+        eprintln!(
+            "No plugin found. Please create a plugin first or navigate to a plugin directory."
+        );
+        exit(1);
+    };
+    let plugin_path = paths.plugin_path(&root_path);
+    let entry_path = paths.entry_path(&root_path);
+
+    let result = match generator {
+        Generator::Control(spec) => control(&plugin_path, &spec),
+        Generator::Property {
+            name,
+            kind,
+            choices,
+        } => property(&plugin_path, &name, kind, &choices),
+        Generator::Pin { name, direction } => pin(&plugin_path, &name, direction),
+        Generator::Page(name) => page(&plugin_path, &name),
+        Generator::Module(name) => module(&plugin_path, &entry_path, &name),
+    };
+
+    match result {
+        Ok(files) => {
+            for file in files {
+                println!("Updated {}", file.display());
+            }
+        }
+        Err(e) => {
+            eprintln!("Failed to generate: {}", e);
+            exit(1);
+        }
+    }
+}
+
+/// Add a control to `GetControls`, and optionally lay it out and stub its `EventHandler`.
+pub fn control(plugin_path: &Path, spec: &ControlSpec) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let name = quote_string(&spec.name);
+    let count = spec.count.max(1);
+
+    let mut fields = vec![("Name", name.to_owned())];
+    fields.extend(match spec.kind {
+        ControlKind::Button => vec![
+            ("ControlType", r#""Button""#.to_string()),
+            ("ButtonType", r#""Toggle""#.to_string()),
+        ],
+        ControlKind::Knob => vec![
+            ("ControlType", r#""Knob""#.to_string()),
+            ("ControlUnit", r#""Integer""#.to_string()),
+            ("Min", "0".to_string()),
+            ("Max", "100".to_string()),
+        ],
+        ControlKind::Indicator => vec![
+            ("ControlType", r#""Indicator""#.to_string()),
+            ("IndicatorType", r#""Led""#.to_string()),
+        ],
+        ControlKind::Text => vec![("ControlType", r#""Text""#.to_string())],
+    });
+    fields.push(("Count", count.to_string()));
+    fields.push(("UserPin", "true".to_string()));
+    fields.push((
+        "PinStyle",
+        match spec.kind {
+            ControlKind::Indicator => r#""Output""#,
+            _ => r#""Both""#,
+        }
+        .to_string(),
+    ));
+
+    // Nothing is written until every edit has worked, so a bad `--page` leaves no trace.
+    let mut edits = Edits::default();
+    insert_entry(&mut edits, plugin_path, "GetControls", &spec.name, &fields)?;
+
+    if spec.layout {
+        insert_layout(&mut edits, plugin_path, spec)?;
+    }
+
+    if spec.handler {
+        let runtime = walk_files(plugin_path)
+            .into_iter()
+            .find(|f| f.file_name().is_some_and(|n| n == "runtime.lua"))
+            .ok_or("Could not find runtime.lua")?;
+        let handler = if count == 1 {
+            format!(
+                "\nif Controls then\n\tControls[{name}].EventHandler = function(ctl)\n\t\t-- TODO: Handle {}\n\tend\nend\n",
+                spec.name
+            )
+        } else {
+            format!(
+                "\nif Controls then\n\tfor i, ctl in ipairs(Controls[{name}]) do\n\t\tctl.EventHandler = function()\n\t\t\t-- TODO: Handle {} i\n\t\tend\n\tend\nend\n",
+                spec.name
+            )
+        };
+        let mut source = edits.read(&runtime)?;
+        if !source.ends_with('\n') {
+            source.push('\n');
+        }
+        source.push_str(&handler);
+        edits.set(runtime, source);
+    }
+
+    Ok(edits.write()?)
+}
+
+/// Add a property to `GetProperties`.
+pub fn property(
+    plugin_path: &Path,
+    name: &str,
+    kind: PropertyKind,
+    choices: &[String],
+) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut fields = vec![("Name", quote_string(name))];
+    match kind {
+        PropertyKind::String => {
+            fields.push(("Type", r#""string""#.to_string()));
+            fields.push(("Value", r#""""#.to_string()));
+        }
+        PropertyKind::Integer | PropertyKind::Double => {
+            let type_name = if kind == PropertyKind::Integer {
+                "integer"
+            } else {
+                "double"
+            };
+            fields.push(("Type", quote_string(type_name)));
+            fields.push(("Min", "0".to_string()));
+            fields.push(("Max", "100".to_string()));
+            fields.push(("Value", "0".to_string()));
+        }
+        PropertyKind::Boolean => {
+            fields.push(("Type", r#""boolean""#.to_string()));
+            fields.push(("Value", "false".to_string()));
+        }
+        PropertyKind::Enum => {
+            if choices.is_empty() {
+                return Err("enum properties need at least one choice (--choices a,b,c)".into());
+            }
+            let quoted: Vec<String> = choices.iter().map(|c| quote_string(c)).collect();
+            fields.push(("Type", r#""enum""#.to_string()));
+            fields.push(("Choices", format!("{{ {} }}", quoted.join(", "))));
+            fields.push(("Value", quoted[0].to_owned()));
+        }
+    }
+
+    let mut edits = Edits::default();
+    insert_entry(&mut edits, plugin_path, "GetProperties", name, &fields)?;
+    Ok(edits.write()?)
+}
+
+/// Add a pin to `GetPins`.
+pub fn pin(
+    plugin_path: &Path,
+    name: &str,
+    direction: PinDirection,
+) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let direction = match direction {
+        PinDirection::Input => "input",
+        PinDirection::Output => "output",
+    };
+    let fields = vec![
+        ("Name", quote_string(name)),
+        ("Direction", quote_string(direction)),
+    ];
+    let mut edits = Edits::default();
+    insert_entry(&mut edits, plugin_path, "GetPins", name, &fields)?;
+    Ok(edits.write()?)
+}
+
+/// Add a page to `PageNames` and an empty branch for it in `GetControlLayout`.
+pub fn page(plugin_path: &Path, name: &str) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let page_names = Regex::new(r"PageNames\s*=\s*\{([^}]*)\}").unwrap();
+    let file = walk_files(plugin_path)
+        .into_iter()
+        .filter(|f| f.extension().is_some_and(|ext| ext == "lua"))
+        .find(|f| {
+            fs::read_to_string(f)
+                .map(|s| page_names.is_match(&s))
+                .unwrap_or(false)
+        })
+        .ok_or("Could not find PageNames")?;
+
+    let source = fs::read_to_string(&file)?;
+    let captures = page_names.captures(&source).unwrap();
+    let existing = captures[1].trim().trim_end_matches(',').trim();
+    if existing.contains(&quote_string(name)) {
+        return Err(format!("page {} already exists", quote_string(name)).into());
+    }
+    let pages = if existing.is_empty() {
+        quote_string(name)
+    } else {
+        format!("{}, {}", existing, quote_string(name))
+    };
+    let updated_source =
+        page_names.replace(&source, format!("PageNames = {{ {} }}", pages).as_str());
+    let mut edits = Edits::default();
+    edits.set(file, updated_source.into_owned());
+
+    // Add `elseif CurrentPage == "<name>" then` to the end of the page chain, if there is one.
+    let (layout_file, layout_source) = find_function(&edits, plugin_path, "GetControlLayout")?;
+    let mut lines: Vec<String> = layout_source.lines().map(String::from).collect();
+    let (start, end) =
+        function_range(&lines, "GetControlLayout").ok_or("Malformed GetControlLayout")?;
+    let branch = Regex::new(r#"^(\s*)(if|elseif)\s+CurrentPage\s*=="#).unwrap();
+    if let Some((last, indent)) = (start..end)
+        .rev()
+        .find_map(|i| branch.captures(&lines[i]).map(|c| (i, c[1].to_string())))
+    {
+        if let Some(close) =
+            (last + 1..end).find(|&i| lines[i].trim() == "end" && leading(&lines[i]) == indent)
+        {
+            lines.insert(close, format!("{}\t-- TBD", indent));
+            lines.insert(
+                close,
+                format!(
+                    "{}elseif CurrentPage == {} then",
+                    indent,
+                    quote_string(name)
+                ),
+            );
+            edits.set_lines(layout_file, &lines, &layout_source);
+        }
+    }
+
+    Ok(edits.write()?)
+}
+
+/// Create `plugin_src/<name>.lua` and require it from `init.lua`.
+pub fn module(
+    plugin_path: &Path,
+    init_path: &Path,
+    name: &str,
+) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let module = name.trim_end_matches(".lua").replace('/', ".");
+    let valid = Regex::new(r"^[A-Za-z_][A-Za-z0-9_]*(\.[A-Za-z_][A-Za-z0-9_]*)*$").unwrap();
+    if !valid.is_match(&module) {
+        return Err(format!("`{}` is not a valid module name", name).into());
+    }
+
+    let module_path = plugin_path.join(format!("{}.lua", module.replace('.', "/")));
+    if module_path.exists() {
+        return Err(format!("{} already exists", module_path.display()).into());
+    }
+    let init_source = fs::read_to_string(init_path)?;
+    let mut edits = Edits::default();
+    edits.set(module_path, format!("-- {}\n", module));
+
+    // The runtime has to stay last, so new modules go right before it.
+    let require = format!("require({})", quote_string(&module));
+    let mut lines: Vec<String> = init_source.lines().map(String::from).collect();
+    let runtime = Regex::new(r#"^\s*require\(\s*["']runtime\.runtime["']\s*\)"#).unwrap();
+    match lines.iter().position(|line| runtime.is_match(line)) {
+        Some(i) => {
+            lines.insert(i, String::new());
+            lines.insert(i, require);
+        }
+        None => lines.push(require),
+    }
+    edits.set_lines(init_path.to_path_buf(), &lines, &init_source);
+
+    Ok(edits.write()?)
+}
+
+/// Insert `table.insert(<list>, { ... })` right before the final `return <list>` of `function`.
+fn insert_entry(
+    edits: &mut Edits,
+    plugin_path: &Path,
+    function: &str,
+    name: &str,
+    fields: &[(&str, String)],
+) -> Result<(), Box<dyn Error>> {
+    let (file, source) = find_function(edits, plugin_path, function)?;
+    let mut lines: Vec<String> = source.lines().map(String::from).collect();
+    let (start, end) =
+        function_range(&lines, function).ok_or(format!("Malformed function {}", function))?;
+
+    let duplicate = Regex::new(&format!(
+        r"Name\s*=\s*{}",
+        regex::escape(&quote_string(name))
+    ))
+    .unwrap();
+    if lines[start..end]
+        .iter()
+        .any(|line| duplicate.is_match(line))
+    {
+        return Err(format!(
+            "{} already has an entry named {}",
+            function,
+            quote_string(name)
+        )
+        .into());
+    }
+
+    let (position, indent, list) = return_statement(&lines, start, end)
+        .ok_or(format!("{} does not return a table variable", function))?;
+
+    let mut entry = vec![format!("{}table.insert({}, {{", indent, list)];
+    entry.extend(
+        fields
+            .iter()
+            .map(|(key, value)| format!("{}\t{} = {},", indent, key, value)),
+    );
+    entry.push(format!("{}}})", indent));
+    lines.splice(position..position, entry);
+
+    edits.set_lines(file, &lines, &source);
+    Ok(())
+}
+
+/// Lay the control out on `spec.page`, or the first page, below what's already on it. Without a
+/// page chain in `GetControlLayout` the whole function is the page.
+fn insert_layout(
+    edits: &mut Edits,
+    plugin_path: &Path,
+    spec: &ControlSpec,
+) -> Result<(), Box<dyn Error>> {
+    let (file, source) = find_function(edits, plugin_path, "GetControlLayout")?;
+    let mut lines: Vec<String> = source.lines().map(String::from).collect();
+    let (start, end) =
+        function_range(&lines, "GetControlLayout").ok_or("Malformed GetControlLayout")?;
+    let (return_position, return_indent, layout) =
+        return_statement(&lines, start, end).ok_or("GetControlLayout does not return a layout")?;
+
+    let page = match &spec.page {
+        Some(page) => regex::escape(&quote_string(page)),
+        None => r#"(?:"[^"]*"|'[^']*')"#.to_string(),
+    };
+    let branch = Regex::new(&format!(
+        r#"^(\s*)(if|elseif)\s+CurrentPage\s*==\s*{}\s+then"#,
+        page
+    ))
+    .unwrap();
+    let branch_end = Regex::new(r"^(elseif|else|end)\b").unwrap();
+    let found =
+        (start..end).find_map(|i| branch.captures(&lines[i]).map(|c| (i, c[1].to_string())));
+    let (body, position, indent) = match (found, &spec.page) {
+        (Some((open, indent)), _) => {
+            // The branch runs to the next `elseif`, `else` or `end` at its own indentation.
+            let close = (open + 1..end)
+                .find(|&i| leading(&lines[i]) == indent && branch_end.is_match(lines[i].trim()))
+                .ok_or("Malformed page branch in GetControlLayout")?;
+            (open + 1..close, close, format!("{}\t", indent))
+        }
+        (None, Some(page)) => {
+            return Err(format!("GetControlLayout has no page named {}", quote_string(page)).into())
+        }
+        (None, None) => (start + 1..return_position, return_position, return_indent),
+    };
+    let top = match lowest_edge(&lines[body]) {
+        0 => LAYOUT_MARGIN,
+        lowest => lowest + LAYOUT_MARGIN,
+    };
+
+    let (style, (width, height)) = match spec.kind {
+        ControlKind::Button => ("Button", (36, 16)),
+        ControlKind::Knob => ("Knob", (36, 36)),
+        ControlKind::Indicator => ("Led", (16, 16)),
+        ControlKind::Text => ("Text", (100, 16)),
+    };
+    let name = quote_string(&spec.name);
+    let entry = if spec.count <= 1 {
+        vec![
+            format!("{}{}[{}] = {{", indent, layout, name),
+            format!("{}\tPrettyName = {},", indent, name),
+            format!("{}\tStyle = {},", indent, quote_string(style)),
+            format!("{}\tPosition = {{ {}, {} }},", indent, LAYOUT_MARGIN, top),
+            format!("{}\tSize = {{ {}, {} }},", indent, width, height),
+            format!("{}}}", indent),
+        ]
+    } else {
+        // Controls with a Count are laid out as "<Name> 1", "<Name> 2", ...
+        vec![
+            format!("{}for i = 1, {} do", indent, spec.count),
+            format!(
+                "{}\t{}[{} .. i] = {{",
+                indent,
+                layout,
+                quote_string(&format!("{} ", spec.name))
+            ),
+            format!(
+                "{}\t\tPrettyName = {} .. i,",
+                indent,
+                quote_string(&format!("{}~", spec.name))
+            ),
+            format!("{}\t\tStyle = {},", indent, quote_string(style)),
+            format!(
+                "{}\t\tPosition = {{ {}, {} + (i - 1) * {} }},",
+                indent,
+                LAYOUT_MARGIN,
+                top,
+                height + 4
+            ),
+            format!("{}\t\tSize = {{ {}, {} }},", indent, width, height),
+            format!("{}\t}}", indent),
+            format!("{}end", indent),
+        ]
+    };
+    lines.splice(position..position, entry);
+
+    edits.set_lines(file, &lines, &source);
+    Ok(())
+}
+
+/// The bottom edge of the lowest thing laid out in `lines`, or 0 if there's nothing. Reads literal
+/// `Position` and `Size` pairs, and the `y + (i - 1) * step` rows written for a control with a
+/// `Count`.
+fn lowest_edge(lines: &[String]) -> i64 {
+    let position = Regex::new(
+        r"Position\s*=\s*\{\s*-?\d+\s*,\s*(-?\d+)(?:\s*\+\s*\(i\s*-\s*1\)\s*\*\s*(\d+))?\s*\}",
+    )
+    .unwrap();
+    let size = Regex::new(r"Size\s*=\s*\{\s*-?\d+\s*,\s*(\d+)\s*\}").unwrap();
+    let rows = Regex::new(r"^\s*for\s+i\s*=\s*1\s*,\s*(\d+)\s+do").unwrap();
+
+    let (mut lowest, mut count) = (0, 1);
+    let (mut top, mut height) = (None, None);
+    for line in lines {
+        if let Some(c) = rows.captures(line) {
+            count = c[1].parse().unwrap_or(1);
+        }
+        if let Some(c) = position.captures(line) {
+            let y: i64 = c[1].parse().unwrap_or(0);
+            let step: i64 = c.get(2).map_or(0, |m| m.as_str().parse().unwrap_or(0));
+            top = Some(y + step * (count - 1));
+        }
+        if let Some(c) = size.captures(line) {
+            height = c[1].parse::<i64>().ok();
+        }
+        if let (Some(y), Some(h)) = (top, height) {
+            lowest = lowest.max(y + h);
+            (top, height) = (None, None);
+        }
+        // A table closed without both; don't pair its Position with the next one's Size.
+        if line.trim_start().starts_with('}') {
+            (top, height) = (None, None);
+        }
+    }
+    lowest
+}
+
+/// The Lua file under `plugin_path` that defines `function`, with `edits` made so far.
+fn find_function(
+    edits: &Edits,
+    plugin_path: &Path,
+    function: &str,
+) -> Result<(PathBuf, String), Box<dyn Error>> {
+    let definition = Regex::new(&format!(r"(?m)^\s*function\s+{}\s*\(", function)).unwrap();
+    walk_files(plugin_path)
+        .into_iter()
+        .filter(|f| f.extension().is_some_and(|ext| ext == "lua"))
+        .find_map(|f| {
+            edits
+                .read(&f)
+                .ok()
+                .filter(|s| definition.is_match(s))
+                .map(|s| (f, s))
+        })
+        .ok_or_else(|| {
+            format!(
+                "Could not find function {} in {}",
+                function,
+                plugin_path.display()
+            )
+            .into()
+        })
+}
+
+/// Line indices of `function <name>(` and its closing `end` (matched by indentation).
+fn function_range(lines: &[String], function: &str) -> Option<(usize, usize)> {
+    let definition = Regex::new(&format!(r"^\s*function\s+{}\s*\(", function)).unwrap();
+    let start = lines.iter().position(|line| definition.is_match(line))?;
+    let indent = leading(&lines[start]);
+    let end = (start + 1..lines.len())
+        .find(|&i| lines[i].trim() == "end" && leading(&lines[i]) == indent)?;
+    Some((start, end))
+}
+
+/// The last `return <variable>` in a function: its line, indentation and variable name.
+fn return_statement(lines: &[String], start: usize, end: usize) -> Option<(usize, String, String)> {
+    let statement = Regex::new(r"^(\s*)return\s+([A-Za-z_][A-Za-z0-9_]*)").unwrap();
+    (start..end).rev().find_map(|i| {
+        statement
+            .captures(&lines[i])
+            .map(|c| (i, c[1].to_string(), c[2].to_string()))
+    })
+}
+
+fn leading(line: &str) -> String {
+    line.chars().take_while(|c| c.is_whitespace()).collect()
+}
+
+/// Changes to the plugin's files, kept in memory until every one has been made, so a generator
+/// that fails part way leaves the plugin as it was.
+#[derive(Default)]
+struct Edits {
+    files: Vec<(PathBuf, String)>,
+}
+
+impl Edits {
+    /// `file` with the edits made to it so far.
+    fn read(&self, file: &Path) -> io::Result<String> {
+        match self.files.iter().find(|(path, _)| path == file) {
+            Some((_, contents)) => Ok(contents.clone()),
+            None => fs::read_to_string(file),
+        }
+    }
+
+    fn set(&mut self, file: PathBuf, contents: String) {
+        match self.files.iter_mut().find(|(path, _)| *path == file) {
+            Some((_, existing)) => *existing = contents,
+            None => self.files.push((file, contents)),
+        }
+    }
+
+    /// `set` the file to `lines`, ending with a newline if `original` did.
+    fn set_lines(&mut self, file: PathBuf, lines: &[String], original: &str) {
+        let mut contents = lines.join("\n");
+        if original.ends_with('\n') {
+            contents.push('\n');
+        }
+        self.set(file, contents);
+    }
+
+    /// Write every edited file and return their paths.
+    fn write(self) -> io::Result<Vec<PathBuf>> {
+        for (file, contents) in &self.files {
+            if let Some(parent) = file.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(file, contents)?;
+        }
+        Ok(self.files.into_iter().map(|(file, _)| file).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{assets::TEMPLATE_DIR, config::Template, files::copy_dir};
+    use mlua::{Lua, Table};
+    use tempfile::{tempdir, TempDir};
+
+    fn template() -> TempDir {
+        let dir = tempdir().unwrap();
+        copy_dir(&Template::InMemoryDir(&TEMPLATE_DIR), dir.path()).unwrap();
+        dir
+    }
+
+    /// Load every module the generators touch so the result is checked by Lua itself.
+    fn load(dir: &Path) -> Lua {
+        let lua = Lua::new();
+        for module in [
+            "setup/pages.lua",
+            "properties/properties.lua",
+            "properties/pins.lua",
+            "control_components/controls.lua",
+            "layout/layout.lua",
+            "runtime/runtime.lua",
+        ] {
+            lua.load(fs::read_to_string(dir.join(module)).unwrap())
+                .exec()
+                .unwrap();
+        }
+        lua
+    }
+
+    fn names(lua: &Lua, function: &str) -> Vec<String> {
+        let entries: Vec<Table> = lua
+            .load(format!("return {}({{}})", function))
+            .eval()
+            .unwrap();
+        entries
+            .into_iter()
+            .map(|t| t.get::<_, String>("Name").unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_generate_control() {
+        let dir = template();
+        let spec = ControlSpec {
+            name: "Volume".to_string(),
+            kind: ControlKind::Knob,
+            count: 2,
+            page: Some("Control".to_string()),
+            layout: true,
+            handler: true,
+        };
+        control(dir.path(), &spec).unwrap();
+
+        let lua = load(dir.path());
+        assert_eq!(names(&lua, "GetControls"), vec!["SendButton", "Volume"]);
+
+        let layout: Table = lua
+            .load(r#"return GetControlLayout({ page_index = { Value = 1 } })"#)
+            .eval()
+            .unwrap();
+        assert!(layout.contains_key("Volume 2").unwrap());
+
+        let runtime = fs::read_to_string(dir.path().join("runtime/runtime.lua")).unwrap();
+        assert!(runtime.contains(r#"ipairs(Controls["Volume"])"#));
+
+        // Adding the same control twice is refused.
+        assert!(control(dir.path(), &spec).is_err());
+    }
+
+    #[test]
+    fn test_generate_control_on_first_page() {
+        let dir = template();
+        let spec = |name: &str, count| ControlSpec {
+            name: name.to_string(),
+            kind: ControlKind::Knob,
+            count,
+            page: None,
+            layout: true,
+            handler: false,
+        };
+        control(dir.path(), &spec("Volume", 2)).unwrap();
+        control(dir.path(), &spec("Trim", 1)).unwrap();
+
+        let lua = load(dir.path());
+        let position = |page: usize, name: &str| -> Option<Vec<i64>> {
+            lua.load(format!(
+                "local layout = GetControlLayout({{ page_index = {{ Value = {} }} }})
+                local entry = layout[{}]
+                return entry and entry.Position",
+                page,
+                quote_string(name)
+            ))
+            .eval::<Option<Vec<i64>>>()
+            .unwrap()
+        };
+        // Below the template's GroupBox, which ends at 105, and then below each other.
+        assert_eq!(position(1, "Volume 1"), Some(vec![10, 115]));
+        assert_eq!(position(1, "Volume 2"), Some(vec![10, 155]));
+        assert_eq!(position(1, "Trim"), Some(vec![10, 201]));
+        assert_eq!(position(2, "Volume 1"), None);
+    }
+
+    #[test]
+    fn test_generate_control_leaves_files_on_error() {
+        let dir = template();
+        let controls = dir.path().join("control_components/controls.lua");
+        let before = fs::read_to_string(&controls).unwrap();
+        let spec = ControlSpec {
+            name: "Bar".to_string(),
+            kind: ControlKind::Button,
+            count: 1,
+            page: Some("Nope".to_string()),
+            layout: true,
+            handler: true,
+        };
+        assert!(control(dir.path(), &spec).is_err());
+        assert_eq!(fs::read_to_string(&controls).unwrap(), before);
+    }
+
+    #[test]
+    fn test_lowest_edge() {
+        let lines: Vec<String> = [
+            "table.insert(graphics, { Type = \"GroupBox\", Position = { 5, 5 }, Size = { 200, 100 } })",
+            "for i = 1, 3 do",
+            "\tlayout[\"Gain \" .. i] = {",
+            "\t\tPosition = { 10, 115 + (i - 1) * 40 },",
+            "\t\tSize = { 36, 36 },",
+            "\t}",
+            "end",
+            "layout.Mute = { Size = { 36, 16 } }",
+        ]
+        .iter()
+        .map(|line| line.to_string())
+        .collect();
+        assert_eq!(lowest_edge(&lines), 231);
+        assert_eq!(lowest_edge(&lines[..1]), 105);
+        assert_eq!(lowest_edge(&[]), 0);
+    }
+
+    #[test]
+    fn test_generate_property_and_pin() {
+        let dir = template();
+        property(
+            dir.path(),
+            "Model",
+            PropertyKind::Enum,
+            &["A".to_string(), "B".to_string()],
+        )
+        .unwrap();
+        pin(dir.path(), "Serial In", PinDirection::Input).unwrap();
+
+        let lua = load(dir.path());
+        assert_eq!(names(&lua, "GetProperties"), vec!["Debug Print", "Model"]);
+        assert_eq!(names(&lua, "GetPins"), vec!["Audio Output", "Serial In"]);
+        assert!(property(dir.path(), "Bad", PropertyKind::Enum, &[]).is_err());
+    }
+
+    #[test]
+    fn test_generate_page() {
+        let dir = template();
+        page(dir.path(), "Status").unwrap();
+
+        let lua = load(dir.path());
+        let pages: Vec<String> = lua.load("return PageNames").eval().unwrap();
+        assert_eq!(pages, vec!["Control", "Setup", "Status"]);
+
+        let layout = fs::read_to_string(dir.path().join("layout/layout.lua")).unwrap();
+        assert!(layout.contains(r#"elseif CurrentPage == "Status" then"#));
+        assert!(page(dir.path(), "Status").is_err());
+    }
+
+    #[test]
+    fn test_generate_module() {
+        let dir = template();
+        let init_path = dir.path().join("init.lua");
+        let updated = module(dir.path(), &init_path, "network.tcp").unwrap();
+        assert_eq!(updated[0], dir.path().join("network/tcp.lua"));
+
+        let init = fs::read_to_string(dir.path().join("init.lua")).unwrap();
+        let require = init.find(r#"require("network.tcp")"#).unwrap();
+        assert!(require < init.find(r#"require("runtime.runtime")"#).unwrap());
+
+        assert!(module(dir.path(), &init_path, "network.tcp").is_err());
+        assert!(module(dir.path(), &init_path, "../escape").is_err());
+    }
+}
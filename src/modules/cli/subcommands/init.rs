@@ -0,0 +1,239 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::exit,
+};
+
+use crate::{
+    config::ProjectPaths,
+    files::{write_marker_file, MARKER_FILE},
+    lua::{parser::quote_string, since::QsysVersion},
+};
+
+use super::{
+    defs::{write_luarc, DEFINITIONS, LUARC_FILE},
+    new::add_lua_defs,
+};
+
+/// Directories never searched for plugin sources.
+const SKIPPED_DIRS: [&str; 3] = ["definitions", "node_modules", "target"];
+
+/// How deep to look for an `init.lua` below the project root.
+const SEARCH_DEPTH: usize = 2;
+
+/// The source layout found in an existing plugin directory.
+#[derive(Debug, PartialEq)]
+pub struct Layout {
+    pub paths: ProjectPaths,
+    /// Location of `info.lua`, if the plugin has one.
+    pub info: Option<PathBuf>,
+}
+
+/// Adopt an existing plugin at `path` (or the current directory) without touching its sources.
+pub fn init(path: Option<&PathBuf>, defs: bool, luarc: bool, target: Option<&QsysVersion>) {
+    let root_path = match path {
+        Some(path) => path.to_owned(),
+        None => std::env::current_dir().expect("Unable to get current dir"),
+    };
+
+    if root_path.join(MARKER_FILE).exists() {
+        eprintln!(
+            "{} is already a qplug project.",
+            root_path.join(MARKER_FILE).display()
+        );
+        exit(1);
+    }
+
+    let layout = match detect_layout(&root_path) {
+        Some(layout) => layout,
+        None => {
+            eprintln!(
+                "No plugin found in {}. Expected init.lua, plugin.lua or plugin_src/init.lua.",
+                root_path.display()
+            );
+            exit(1);
+        }
+    };
+
+    println!(
+        "Found plugin entry point: {}",
+        layout.paths.entry_path(Path::new("")).display()
+    );
+    match &layout.info {
+        Some(info) => println!("Found plugin info: {}", info.display()),
+        None => println!("No info.lua found. `qplug build` needs one to manage versions."),
+    }
+
+    write_marker_file(&root_path, &marker_settings(&layout.paths));
+
+    if defs {
+        if root_path.join(DEFINITIONS).exists() {
+            println!("definitions already exists, leaving it as is.");
+        } else {
+            add_lua_defs(&root_path, target);
+        }
+    }
+
+    if luarc {
+        if root_path.join(LUARC_FILE).exists() {
+            println!("{} already exists, leaving it as is.", LUARC_FILE);
+        } else {
+            write_luarc(&root_path, false, false).expect("Failed to write .luarc.json.");
+        }
+    }
+
+    println!("Plugin adopted: {}", root_path.display());
+}
+
+/// Work out where the sources live. Checks, in order: the qplug layout (`plugin_src/init.lua`),
+/// a QSC-style `plugin.lua`, an `init.lua` at the root, then any `init.lua` a couple of levels down.
+pub fn detect_layout(root_path: &Path) -> Option<Layout> {
+    let defaults = ProjectPaths::default();
+
+    let paths = if defaults.entry_path(root_path).exists() {
+        defaults
+    } else if root_path.join("plugin.lua").exists() {
+        ProjectPaths {
+            plugin_src: PathBuf::from("."),
+            entry: PathBuf::from("plugin.lua"),
+        }
+    } else if root_path.join(&defaults.entry).exists() {
+        ProjectPaths {
+            plugin_src: PathBuf::from("."),
+            ..defaults
+        }
+    } else {
+        let dir = find_entry_dir(root_path, &defaults.entry, SEARCH_DEPTH)?;
+        ProjectPaths {
+            plugin_src: dir.strip_prefix(root_path).unwrap().to_path_buf(),
+            ..defaults
+        }
+    };
+
+    let info = [paths.plugin_path(root_path), root_path.to_path_buf()]
+        .into_iter()
+        .map(|dir| dir.join("info.lua"))
+        .find(|info| info.exists())
+        .map(|info| info.strip_prefix(root_path).unwrap().to_path_buf());
+
+    Some(Layout { paths, info })
+}
+
+fn find_entry_dir(dir: &Path, entry: &Path, depth: usize) -> Option<PathBuf> {
+    if depth == 0 {
+        return None;
+    }
+
+    let mut children: Vec<PathBuf> = fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .filter(|path| {
+            let name = path.file_name().unwrap().to_string_lossy();
+            !name.starts_with('.') && !SKIPPED_DIRS.contains(&name.as_ref())
+        })
+        .collect();
+    children.sort();
+
+    if let Some(found) = children.iter().find(|child| child.join(entry).exists()) {
+        return Some(found.to_owned());
+    }
+    children
+        .iter()
+        .find_map(|child| find_entry_dir(child, entry, depth - 1))
+}
+
+/// The `.qplug` settings `paths` needs. Empty when the defaults apply.
+pub fn marker_settings(paths: &ProjectPaths) -> Vec<(&'static str, String)> {
+    let defaults = ProjectPaths::default();
+    let path = |path: &Path| quote_string(&path.to_string_lossy().replace('\\', "/"));
+    let mut settings = Vec::new();
+    if paths.plugin_src != defaults.plugin_src {
+        settings.push(("plugin_src", path(&paths.plugin_src)));
+    }
+    if paths.entry != defaults.entry {
+        settings.push(("entry", path(&paths.entry)));
+    }
+    settings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, ConfigOverrides, UserConfig};
+    use mlua::Lua;
+    use tempfile::tempdir;
+
+    fn touch(root: &Path, file: &str) {
+        let path = root.join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "").unwrap();
+    }
+
+    #[test]
+    fn test_detect_qplug_layout() {
+        let dir = tempdir().unwrap();
+        touch(dir.path(), "plugin_src/init.lua");
+        touch(dir.path(), "info.lua");
+
+        let layout = detect_layout(dir.path()).unwrap();
+        assert_eq!(layout.paths, ProjectPaths::default());
+        assert_eq!(layout.info, Some(PathBuf::from("info.lua")));
+        assert!(marker_settings(&layout.paths).is_empty());
+    }
+
+    #[test]
+    fn test_detect_qsc_layout() {
+        let dir = tempdir().unwrap();
+        touch(dir.path(), "plugin.lua");
+        touch(dir.path(), "info.lua");
+
+        let layout = detect_layout(dir.path()).unwrap();
+        assert_eq!(layout.paths.plugin_src, PathBuf::from("."));
+        assert_eq!(layout.paths.entry, PathBuf::from("plugin.lua"));
+        write_marker_file(dir.path(), &marker_settings(&layout.paths));
+        assert_eq!(
+            fs::read_to_string(dir.path().join(MARKER_FILE)).unwrap(),
+            "return {\n  plugin_src = \".\",\n  entry = \"plugin.lua\",\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_detect_nested_layout() {
+        let dir = tempdir().unwrap();
+        touch(dir.path(), "definitions/init.lua");
+        touch(dir.path(), "src/lua/init.lua");
+        touch(dir.path(), "src/lua/info.lua");
+
+        let layout = detect_layout(dir.path()).unwrap();
+        assert_eq!(layout.paths.plugin_src, PathBuf::from("src/lua"));
+        assert_eq!(layout.paths.entry, PathBuf::from("init.lua"));
+        assert_eq!(layout.info, Some(PathBuf::from("src/lua/info.lua")));
+    }
+
+    #[test]
+    fn test_detect_nothing() {
+        let dir = tempdir().unwrap();
+        touch(dir.path(), "README.md");
+        assert_eq!(detect_layout(dir.path()), None);
+    }
+
+    #[test]
+    fn test_marker_overrides_paths() {
+        let dir = tempdir().unwrap();
+        touch(dir.path(), "plugin.lua");
+        let layout = detect_layout(dir.path()).unwrap();
+        write_marker_file(dir.path(), &marker_settings(&layout.paths));
+
+        let lua = Lua::new();
+        let overrides = ConfigOverrides {
+            no_config: true,
+            ..Default::default()
+        };
+        let layers = crate::config::config_layers(&overrides, dir.path());
+        let user_config = UserConfig::from_layers(&lua, &layers, &overrides);
+        let config = Config::from_user_config(&user_config);
+        assert_eq!(config.paths, layout.paths);
+    }
+}
@@ -0,0 +1,317 @@
+use std::{fs, path::PathBuf, process::exit};
+
+use mlua::Lua;
+use regex::{Captures, Regex};
+use serde_json::{json, Map, Value as JsonValue};
+
+use crate::{
+    assets::DEFINITIONS_DIR,
+    config::ProjectPaths,
+    files::find_project_dir,
+    lua::{
+        designer::{run_design_time, seed_design_time, CallbackResult},
+        error_message,
+        lexer::{tokenize, Tok, Token},
+        parser::{locate, merge_plugin_mapped, quote_string},
+        plugin::load_plugin,
+    },
+};
+
+/// Chunk name of the merged `plugin_src`. Lua reports its lines as `[string "plugin"]:12:`.
+const MERGED_CHUNK: &str = "plugin";
+
+/// Lua tables longer than this are split over several lines.
+const WIDTH: usize = 100;
+
+/// The plugin's source as one chunk, and how to turn its line numbers back into files.
+pub struct PluginSource {
+    pub source: String,
+    /// The name the chunk is loaded under.
+    pub chunk: String,
+    locate_line: Box<dyn Fn(usize) -> String>,
+}
+
+impl PluginSource {
+    /// Read `file`, a compiled plugin, or the project's `plugin_src` when it's `None`. Exits if
+    /// there's nothing to read.
+    pub fn load(paths: &ProjectPaths, file: Option<&PathBuf>) -> PluginSource {
+        if let Some(file) = file {
+            let source = fs::read_to_string(file).unwrap_or_else(|e| {
+                eprintln!("Could not read {}: {}", file.display(), e);
+                exit(1);
+            });
+            let name = file.display().to_string();
+            return PluginSource {
+                source,
+                chunk: name.clone(),
+                locate_line: Box::new(move |line| format!("{}:{}", name, line)),
+            };
+        }
+
+        let Some(root_path) = find_project_dir(None) else {
+            eprintln!("Not a qplug project. Run `qplug new` or `qplug init` first.");
+            exit(1);
+        };
+        let (source, map) = merge_plugin_mapped(
+            &paths.plugin_path(&root_path),
+            &paths.entry_path(&root_path),
+        )
+        .unwrap_or_else(|e| {
+            eprintln!("Could not read the plugin: {}", e);
+            exit(1);
+        });
+        PluginSource {
+            source,
+            chunk: MERGED_CHUNK.to_string(),
+            locate_line: Box::new(move |line| locate(&root_path, &map, line)),
+        }
+    }
+
+    /// Replace the chunk's `[string "chunk"]:line:` locations in a Lua error with project ones.
+    pub fn relocate(&self, message: &str) -> String {
+        relocate(message, &self.chunk, &self.locate_line)
+    }
+}
+
+/// Run the design-time callbacks on the plugin, with errors pointing into its files. Exits if
+/// the plugin doesn't load.
+pub fn design_time_results(plugin: &PluginSource) -> Vec<CallbackResult> {
+    let lua = Lua::new();
+    let results = seed_design_time(&lua, &DEFINITIONS_DIR)
+        .and_then(|_| load_plugin(&lua, &plugin.source, &plugin.chunk))
+        .and_then(|_| run_design_time(&lua, &Map::new()));
+    match results {
+        Ok(results) => results
+            .into_iter()
+            .map(|result| CallbackResult {
+                result: result.result.map_err(|message| plugin.relocate(&message)),
+                ..result
+            })
+            .collect(),
+        Err(e) => {
+            eprintln!("error: {}", plugin.relocate(&error_message(&e)));
+            exit(1);
+        }
+    }
+}
+
+/// `qplug inspect`: run the design-time callbacks the way Designer does and print what they
+/// return, as Lua tables or as JSON. `file` is a compiled plugin to use instead of `plugin_src`.
+pub fn inspect(paths: &ProjectPaths, file: Option<&PathBuf>, json: bool) {
+    let results = design_time_results(&PluginSource::load(paths, file));
+
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&to_report(&results)).unwrap()
+        );
+    } else {
+        print_results(&results);
+    }
+
+    match results
+        .iter()
+        .filter(|result| result.result.is_err())
+        .count()
+    {
+        0 => {}
+        1 => {
+            eprintln!("1 callback failed.");
+            exit(1);
+        }
+        n => {
+            eprintln!("{} callbacks failed.", n);
+            exit(1);
+        }
+    }
+}
+
+fn relocate(message: &str, chunk: &str, locate_line: &dyn Fn(usize) -> String) -> String {
+    let pattern = Regex::new(&format!(r#"\[string "{}"\]:(\d+):"#, regex::escape(chunk))).unwrap();
+    pattern
+        .replace_all(message, |cap: &Captures| {
+            format!("{}:", locate_line(cap[1].parse().unwrap()))
+        })
+        .into_owned()
+}
+
+/// Every callback's result under its name, with `GetControlLayout` as a list of pages, and the
+/// errors on their own.
+fn to_report(results: &[CallbackResult]) -> JsonValue {
+    let mut report = Map::new();
+    let mut layouts = Vec::new();
+    let mut errors = Vec::new();
+    for result in results {
+        match &result.result {
+            Ok(returned) if result.callback == "GetControlLayout" => {
+                let mut page = Map::new();
+                page.insert("page".to_string(), json!(result.page));
+                if let JsonValue::Object(layout) = returned {
+                    page.extend(layout.clone());
+                }
+                layouts.push(JsonValue::Object(page));
+            }
+            Ok(returned) => {
+                report.insert(result.callback.to_string(), returned.clone());
+            }
+            Err(message) => errors.push(json!({
+                "callback": result.callback,
+                "page": result.page,
+                "message": message,
+            })),
+        }
+    }
+    if !layouts.is_empty() {
+        report.insert("GetControlLayout".to_string(), JsonValue::Array(layouts));
+    }
+    report.insert("errors".to_string(), JsonValue::Array(errors));
+    JsonValue::Object(report)
+}
+
+fn print_results(results: &[CallbackResult]) {
+    for (i, result) in results.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        match &result.page {
+            Some(page) => println!("-- {} ({})", result.callback, page),
+            None => println!("-- {}", result.callback),
+        }
+        match &result.result {
+            Ok(returned) => println!("{}", lua_literal(returned, 0)),
+            Err(message) => println!("error: {}", message),
+        }
+    }
+}
+
+/// `value` as a Lua constructor, on one line if it fits and one entry per line if it doesn't.
+pub fn lua_literal(value: &JsonValue, indent: usize) -> String {
+    let inline = inline_literal(value);
+    if indent * 2 + inline.len() <= WIDTH {
+        return inline;
+    }
+    let entries: Vec<String> = match value {
+        JsonValue::Array(items) => items
+            .iter()
+            .map(|item| lua_literal(item, indent + 1))
+            .collect(),
+        JsonValue::Object(fields) => fields
+            .iter()
+            .map(|(key, field)| format!("{} = {}", lua_key(key), lua_literal(field, indent + 1)))
+            .collect(),
+        _ => return inline,
+    };
+    let pad = "  ".repeat(indent + 1);
+    let mut literal = String::from("{\n");
+    for entry in entries {
+        literal.push_str(&format!("{}{},\n", pad, entry));
+    }
+    literal.push_str(&"  ".repeat(indent));
+    literal.push('}');
+    literal
+}
+
+fn inline_literal(value: &JsonValue) -> String {
+    match value {
+        JsonValue::Null => "nil".to_string(),
+        JsonValue::Bool(b) => b.to_string(),
+        JsonValue::Number(n) => n.to_string(),
+        JsonValue::String(s) => quote_string(s),
+        JsonValue::Array(items) if items.is_empty() => "{}".to_string(),
+        JsonValue::Object(fields) if fields.is_empty() => "{}".to_string(),
+        JsonValue::Array(items) => {
+            let items: Vec<String> = items.iter().map(inline_literal).collect();
+            format!("{{ {} }}", items.join(", "))
+        }
+        JsonValue::Object(fields) => {
+            let fields: Vec<String> = fields
+                .iter()
+                .map(|(key, field)| format!("{} = {}", lua_key(key), inline_literal(field)))
+                .collect();
+            format!("{{ {} }}", fields.join(", "))
+        }
+    }
+}
+
+/// `key` as written in a table constructor: bare if it's a name, bracketed otherwise.
+fn lua_key(key: &str) -> String {
+    let is_name = matches!(
+        tokenize(key).as_deref(),
+        Ok([
+            Token {
+                tok: Tok::Name(_),
+                ..
+            },
+            Token { tok: Tok::Eof, .. }
+        ])
+    );
+    if is_name {
+        key.to_string()
+    } else if key.parse::<i64>().is_ok() {
+        format!("[{}]", key)
+    } else {
+        format!("[{}]", quote_string(key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lua_literal() {
+        let value = json!({ "n inputs": 8, "Name": "main_mixer", "end": true, "2": [1, "a"] });
+        assert_eq!(
+            lua_literal(&value, 0),
+            r#"{ [2] = { 1, "a" }, Name = "main_mixer", ["end"] = true, ["n inputs"] = 8 }"#
+        );
+
+        let long = json!([{ "Text": "x".repeat(60) }, { "Text": "y".repeat(60) }]);
+        assert_eq!(
+            lua_literal(&long, 0),
+            format!(
+                "{{\n  {{ Text = \"{}\" }},\n  {{ Text = \"{}\" }},\n}}",
+                "x".repeat(60),
+                "y".repeat(60)
+            )
+        );
+    }
+
+    #[test]
+    fn test_relocate() {
+        let message = r#"[string "plugin"]:12: attempt to index a nil value"#;
+        let relocated = relocate(message, "plugin", &|line| {
+            format!("src/a.lua:{}", line - 10)
+        });
+        assert_eq!(relocated, "src/a.lua:2: attempt to index a nil value");
+    }
+
+    #[test]
+    fn test_to_report() {
+        let results = [
+            CallbackResult {
+                callback: "GetControls",
+                page: None,
+                result: Ok(json!([{ "Name": "Gain" }])),
+            },
+            CallbackResult {
+                callback: "GetControlLayout",
+                page: Some("Control".to_string()),
+                result: Ok(json!({ "layout": {}, "graphics": [] })),
+            },
+            CallbackResult {
+                callback: "GetControlLayout",
+                page: Some("Setup".to_string()),
+                result: Err("a.lua:3: boom".to_string()),
+            },
+        ];
+        assert_eq!(
+            to_report(&results),
+            json!({
+                "GetControls": [{ "Name": "Gain" }],
+                "GetControlLayout": [{ "page": "Control", "layout": {}, "graphics": [] }],
+                "errors": [{ "callback": "GetControlLayout", "page": "Setup", "message": "a.lua:3: boom" }],
+            })
+        );
+    }
+}
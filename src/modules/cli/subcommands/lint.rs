@@ -0,0 +1,80 @@
+use std::{io, path::Path, process::exit};
+
+use crate::{
+    assets::DEFINITIONS_DIR,
+    config::ProjectPaths,
+    files::find_project_dir,
+    lua::{
+        compat::{check_compat, Incompatibility},
+        lint::{known_globals, lint as lint_source, Problem},
+        parser::{locate, merge_plugin_mapped},
+    },
+};
+
+/// `qplug lint`: report reads of undefined globals, writes to unexpected ones, and anything that
+/// won't run in the Q-SYS sandbox. The plugin is checked the way it's compiled, as one chunk, so
+/// locals shared between modules resolve.
+pub fn lint(paths: &ProjectPaths) {
+    let Some(root_path) = find_project_dir(None) else {
+        eprintln!("Not a qplug project. Run `qplug new` or `qplug init` first.");
+        exit(1);
+    };
+
+    let (source, map) = match merge_plugin_mapped(
+        &paths.plugin_path(&root_path),
+        &paths.entry_path(&root_path),
+    ) {
+        Ok(merged) => merged,
+        Err(e) => {
+            eprintln!("Could not read the plugin: {}", e);
+            exit(1);
+        }
+    };
+
+    let lint_problems = lint_source(&source, &known_globals(&DEFINITIONS_DIR));
+    // A syntax error is already reported by the linter.
+    let syntax_error = lint_problems
+        .iter()
+        .any(|(_, problem)| matches!(problem, Problem::Syntax(_)));
+    let mut problems: Vec<(usize, String)> = lint_problems
+        .into_iter()
+        .map(|(line, problem)| (line, problem.to_string()))
+        .collect();
+    if !syntax_error {
+        problems.extend(
+            check_compat(&source)
+                .into_iter()
+                .map(|(line, problem)| (line, problem.to_string())),
+        );
+    }
+    problems.sort_by_key(|(line, _)| *line);
+
+    for (line, problem) in &problems {
+        println!("{}: {}", locate(&root_path, &map, *line), problem);
+    }
+
+    match problems.len() {
+        0 => println!("No problems found."),
+        1 => {
+            eprintln!("1 problem found.");
+            exit(1);
+        }
+        n => {
+            eprintln!("{} problems found.", n);
+            exit(1);
+        }
+    }
+}
+
+/// Everything in the plugin that won't run on a Core, with the `file:line` it's at.
+pub fn compat_problems(
+    root_path: &Path,
+    paths: &ProjectPaths,
+) -> io::Result<Vec<(String, Incompatibility)>> {
+    let (source, map) =
+        merge_plugin_mapped(&paths.plugin_path(root_path), &paths.entry_path(root_path))?;
+    Ok(check_compat(&source)
+        .into_iter()
+        .map(|(line, problem)| (locate(root_path, &map, line), problem))
+        .collect())
+}
@@ -1,6 +1,16 @@
 pub mod build;
 pub mod check;
 pub mod compile;
+pub mod config;
 pub mod copy;
+pub mod defs;
+pub mod generate;
+pub mod init;
+pub mod inspect;
+pub mod lint;
 pub mod new;
+pub mod preview;
+pub mod run;
+pub mod template;
+pub mod test;
 pub mod update;
@@ -1,27 +1,59 @@
 use std::{
-    fs,
-    io::{self, Write},
+    error::Error,
+    fs, io,
     path::{Path, PathBuf},
     process::exit,
 };
 
 use git2::Repository;
+use mlua::Lua;
+
+use super::defs::{install_definitions, update_generated, write_luarc, DEFINITIONS};
 use uuid::Uuid;
 
 use crate::{
-    assets::DEFINITIONS_DIR,
-    config::{Config, Template, UserEnv},
-    files::{self, copy_dir, create_marker_file},
-    lua::info::PluginInfo,
+    cli::prompt::{is_interactive, prompt},
+    config::{Author, Config, ProjectPaths, Template, UserEnv},
+    files::{self, copy_dir, create_marker_file, walk_files, write_marker_file},
+    license::{stamp_spdx_header, write_license, License},
+    lua::{
+        decompile::{first_difference, split_plugin},
+        info::PluginInfo,
+        parser::{find_lua_requirements, quote_string},
+        since::QsysVersion,
+    },
+    template::{
+        fetch_git_template,
+        manifest::{TemplateManifest, MANIFEST_FILE},
+        render_dir, GitSource, TemplateCache, TemplateVars,
+    },
 };
 
-const PLUGIN_ROOT: &str = "plugin_src";
+pub const PLUGIN_ROOT: &str = "plugin_src";
+
+/// Plugin details given on the command line instead of at the prompts.
+#[derive(Debug, Default)]
+pub struct NewOptions {
+    pub author: Option<String>,
+    pub description: Option<String>,
+    pub manufacturer: Option<String>,
+    pub id: Option<Uuid>,
+    /// Accept the defaults for anything not given instead of prompting.
+    pub yes: bool,
+    /// Split this flat plugin into modules instead of using a template.
+    pub from_qplug: Option<PathBuf>,
+    /// SPDX identifier or path of the license. Overrides `license` from the config.
+    pub license: Option<String>,
+    /// Prepend an SPDX header to every Lua module. Also enabled by `license_header` in the config.
+    pub license_header: bool,
+}
 
 pub fn create_plugin(
     name: Option<&String>,
     no_git: &bool,
     no_template: &bool,
     no_defs: &bool,
+    options: &NewOptions,
     user_env: UserEnv,
 ) {
     // Check if name was provided - if not set name to parent directory
@@ -50,51 +82,119 @@ pub fn create_plugin(
     let root_path = Path::new(&file_name);
     let plugin_path = root_path.join(PLUGIN_ROOT);
 
+    // If name was set as a path, use the last part. Fall back to the current directory's name.
+    let plugin_name = match root_path.file_name() {
+        Some(name) => name.to_string_lossy().to_string(),
+        None => std::env::current_dir()
+            .unwrap()
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .to_string(),
+    };
+
+    if let Some(qplug_file) = &options.from_qplug {
+        import_qplug(qplug_file, &plugin_path).unwrap_or_else(|e| {
+            eprintln!("Failed to import {}: {}", qplug_file.display(), e);
+            exit(1);
+        });
+        println!("Plugin split into modules and verified");
+        setup_project(
+            root_path,
+            no_defs,
+            no_git,
+            user_env.config.target_qsys.as_ref(),
+        );
+        create_marker_file(root_path);
+        if !no_defs {
+            update_generated(root_path, &ProjectPaths::default());
+        }
+        println!("New plugin created: {}", plugin_name);
+        return;
+    }
+
+    // Resolve the license up front so a typo fails before anything is created.
+    let license = options
+        .license
+        .as_ref()
+        .or(user_env.config.license.as_ref())
+        .map(|spec| {
+            License::resolve(spec).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                exit(1);
+            })
+        });
+    let license_header = options.license_header || user_env.config.license_header;
+    if license_header && license.is_none() {
+        eprintln!("No license selected, skipping the SPDX header. Pass --license or set `license` in your config.");
+    }
+
+    let interactive = is_interactive();
+    let info = get_user_info(&plugin_name, None, user_env.config, options, interactive)
+        .unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            exit(1);
+        });
+    let author = Author {
+        name: Some(info.author.clone()).filter(|name| !name.is_empty()),
+        email: user_env.config.me.email.clone(),
+        company: user_env.config.me.company.clone(),
+    };
+
     // Create plugin directories
     fs::create_dir_all(&plugin_path).expect("Directory creation failed.");
 
-    // fetch the template based on the user's config. Default to internal template if none set.
-    fetch_template(&plugin_path, &user_env.config.template);
-
+    let mut vars = template_vars(&info, &author);
+    let mut manifest = None;
     if !no_template {
-        fs::create_dir_all(&plugin_path).expect("Directory creation failed.");
+        // fetch the template based on the user's config. Default to internal template if none set.
         fetch_template(plugin_path.as_path(), &user_env.config.template);
-        println!("Template initialized");
-    }
 
-    if !no_defs {
-        add_lua_defs(root_path);
-        println!("Definitions initialized");
-    }
+        manifest = TemplateManifest::find(user_env.lua, &plugin_path).unwrap_or_else(|e| {
+            eprintln!("Invalid {}: {}", MANIFEST_FILE, e);
+            exit(1);
+        });
+        if let Some(manifest) = &manifest {
+            let answers = manifest
+                .ask(user_env.lua, |p| {
+                    let default = p.default.is_some() && (options.yes || !interactive);
+                    if default {
+                        Ok(String::new())
+                    } else if !interactive {
+                        Err(io::Error::other(format!(
+                            "The template asks for `{}` and has no default for it, so it can't be answered without a terminal.",
+                            p.name
+                        )))
+                    } else {
+                        prompt(&p.question())
+                    }
+                })
+                .unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    exit(1);
+                });
+            vars.extend(answers);
+            manifest
+                .apply_excludes(&plugin_path)
+                .expect("Failed to remove excluded template files");
+        }
 
-    // Init git repo
-    if !no_git {
-        init_git(root_path);
-        println!("Git initialized");
+        render_dir(&plugin_path, &vars).expect("Failed to fill in template variables");
+        println!("Template initialized");
     }
 
-    // If name was set as a path, use the last part
-    let plugin_name = &file_name
-        .split('/')
-        .collect::<Vec<&str>>()
-        .last()
-        .unwrap()
-        .to_owned()
-        .to_string();
+    setup_project(
+        root_path,
+        no_defs,
+        no_git,
+        user_env.config.target_qsys.as_ref(),
+    );
 
     // Print Creation Confirmation
-    if plugin_name == "." {
-        println!(
-            "New plugin created: {:?}",
-            std::env::current_dir().unwrap().file_name().unwrap()
-        );
-    } else {
-        println!("New plugin created: {}", plugin_name);
-    }
+    println!("New plugin created: {}", plugin_name);
 
     // Write the info.lua file
     let info_lua_file = files::find_file_recursively(&plugin_path, "info.lua");
-    let info = get_user_info(plugin_name, None, user_env.config);
     match info_lua_file {
         Some(file) => {
             info.write_to_file(file, user_env.lua)
@@ -105,7 +205,107 @@ pub fn create_plugin(
         }
     }
 
-    create_marker_file(root_path);
+    if !no_template {
+        stamp_author(
+            &plugin_path,
+            &ProjectPaths::default().entry_path(root_path),
+            &author,
+            current_year(),
+        )
+        .expect("Failed to add author info");
+    }
+
+    let mut settings = Vec::new();
+    if let Some(license) = &license {
+        let file = write_license(&plugin_path, root_path, &license.render(&vars))
+            .expect("Failed to write the license");
+        println!("{} license written to {}", license.id, file.display());
+        settings.push(("license", quote_string(&license.id)));
+
+        if license_header {
+            stamp_spdx_header(&plugin_path, &license.id).expect("Failed to add SPDX headers");
+            settings.push(("license_header", "true".to_string()));
+        }
+    }
+
+    write_marker_file(root_path, &settings);
+
+    if !no_defs {
+        update_generated(root_path, &ProjectPaths::default());
+    }
+
+    if let Some(manifest) = manifest {
+        if let Err(e) = manifest.run_post_create(user_env.lua, root_path, &plugin_path, &vars) {
+            eprintln!("Post-create hook failed: {}", e);
+            exit(1);
+        }
+    }
+}
+
+fn setup_project(root_path: &Path, no_defs: &bool, no_git: &bool, target: Option<&QsysVersion>) {
+    if !no_defs {
+        add_lua_defs(root_path, target);
+        write_luarc(root_path, false, false).expect("Failed to write .luarc.json.");
+        println!("Definitions initialized");
+    }
+
+    // Init git repo
+    if !no_git {
+        init_git(root_path);
+        println!("Git initialized");
+    }
+}
+
+/// Split a flat `.qplug` into the template's module layout under `plugin_path`, then make sure
+/// compiling it again gives back the same plugin.
+pub fn import_qplug(qplug_file: &Path, plugin_path: &Path) -> Result<(), Box<dyn Error>> {
+    let source = fs::read_to_string(qplug_file)?;
+    let decompiled = split_plugin(&source)?;
+
+    for section in &decompiled.sections {
+        let file = plugin_path.join(section.file());
+        fs::create_dir_all(file.parent().unwrap())?;
+        fs::write(&file, &section.source)?;
+    }
+    let init_lua = decompiled.init_lua();
+    fs::create_dir_all(plugin_path)?;
+    fs::write(plugin_path.join("init.lua"), &init_lua)?;
+
+    let recompiled = find_lua_requirements(&init_lua, plugin_path.to_path_buf());
+    match first_difference(&source, &recompiled) {
+        None => Ok(()),
+        Some(line) => Err(format!(
+            "the recompiled plugin differs from the original at line {}. The split modules were left in {} for inspection.",
+            line,
+            plugin_path.display()
+        )
+        .into()),
+    }
+}
+
+/// Variables available to template files as `{{ key }}`.
+pub fn template_vars(info: &PluginInfo, author: &Author) -> TemplateVars {
+    let mut vars = TemplateVars::new();
+    vars.insert("name".to_string(), info.name.to_owned());
+    vars.insert("description".to_string(), info.description.to_owned());
+    vars.insert("uuid".to_string(), info.id.to_owned());
+    vars.insert("author".to_string(), info.author.to_owned());
+    vars.insert(
+        "email".to_string(),
+        author.email.clone().unwrap_or_default(),
+    );
+    vars.insert(
+        "company".to_string(),
+        author.company.clone().unwrap_or_default(),
+    );
+    vars.insert("year".to_string(), current_year().to_string());
+    vars.insert(
+        "copyright".to_string(),
+        author
+            .copyright_line(current_year())
+            .unwrap_or_else(|| format!("Copyright (c) {}", current_year())),
+    );
+    vars
 }
 
 //TODO: Cleanup signature - Returns not currently being used.
@@ -113,10 +313,12 @@ pub fn fetch_template(path: &Path, template: &Template) -> PathBuf {
     // path = path to plugin dir
     // let url = "https://github.com/qsys-plugins/BasePlugin";
     match template {
-        Template::Url(s) => match Repository::clone(s, path) {
-            Ok(repo) => repo.path().to_path_buf(),
-            Err(e) => panic!("Failed to clone: {}", e),
-        },
+        Template::Url(s) => {
+            let cache = TemplateCache::user_cache();
+            fetch_git_template(&GitSource::parse(s), path, cache.as_ref())
+                .unwrap_or_else(|e| panic!("Failed to clone: {}", e));
+            path.to_path_buf()
+        }
         Template::FileSystem(_) => {
             copy_dir(template, path).expect("Failed to copy user template");
             path.to_path_buf()
@@ -128,51 +330,141 @@ pub fn fetch_template(path: &Path, template: &Template) -> PathBuf {
     }
 }
 
-fn get_user_info(name: &String, existing_info: Option<PluginInfo>, config: &Config) -> PluginInfo {
+fn get_user_info(
+    name: &str,
+    existing_info: Option<PluginInfo>,
+    config: &Config,
+    options: &NewOptions,
+    interactive: bool,
+) -> Result<PluginInfo, String> {
     match existing_info {
-        Some(config) => config,
+        Some(config) => Ok(config),
         None => {
-            // Author Name
-            let author = match &config.me.name {
-                // Get name from config file
-                Some(name) => name.to_owned(),
-                // If not set in config file, ask user
-                None => {
-                    let mut author = String::new();
-                    println!("Enter your name: ");
-                    io::stdin()
-                        .read_line(&mut author)
-                        .expect("Oops, Could not read your name.");
-                    author
-                }
-            };
+            // Author Name: flag, then config file, then ask the user
+            let author = answer(
+                options.author.as_ref().or(config.me.name.as_ref()),
+                "author",
+                "--author",
+                "Enter your name: ",
+                options.yes,
+                interactive,
+            )?;
 
             // Description
-            io::stdout().flush().unwrap();
-            let mut description = String::new();
-            println!("Enter a description for your plugin: ");
-            io::stdin()
-                .read_line(&mut description)
-                .expect("Oops, Could not read description.");
-
-            PluginInfo {
+            let description = answer(
+                options.description.as_ref(),
+                "description",
+                "--description",
+                "Enter a description for your plugin: ",
+                options.yes,
+                interactive,
+            )?;
+
+            Ok(PluginInfo {
                 name: name.to_string(),
                 version: "0.0.0.0".to_string(),
                 build_version: "0.0.0.0".to_string(),
-                id: Uuid::new_v4().to_string(),
-                author: author.trim().to_string(),
-                description: description.trim().to_string(),
+                id: options.id.unwrap_or_else(Uuid::new_v4).to_string(),
+                author,
+                description,
+                manufacturer: options
+                    .manufacturer
+                    .clone()
+                    .or_else(|| config.me.company.clone()),
+            })
+        }
+    }
+}
+
+/// Use `given` if there is one. Otherwise ask, unless `--yes` was passed (empty answer) or
+/// nobody is at stdin to answer (error naming the missing value).
+fn answer(
+    given: Option<&String>,
+    value: &str,
+    flag: &str,
+    question: &str,
+    yes: bool,
+    interactive: bool,
+) -> Result<String, String> {
+    match given {
+        Some(given) => Ok(given.trim().to_string()),
+        None if yes => Ok(String::new()),
+        None if !interactive => Err(missing_value(value, flag)),
+        None => prompt(question).map_err(|e| format!("Oops, Could not read the {}: {}", value, e)),
+    }
+}
+
+fn missing_value(value: &str, flag: &str) -> String {
+    format!(
+        "No {} given and stdin is not a terminal. Pass {} (or --yes to accept defaults).",
+        value, flag
+    )
+}
+
+/// Add the author's copyright line to the top of the entry file and to the LICENSE files of a
+/// new plugin. Modules are inlined into the entry file when compiled, so one notice covers them.
+pub fn stamp_author(
+    plugin_path: &Path,
+    entry_file: &Path,
+    author: &Author,
+    year: i32,
+) -> io::Result<()> {
+    let Some(copyright) = author.copyright_line(year) else {
+        return Ok(());
+    };
+
+    if entry_file.exists() {
+        let contents = fs::read_to_string(entry_file)?;
+        fs::write(entry_file, format!("-- {}\n\n{}", copyright, contents))?;
+    }
+    for file in walk_files(plugin_path) {
+        let file_name = file
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        if file_name.to_uppercase().starts_with("LICENSE") {
+            let contents = fs::read_to_string(&file)?;
+            fs::write(&file, add_license_author(&contents, &copyright))?;
+        }
+    }
+    Ok(())
+}
+
+/// Insert `copyright` after the last copyright notice of a license, or at the top if it has none.
+fn add_license_author(license: &str, copyright: &str) -> String {
+    let mut lines: Vec<&str> = license.lines().collect();
+    let position = lines
+        .iter()
+        .rposition(|line| line.trim_start().starts_with("Copyright"))
+        .map(|i| i + 1);
+
+    match position {
+        Some(i) => {
+            lines.insert(i, copyright);
+            let mut updated = lines.join("\n");
+            if license.ends_with('\n') {
+                updated.push('\n');
             }
+            updated
         }
+        None => format!("{}\n\n{}", copyright, license),
     }
 }
 
-pub fn add_lua_defs(root_path: &Path) {
+/// This year in local time, from Lua's `os.date`.
+pub fn current_year() -> i32 {
+    Lua::new()
+        .load("return tonumber(os.date('%Y'))")
+        .eval()
+        .unwrap_or(1970)
+}
+
+pub fn add_lua_defs(root_path: &Path, target: Option<&QsysVersion>) {
     // Add Lua Defs
-    let defs_path = root_path.join("definitions");
+    let defs_path = root_path.join(DEFINITIONS);
     fs::create_dir(&defs_path).expect("Directory creation failed.");
-    copy_dir(&Template::InMemoryDir(&DEFINITIONS_DIR), &defs_path)
-        .expect("Failed to copy definitions.");
+    install_definitions(&defs_path, target).expect("Failed to copy definitions.");
 }
 
 pub fn init_git(path: &Path) -> Repository {
@@ -181,3 +473,147 @@ pub fn init_git(path: &Path) -> Repository {
         Err(e) => panic!("Failed to initialize git repo: {}", e),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{assets::TEMPLATE_DIR, template::BUILTIN_VARS};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_current_year() {
+        let year: i32 = Lua::new().load("return os.date('*t').year").eval().unwrap();
+        assert_eq!(current_year(), year);
+    }
+
+    #[test]
+    fn test_template_vars_are_builtin() {
+        let info = PluginInfo {
+            name: "Amp".to_string(),
+            version: "0.0.0.0".to_string(),
+            build_version: "0.0.0.0".to_string(),
+            id: Uuid::new_v4().to_string(),
+            author: String::new(),
+            description: String::new(),
+            manufacturer: None,
+        };
+        let author = Author {
+            name: None,
+            email: None,
+            company: None,
+        };
+        let mut names: Vec<String> = template_vars(&info, &author).into_keys().collect();
+        let mut builtin = BUILTIN_VARS.map(str::to_string);
+        names.sort();
+        builtin.sort();
+        assert_eq!(names, builtin);
+    }
+
+    #[test]
+    fn test_stamp_author() {
+        let temp_dir = tempdir().unwrap();
+        copy_dir(&Template::InMemoryDir(&TEMPLATE_DIR), temp_dir.path()).unwrap();
+        let author = Author {
+            name: Some("Jane Doe".to_string()),
+            email: Some("jane@example.com".to_string()),
+            company: Some("ACME".to_string()),
+        };
+
+        let entry = temp_dir.path().join("init.lua");
+        stamp_author(temp_dir.path(), &entry, &author, 2024).unwrap();
+
+        let expected = "Copyright (c) Jane Doe <jane@example.com> / ACME, 2024";
+        let init = fs::read_to_string(&entry).unwrap();
+        assert!(init.starts_with(&format!("-- {}\n", expected)));
+        let info = fs::read_to_string(temp_dir.path().join("setup/info.lua")).unwrap();
+        assert!(!info.contains(expected));
+
+        let license = fs::read_to_string(temp_dir.path().join("LICENSE.txt")).unwrap();
+        assert!(license.contains(&format!("Ascend Studios, LLC, 2024\n{}\n", expected)));
+    }
+
+    fn no_config<R>(f: impl FnOnce(&Config) -> R) -> R {
+        let lua = mlua::Lua::new();
+        let overrides = crate::config::ConfigOverrides {
+            no_config: true,
+            ..Default::default()
+        };
+        let user_config = crate::config::UserConfig::from_layers(&lua, &[], &overrides);
+        let config = Config::from_user_config(&user_config);
+        f(&config)
+    }
+
+    #[test]
+    fn test_get_user_info_from_flags() {
+        let id = Uuid::new_v4();
+        let options = NewOptions {
+            author: Some("Jane Doe".to_string()),
+            description: Some("A plugin".to_string()),
+            manufacturer: Some("ACME".to_string()),
+            id: Some(id),
+            ..Default::default()
+        };
+
+        let info =
+            no_config(|config| get_user_info("demo", None, config, &options, false)).unwrap();
+        assert_eq!(info.author, "Jane Doe");
+        assert_eq!(info.description, "A plugin");
+        assert_eq!(info.manufacturer.as_deref(), Some("ACME"));
+        assert_eq!(info.id, id.to_string());
+    }
+
+    #[test]
+    fn test_get_user_info_fails_without_tty() {
+        let options = NewOptions {
+            author: Some("Jane Doe".to_string()),
+            ..Default::default()
+        };
+
+        let err =
+            no_config(|config| get_user_info("demo", None, config, &options, false)).unwrap_err();
+        assert!(err.contains("description"));
+        assert!(err.contains("--description"));
+    }
+
+    #[test]
+    fn test_get_user_info_yes_accepts_defaults() {
+        let options = NewOptions {
+            yes: true,
+            ..Default::default()
+        };
+
+        let info =
+            no_config(|config| get_user_info("demo", None, config, &options, false)).unwrap();
+        assert_eq!(info.author, "");
+        assert_eq!(info.description, "");
+        assert!(Uuid::parse_str(&info.id).is_ok());
+    }
+
+    #[test]
+    fn test_import_qplug() {
+        let temp_dir = tempdir().unwrap();
+        let qplug_file = temp_dir.path().join("legacy.qplug");
+        let source = "json = require(\"rapidjson\")\n\nPluginInfo = { Name = \"Legacy\" }\n\n\
+            function GetControls(props)\n  return {}\nend\n\nif Controls then\n  print(1)\nend\n";
+        fs::write(&qplug_file, source).unwrap();
+
+        let plugin_path = temp_dir.path().join("legacy").join(PLUGIN_ROOT);
+        import_qplug(&qplug_file, &plugin_path).unwrap();
+
+        let init = fs::read_to_string(plugin_path.join("init.lua")).unwrap();
+        assert_eq!(
+            init,
+            "json = require(\"rapidjson\")\n\nrequire(\"setup.info\")\n\
+            require(\"control_components.controls\")\nrequire(\"runtime.runtime\")\n"
+        );
+        assert!(plugin_path.join("setup/info.lua").exists());
+        assert!(plugin_path.join("control_components/controls.lua").exists());
+        assert!(plugin_path.join("runtime/runtime.lua").exists());
+    }
+
+    #[test]
+    fn test_add_license_author_without_notice() {
+        let updated = add_license_author("Some license\n", "Copyright (c) Me, 2024");
+        assert_eq!(updated, "Copyright (c) Me, 2024\n\nSome license\n");
+    }
+}
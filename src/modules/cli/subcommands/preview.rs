@@ -0,0 +1,510 @@
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+    process::exit,
+};
+
+use serde_json::Value as JsonValue;
+
+use super::inspect::{design_time_results, PluginSource};
+use crate::config::ProjectPaths;
+
+/// Space left around the right and bottom of a page.
+const PADDING: f64 = 5.0;
+/// Designer's font size for text that doesn't give one.
+const DEFAULT_FONT_SIZE: f64 = 12.0;
+const FONT_FAMILY: &str = "Roboto, Arial, sans-serif";
+
+/// `qplug preview`: lay out every page the way `GetControlLayout` describes it and write one SVG
+/// per page to `out_dir`. `file` is a compiled plugin to use instead of `plugin_src`.
+pub fn preview(paths: &ProjectPaths, file: Option<&PathBuf>, out_dir: &Path) {
+    let results = design_time_results(&PluginSource::load(paths, file));
+
+    let control_types: HashMap<String, String> = results
+        .iter()
+        .filter(|result| result.callback == "GetControls")
+        .filter_map(|result| result.result.as_ref().ok()?.as_array())
+        .flatten()
+        .filter_map(|control| {
+            let name = control.get("Name")?.as_str()?;
+            let control_type = control
+                .get("ControlType")
+                .and_then(JsonValue::as_str)
+                .unwrap_or("Button");
+            Some((name.to_string(), control_type.to_string()))
+        })
+        .collect();
+
+    let layouts: Vec<_> = results
+        .iter()
+        .filter(|result| result.callback == "GetControlLayout")
+        .collect();
+    if layouts.is_empty() {
+        eprintln!("The plugin has no GetControlLayout.");
+        exit(1);
+    }
+    if let Err(e) = fs::create_dir_all(out_dir) {
+        eprintln!("Could not create {}: {}", out_dir.display(), e);
+        exit(1);
+    }
+
+    let mut failed = 0;
+    for (index, result) in layouts.iter().enumerate() {
+        let title = result.page.as_deref().unwrap_or("Layout");
+        let returned = match &result.result {
+            Ok(returned) => returned,
+            Err(message) => {
+                eprintln!("error: {}: {}", title, message);
+                failed += 1;
+                continue;
+            }
+        };
+        let svg = page_svg(
+            title,
+            &returned["layout"],
+            &returned["graphics"],
+            &control_types,
+        );
+        let path = out_dir.join(page_file_name(index, result.page.as_deref()));
+        match fs::write(&path, svg) {
+            Ok(_) => println!("Wrote {}", path.display()),
+            Err(e) => {
+                eprintln!("Could not write {}: {}", path.display(), e);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        exit(1);
+    }
+}
+
+/// `1-control.svg` for the first page, named `Control`. Numbered so pages sort in order.
+fn page_file_name(index: usize, page: Option<&str>) -> String {
+    let slug: String = page
+        .unwrap_or("layout")
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect();
+    let slug = slug.trim_matches('-');
+    let slug = if slug.is_empty() { "page" } else { slug };
+    format!("{}-{}.svg", index + 1, slug)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Rect {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+impl Rect {
+    /// The `Position` and `Size` of a layout entry or graphic.
+    fn of(item: &JsonValue) -> Option<Rect> {
+        let pair = |key: &str| -> Option<(f64, f64)> {
+            let values = item.get(key)?.as_array()?;
+            Some((values.first()?.as_f64()?, values.get(1)?.as_f64()?))
+        };
+        let (x, y) = pair("Position")?;
+        let (width, height) = pair("Size")?;
+        Some(Rect {
+            x,
+            y,
+            width,
+            height,
+        })
+    }
+
+    fn attributes(&self) -> String {
+        format!(
+            r#"x="{}" y="{}" width="{}" height="{}""#,
+            self.x, self.y, self.width, self.height
+        )
+    }
+}
+
+/// One page as an SVG: the graphics in the order they're listed, then the controls on top.
+fn page_svg(
+    title: &str,
+    layout: &JsonValue,
+    graphics: &JsonValue,
+    control_types: &HashMap<String, String>,
+) -> String {
+    let graphics: Vec<&JsonValue> = graphics.as_array().into_iter().flatten().collect();
+    // An empty layout comes back as an empty list.
+    let controls: Vec<(&String, &JsonValue)> = layout.as_object().into_iter().flatten().collect();
+
+    let rects = graphics
+        .iter()
+        .copied()
+        .chain(controls.iter().map(|(_, control)| *control))
+        .filter_map(Rect::of);
+    let (mut width, mut height) = (0.0_f64, 0.0_f64);
+    for rect in rects {
+        width = width.max(rect.x + rect.width);
+        height = height.max(rect.y + rect.height);
+    }
+    let (width, height) = (width + PADDING, height + PADDING);
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="{font}">"#,
+        w = width,
+        h = height,
+        font = FONT_FAMILY
+    );
+    let _ = writeln!(svg, "  <title>{}</title>", escape(title));
+    let _ = writeln!(
+        svg,
+        r#"  <rect width="100%" height="100%" fill="rgb(240,240,240)"/>"#
+    );
+    for graphic in graphics {
+        if let Some(element) = graphic_svg(graphic) {
+            let _ = writeln!(svg, "  {}", element);
+        }
+    }
+    for (name, control) in controls {
+        let style = control
+            .get("Style")
+            .and_then(JsonValue::as_str)
+            .map(str::to_string)
+            .unwrap_or_else(|| default_style(name, control_types).to_string());
+        if let Some(element) = control_svg(name, control, &style) {
+            let _ = writeln!(svg, "  {}", element);
+        }
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// The style Designer draws a control in when the layout doesn't give one. Controls with a
+/// `Count` are laid out as `Name 1`, `Name 2`, ...
+fn default_style<'a>(name: &str, control_types: &'a HashMap<String, String>) -> &'a str {
+    let base = match name.rsplit_once(' ') {
+        Some((base, index))
+            if index.parse::<u32>().is_ok() && !control_types.contains_key(name) =>
+        {
+            base
+        }
+        _ => name,
+    };
+    match control_types.get(base).map(String::as_str) {
+        Some("Knob") => "Knob",
+        Some("Indicator") => "Led",
+        Some("Text") => "Text",
+        _ => "Button",
+    }
+}
+
+fn graphic_svg(graphic: &JsonValue) -> Option<String> {
+    let kind = graphic.get("Type")?.as_str()?;
+    let rect = Rect::of(graphic)?;
+    let fill = graphic.get("Fill").and_then(color);
+    let stroke = graphic.get("StrokeColor").and_then(color);
+    let stroke_width = graphic
+        .get("StrokeWidth")
+        .and_then(JsonValue::as_f64)
+        .unwrap_or(0.0);
+    let outline = |default_stroke: &str, default_width: f64| {
+        let stroke_width = if stroke_width > 0.0 {
+            stroke_width
+        } else {
+            default_width
+        };
+        format!(
+            r#"<rect {} rx="{}" fill="{}" stroke="{}" stroke-width="{}"/>"#,
+            rect.attributes(),
+            number(graphic, "CornerRadius").unwrap_or(0.0),
+            fill.as_deref().unwrap_or("none"),
+            stroke.as_deref().unwrap_or(default_stroke),
+            stroke_width
+        )
+    };
+
+    let element = match kind {
+        "GroupBox" => {
+            let font_size = number(graphic, "FontSize").unwrap_or(DEFAULT_FONT_SIZE);
+            let label = Rect {
+                height: font_size + 4.0,
+                x: rect.x + 4.0,
+                width: rect.width - 8.0,
+                ..rect
+            };
+            format!(
+                "<g>{}{}</g>",
+                outline("black", 1.0),
+                text_svg(graphic, label, "Left", "Top").unwrap_or_default()
+            )
+        }
+        "Text" | "Label" => {
+            let background = if fill.is_some() || stroke_width > 0.0 {
+                outline("none", 0.0)
+            } else {
+                String::new()
+            };
+            format!(
+                "<g>{}{}</g>",
+                background,
+                text_svg(graphic, rect, "Center", "Center").unwrap_or_default()
+            )
+        }
+        "Header" => {
+            // A heading with a rule either side. SVG can't measure text, so the gap is a guess.
+            let font_size = number(graphic, "FontSize").unwrap_or(DEFAULT_FONT_SIZE);
+            let text = graphic
+                .get("Text")
+                .and_then(JsonValue::as_str)
+                .unwrap_or("");
+            let gap = (text.chars().count() as f64 * font_size * 0.3 + 4.0).min(rect.width / 2.0);
+            let middle = rect.x + rect.width / 2.0;
+            let y = rect.y + rect.height / 2.0;
+            let rule = stroke.as_deref().unwrap_or("rgb(128,128,128)");
+            format!(
+                r#"<g><line x1="{}" y1="{y}" x2="{}" y2="{y}" stroke="{rule}"/><line x1="{}" y1="{y}" x2="{}" y2="{y}" stroke="{rule}"/>{}</g>"#,
+                rect.x,
+                middle - gap,
+                middle + gap,
+                rect.x + rect.width,
+                text_svg(graphic, rect, "Center", "Center").unwrap_or_default(),
+            )
+        }
+        "Image" => {
+            let image = graphic.get("Image")?.as_str()?;
+            // Designer takes base64 PNG, JPEG or SVG. Browsers sniff PNG and JPEG, not SVG.
+            let mime = if image.starts_with("PHN2Zy") || image.starts_with("PD94bWw") {
+                "image/svg+xml"
+            } else {
+                "image/png"
+            };
+            format!(
+                r#"<image {} preserveAspectRatio="xMidYMid meet" href="data:{};base64,{}"/>"#,
+                rect.attributes(),
+                mime,
+                image
+            )
+        }
+        // Anything else is outlined so it's clear something is there.
+        other => format!(
+            r#"<rect {} fill="none" stroke="rgb(128,128,128)" stroke-dasharray="4 2"><title>{}</title></rect>"#,
+            rect.attributes(),
+            escape(other)
+        ),
+    };
+    Some(element)
+}
+
+fn control_svg(name: &str, control: &JsonValue, style: &str) -> Option<String> {
+    if style == "None" || control.get("IsInvisible") == Some(&JsonValue::Bool(true)) {
+        return None;
+    }
+    let rect = Rect::of(control)?;
+    let tooltip = control
+        .get("PrettyName")
+        .and_then(JsonValue::as_str)
+        .unwrap_or(name);
+    let fill = control.get("Color").and_then(color);
+
+    let shape = match style {
+        "Knob" | "Led" => {
+            let default_fill = if style == "Led" {
+                "rgb(255,0,0)"
+            } else {
+                "rgb(90,90,90)"
+            };
+            format!(
+                r#"<ellipse cx="{}" cy="{}" rx="{}" ry="{}" fill="{}" stroke="rgb(60,60,60)"/>"#,
+                rect.x + rect.width / 2.0,
+                rect.y + rect.height / 2.0,
+                rect.width / 2.0,
+                rect.height / 2.0,
+                fill.as_deref().unwrap_or(default_fill)
+            )
+        }
+        "Text" | "ComboBox" | "ListBox" => format!(
+            r#"<rect {} fill="{}" stroke="rgb(128,128,128)"/>"#,
+            rect.attributes(),
+            fill.as_deref().unwrap_or("white")
+        ),
+        "Fader" | "Meter" => format!(
+            r#"<rect {} fill="rgb(60,60,60)" stroke="rgb(128,128,128)"/>"#,
+            rect.attributes()
+        ),
+        _ => format!(
+            r#"<rect {} rx="2" fill="{}" stroke="rgb(100,100,100)"/>"#,
+            rect.attributes(),
+            fill.as_deref().unwrap_or("rgb(205,205,205)")
+        ),
+    };
+    let legend = legend_svg(control, rect).unwrap_or_default();
+    Some(format!(
+        "<g><title>{}</title>{}{}</g>",
+        escape(tooltip),
+        shape,
+        legend
+    ))
+}
+
+/// A control's `Legend`, drawn like text.
+fn legend_svg(control: &JsonValue, rect: Rect) -> Option<String> {
+    let legend = control.get("Legend")?.as_str()?;
+    let mut with_text = control.clone();
+    with_text["Text"] = JsonValue::String(legend.to_string());
+    text_svg(&with_text, rect, "Center", "Center")
+}
+
+/// The item's `Text` inside `rect`, honouring `FontSize`, `HTextAlign`, `VTextAlign` and `Color`.
+fn text_svg(item: &JsonValue, rect: Rect, h_align: &str, v_align: &str) -> Option<String> {
+    let text = item.get("Text")?.as_str()?;
+    if text.is_empty() {
+        return None;
+    }
+    let font_size = number(item, "FontSize").unwrap_or(DEFAULT_FONT_SIZE);
+    let h_align = item
+        .get("HTextAlign")
+        .and_then(JsonValue::as_str)
+        .unwrap_or(h_align);
+    let v_align = item
+        .get("VTextAlign")
+        .and_then(JsonValue::as_str)
+        .unwrap_or(v_align);
+    let (x, anchor) = match h_align {
+        "Left" => (rect.x + 2.0, "start"),
+        "Right" => (rect.x + rect.width - 2.0, "end"),
+        _ => (rect.x + rect.width / 2.0, "middle"),
+    };
+    let (y, baseline) = match v_align {
+        "Top" => (rect.y, "hanging"),
+        "Bottom" => (rect.y + rect.height, "auto"),
+        _ => (rect.y + rect.height / 2.0, "central"),
+    };
+    // On controls `Color` is the control's, not the text's.
+    let text_color = if item.get("Type").is_some() {
+        item.get("Color").and_then(color)
+    } else {
+        item.get("TextColor").and_then(color)
+    };
+    Some(format!(
+        r#"<text x="{}" y="{}" font-size="{}" text-anchor="{}" dominant-baseline="{}" fill="{}">{}</text>"#,
+        x,
+        y,
+        font_size,
+        anchor,
+        baseline,
+        text_color.as_deref().unwrap_or("black"),
+        escape(text)
+    ))
+}
+
+fn number(item: &JsonValue, key: &str) -> Option<f64> {
+    item.get(key)?.as_f64()
+}
+
+/// A Q-SYS colour, `{ r, g, b }` or `{ r, g, b, a }` from 0 to 255, or a CSS colour string.
+fn color(value: &JsonValue) -> Option<String> {
+    match value {
+        JsonValue::String(s) => Some(escape(s)),
+        JsonValue::Array(parts) => {
+            let parts: Option<Vec<f64>> = parts.iter().map(JsonValue::as_f64).collect();
+            match parts?.as_slice() {
+                [r, g, b] => Some(format!("rgb({},{},{})", r, g, b)),
+                [r, g, b, a] => Some(format!("rgba({},{},{},{})", r, g, b, a / 255.0)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+pub fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_page_svg() {
+        let layout = json!({
+            "SendButton": {
+                "PrettyName": "Buttons~Send <It>",
+                "Position": [105, 42],
+                "Size": [50, 16],
+                "Color": [0, 0, 0],
+            },
+            "Gain 2": { "Position": [0, 0], "Size": [36, 36] },
+        });
+        let graphics = json!([
+            {
+                "Type": "GroupBox",
+                "Text": "Control",
+                "Fill": [200, 200, 200],
+                "StrokeWidth": 1,
+                "Position": [5, 5],
+                "Size": [200, 100],
+            },
+            {
+                "Type": "Text",
+                "Text": "Say Hello:",
+                "Position": [10, 42],
+                "Size": [90, 16],
+                "FontSize": 14,
+                "HTextAlign": "Right",
+            },
+        ]);
+        let control_types = HashMap::from([("Gain".to_string(), "Knob".to_string())]);
+        let svg = page_svg("Control", &layout, &graphics, &control_types);
+
+        assert!(
+            svg.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" width="210" height="110""#)
+        );
+        assert!(svg.contains(
+            r#"<rect x="5" y="5" width="200" height="100" rx="0" fill="rgb(200,200,200)" stroke="black" stroke-width="1"/>"#
+        ));
+        assert!(svg.contains(
+            r#"<text x="98" y="50" font-size="14" text-anchor="end" dominant-baseline="central" fill="black">Say Hello:</text>"#
+        ));
+        assert!(svg.contains(
+            r#"<g><title>Buttons~Send &lt;It&gt;</title><rect x="105" y="42" width="50" height="16" rx="2" fill="rgb(0,0,0)""#
+        ));
+        assert!(svg.contains(r#"<g><title>Gain 2</title><ellipse cx="18" cy="18""#));
+    }
+
+    #[test]
+    fn test_empty_page_svg() {
+        let svg = page_svg("Setup", &json!([]), &json!([]), &HashMap::new());
+        assert!(svg.contains(r#"width="5" height="5""#));
+        assert!(svg.ends_with("</svg>\n"));
+    }
+
+    #[test]
+    fn test_color() {
+        assert_eq!(color(&json!([1, 2, 3])), Some("rgb(1,2,3)".to_string()));
+        assert_eq!(
+            color(&json!([255, 0, 0, 51])),
+            Some("rgba(255,0,0,0.2)".to_string())
+        );
+        assert_eq!(color(&json!("#FF0000")), Some("#FF0000".to_string()));
+        assert_eq!(color(&json!([1, 2])), None);
+    }
+
+    #[test]
+    fn test_page_file_name() {
+        assert_eq!(page_file_name(0, Some("Control")), "1-control.svg");
+        assert_eq!(
+            page_file_name(2, Some("Setup / Advanced")),
+            "3-setup---advanced.svg"
+        );
+        assert_eq!(page_file_name(0, None), "1-layout.svg");
+    }
+}
@@ -0,0 +1,189 @@
+use std::{
+    error::Error,
+    fs,
+    io::{self, BufRead, IsTerminal, Write},
+    path::{Path, PathBuf},
+    process::exit,
+};
+
+use mlua::{Function, Lua, MultiValue, Value};
+use serde_json::{Map, Value as JsonValue};
+
+use super::inspect::{lua_literal, PluginSource};
+use crate::{
+    assets::DEFINITIONS_DIR,
+    config::ProjectPaths,
+    lua::{designer::to_json, error_message, parser::quote_string, runtime::start_runtime},
+};
+
+const REPL_HELP: &str = "qplug runtime emulator. Change controls with qplug.set(\"Name\", value) \
+or qplug.press(\"Name\"), move the clock with qplug.advance(seconds). Ctrl-D quits.";
+
+/// `qplug run`: start the plugin with emulated `Controls` and `Timer`, then run `script` against
+/// it, or read Lua from stdin if there's no script. `props` is a JSON object of property values.
+pub fn run(
+    paths: &ProjectPaths,
+    file: Option<&PathBuf>,
+    props: Option<&PathBuf>,
+    script: Option<&PathBuf>,
+) {
+    let values = match props.map(|path| read_props(path)) {
+        Some(Ok(values)) => values,
+        Some(Err(e)) => {
+            eprintln!("Could not read {}: {}", props.unwrap().display(), e);
+            exit(1);
+        }
+        None => Map::new(),
+    };
+
+    let plugin = PluginSource::load(paths, file);
+    let lua = match start_runtime(&DEFINITIONS_DIR, &plugin.source, &plugin.chunk, &values) {
+        Ok(lua) => lua,
+        Err(e) => {
+            eprintln!("error: {}", plugin.relocate(&error_message(&e)));
+            exit(1);
+        }
+    };
+
+    match script {
+        Some(script) => {
+            let source = fs::read_to_string(script).unwrap_or_else(|e| {
+                eprintln!("Could not read {}: {}", script.display(), e);
+                exit(1);
+            });
+            let result = lua
+                .load(&source)
+                .set_name(format!("@{}", script.display()))
+                .exec();
+            if let Err(e) = result {
+                eprintln!("error: {}", plugin.relocate(&error_message(&e)));
+                exit(1);
+            }
+        }
+        None => repl(&lua, &plugin),
+    }
+}
+
+/// Property values by name, e.g. `{ "Input Count": 4, "Model": "Large" }`.
+pub fn read_props(path: &Path) -> Result<Map<String, JsonValue>, Box<dyn Error>> {
+    match serde_json::from_str(&fs::read_to_string(path)?)? {
+        JsonValue::Object(values) => Ok(values),
+        _ => Err("expected a JSON object of property names and values".into()),
+    }
+}
+
+/// Read Lua from stdin a statement at a time, like the standalone `lua` does, printing the value
+/// of expressions.
+fn repl(lua: &Lua, plugin: &PluginSource) {
+    let interactive = io::stdin().is_terminal();
+    if interactive {
+        eprintln!("{}", REPL_HELP);
+    }
+
+    let mut buffer = String::new();
+    let mut lines = io::stdin().lock();
+    loop {
+        if interactive {
+            print!("{}", if buffer.is_empty() { "> " } else { ">> " });
+            let _ = io::stdout().flush();
+        }
+        let mut line = String::new();
+        match lines.read_line(&mut line) {
+            Ok(0) => break,
+            Ok(_) => buffer.push_str(&line),
+            Err(e) => {
+                eprintln!("Could not read stdin: {}", e);
+                exit(1);
+            }
+        }
+
+        match eval(lua, &buffer) {
+            Err(mlua::Error::SyntaxError {
+                incomplete_input: true,
+                ..
+            }) => continue,
+            Ok(values) => {
+                for value in values {
+                    println!("{}", show(lua, value));
+                }
+            }
+            Err(e) => eprintln!("error: {}", plugin.relocate(&error_message(&e))),
+        }
+        buffer.clear();
+    }
+}
+
+fn eval<'lua>(lua: &'lua Lua, code: &str) -> mlua::Result<MultiValue<'lua>> {
+    // Try it as an expression first, so `Controls.Gain.Value` prints its value.
+    match lua
+        .load(format!("return {}", code))
+        .set_name("=stdin")
+        .eval()
+    {
+        Err(mlua::Error::SyntaxError { .. }) => lua.load(code).set_name("=stdin").eval(),
+        result => result,
+    }
+}
+
+/// A value as the REPL prints it: tables as constructors, except those with a `__tostring`,
+/// such as controls.
+fn show(lua: &Lua, value: Value) -> String {
+    let has_tostring = match &value {
+        Value::Table(table) => table
+            .get_metatable()
+            .is_some_and(|meta| meta.contains_key("__tostring").unwrap_or(false)),
+        _ => false,
+    };
+    match value {
+        Value::Table(_) if !has_tostring => lua_literal(&to_json(&value), 0),
+        Value::String(s) => quote_string(&s.to_string_lossy()),
+        other => lua
+            .globals()
+            .get::<_, Function>("tostring")
+            .and_then(|tostring| tostring.call::<_, String>(other))
+            .unwrap_or_else(|e| error_message(&e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_props() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("props.json");
+        fs::write(&path, r#"{ "Input Count": 4, "Model": "Large" }"#).unwrap();
+        let values = read_props(&path).unwrap();
+        assert_eq!(values["Input Count"], 4);
+        assert_eq!(values["Model"], "Large");
+
+        fs::write(&path, "[4]").unwrap();
+        assert!(read_props(&path).is_err());
+    }
+
+    #[test]
+    fn test_eval_and_show() {
+        let lua = Lua::new();
+        lua.load("t = { 1, 2 }; c = setmetatable({}, { __tostring = function() return 'C' end })")
+            .exec()
+            .unwrap();
+        let shown = |code: &str| -> Vec<String> {
+            eval(&lua, code)
+                .unwrap()
+                .into_iter()
+                .map(|value| show(&lua, value))
+                .collect()
+        };
+        assert_eq!(shown("t"), ["{ 1, 2 }"]);
+        assert_eq!(shown("c, 'x', 1.5"), ["C", "\"x\"", "1.5"]);
+        assert!(shown("x = 1").is_empty());
+        assert!(matches!(
+            eval(&lua, "if x then"),
+            Err(mlua::Error::SyntaxError {
+                incomplete_input: true,
+                ..
+            })
+        ));
+    }
+}
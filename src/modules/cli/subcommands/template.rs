@@ -0,0 +1,55 @@
+use std::process::exit;
+
+use crate::{
+    config::{Config, Template},
+    template::{GitSource, TemplateCache},
+};
+
+/// Refresh cached git templates. Without a URL, refresh the configured template and
+/// everything already in the cache.
+pub fn update(url: Option<&String>, config: &Config) {
+    let cache = TemplateCache::user_cache().expect("Unable to locate the cache directory.");
+
+    let candidates = match url {
+        Some(url) => vec![GitSource::parse(url)],
+        None => {
+            let mut candidates = cache.sources();
+            if let Template::Url(url) = &config.template {
+                candidates.insert(0, GitSource::parse(url));
+            }
+            candidates
+        }
+    };
+
+    // Cache entries are keyed by URL and ref only.
+    let mut sources: Vec<GitSource> = Vec::new();
+    for source in candidates {
+        if !sources
+            .iter()
+            .any(|s| s.url == source.url && s.reference == source.reference)
+        {
+            sources.push(source);
+        }
+    }
+
+    if sources.is_empty() {
+        println!("No git templates configured or cached.");
+        return;
+    }
+
+    let mut failed = false;
+    for source in sources {
+        match cache.update(&source) {
+            Ok(_) => println!("Updated {}", source.url),
+            Err(e) => {
+                eprintln!("Failed to update {}: {}", source.url, e);
+                failed = true;
+            }
+        }
+    }
+    println!("Template cache: {}", cache.root().display());
+
+    if failed {
+        exit(1);
+    }
+}
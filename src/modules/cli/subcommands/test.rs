@@ -0,0 +1,388 @@
+use std::{
+    path::{Path, PathBuf},
+    process::exit,
+    time::Instant,
+};
+
+use clap::ValueEnum;
+use serde_json::Map;
+
+use super::{inspect::PluginSource, preview::escape, run::read_props};
+use crate::{
+    assets::DEFINITIONS_DIR,
+    config::ProjectPaths,
+    files::{find_project_dir, walk_files},
+    lua::{
+        error_message,
+        runtime::start_runtime,
+        spec::{add_module_searcher, run_spec, Outcome, TestResult},
+    },
+};
+
+/// Where `qplug test` looks for specs, relative to the project root.
+const TESTS_DIR: &str = "tests";
+
+const SPEC_SUFFIX: &str = "_spec.lua";
+
+#[derive(ValueEnum, Clone, Debug)]
+#[clap(rename_all = "lower")]
+pub enum ReportFormat {
+    Human,
+    Junit,
+}
+
+/// What one spec file did.
+struct SpecReport {
+    /// The spec's path as it's shown, relative to the project root.
+    path: String,
+    /// Its tests, or why it couldn't run them.
+    results: Result<Vec<TestResult>, String>,
+    seconds: f64,
+}
+
+/// `qplug test`: run `files`, or every `*_spec.lua` under `tests/`, each against a fresh start
+/// of the plugin with emulated `Controls` and `Timer`, as in `qplug run`.
+pub fn test(
+    paths: &ProjectPaths,
+    files: &[PathBuf],
+    props: Option<&PathBuf>,
+    format: ReportFormat,
+) {
+    let Some(root_path) = find_project_dir(None) else {
+        eprintln!("Not a qplug project. Run `qplug new` or `qplug init` first.");
+        exit(1);
+    };
+    let values = match props.map(|path| read_props(path)) {
+        Some(Ok(values)) => values,
+        Some(Err(e)) => {
+            eprintln!("Could not read {}: {}", props.unwrap().display(), e);
+            exit(1);
+        }
+        None => Map::new(),
+    };
+
+    let specs = if files.is_empty() {
+        find_specs(&root_path.join(TESTS_DIR))
+    } else {
+        files.to_vec()
+    };
+    if specs.is_empty() {
+        eprintln!(
+            "No specs found. Add `*{}` files under {}/.",
+            SPEC_SUFFIX, TESTS_DIR
+        );
+        exit(1);
+    }
+
+    let plugin = PluginSource::load(paths, None);
+    let plugin_path = paths.plugin_path(&root_path);
+    let reports: Vec<SpecReport> = specs
+        .iter()
+        .map(|spec| run_spec_file(&plugin, &plugin_path, &root_path, spec, &values))
+        .collect();
+
+    match format {
+        ReportFormat::Human => print!("{}", human_report(&reports)),
+        ReportFormat::Junit => print!("{}", junit_report(&reports)),
+    }
+    if !reports.iter().all(passed) {
+        exit(1);
+    }
+}
+
+/// Every `*_spec.lua` below `dir`, in a stable order.
+fn find_specs(dir: &Path) -> Vec<PathBuf> {
+    walk_files(dir)
+        .into_iter()
+        .filter(|path| {
+            path.file_name()
+                .is_some_and(|name| name.to_string_lossy().ends_with(SPEC_SUFFIX))
+        })
+        .collect()
+}
+
+fn run_spec_file(
+    plugin: &PluginSource,
+    plugin_path: &Path,
+    root_path: &Path,
+    spec: &Path,
+    values: &Map<String, serde_json::Value>,
+) -> SpecReport {
+    let absolute = spec.canonicalize().unwrap_or_else(|_| spec.to_path_buf());
+    let root = root_path
+        .canonicalize()
+        .unwrap_or_else(|_| root_path.to_path_buf());
+    let path = absolute
+        .strip_prefix(&root)
+        .unwrap_or(spec)
+        .display()
+        .to_string();
+
+    let started = Instant::now();
+    let results = std::fs::read_to_string(spec)
+        .map_err(|e| format!("Could not read {}: {}", spec.display(), e))
+        .and_then(|source| {
+            start_runtime(&DEFINITIONS_DIR, &plugin.source, &plugin.chunk, values)
+                .and_then(|lua| {
+                    add_module_searcher(&lua, plugin_path, root_path)?;
+                    run_spec(&lua, &source, &format!("@{}", path))
+                })
+                .map_err(|e| plugin.relocate(&error_message(&e)))
+        })
+        .map(|results| {
+            results
+                .into_iter()
+                .map(|result| TestResult {
+                    outcome: match result.outcome {
+                        Outcome::Failed(message) => Outcome::Failed(plugin.relocate(&message)),
+                        outcome => outcome,
+                    },
+                    ..result
+                })
+                .collect()
+        });
+
+    SpecReport {
+        path,
+        results,
+        seconds: started.elapsed().as_secs_f64(),
+    }
+}
+
+fn passed(report: &SpecReport) -> bool {
+    report.results.as_ref().is_ok_and(|results| {
+        results
+            .iter()
+            .all(|result| !matches!(result.outcome, Outcome::Failed(_)))
+    })
+}
+
+/// One line per test, `ok`, `FAIL` or `skip` and where it is, with the error under failures.
+fn human_report(reports: &[SpecReport]) -> String {
+    let (mut passes, mut failures, mut pending, mut errors) = (0, 0, 0, 0);
+    let mut out = String::new();
+    for report in reports {
+        let results = match &report.results {
+            Ok(results) => results,
+            Err(message) => {
+                errors += 1;
+                out.push_str(&format!("ERROR {}\n{}", report.path, indent(message)));
+                continue;
+            }
+        };
+        for result in results {
+            let status = match &result.outcome {
+                Outcome::Passed => {
+                    passes += 1;
+                    "ok   "
+                }
+                Outcome::Failed(_) => {
+                    failures += 1;
+                    "FAIL "
+                }
+                Outcome::Pending => {
+                    pending += 1;
+                    "skip "
+                }
+            };
+            out.push_str(&format!(
+                "{} {}:{} {}\n",
+                status, report.path, result.line, result.name
+            ));
+            if let Outcome::Failed(message) = &result.outcome {
+                out.push_str(&indent(message));
+            }
+        }
+    }
+
+    out.push_str(&format!(
+        "\n{} passed, {} failed, {} pending.\n",
+        passes, failures, pending
+    ));
+    if errors > 0 {
+        out.push_str(&format!(
+            "{} spec{} failed to run.\n",
+            errors,
+            if errors == 1 { "" } else { "s" }
+        ));
+    }
+    out
+}
+
+fn indent(message: &str) -> String {
+    message
+        .lines()
+        .map(|line| format!("      {}\n", line))
+        .collect()
+}
+
+/// The reports as JUnit XML, one `<testsuite>` per spec file. A spec that couldn't run is a suite
+/// with a single errored test.
+fn junit_report(reports: &[SpecReport]) -> String {
+    let count = |report: &SpecReport, matches: fn(&Outcome) -> bool| match &report.results {
+        Ok(results) => results.iter().filter(|r| matches(&r.outcome)).count(),
+        Err(_) => 0,
+    };
+    let tests = |report: &SpecReport| report.results.as_ref().map_or(1, |results| results.len());
+    let failures = |report: &SpecReport| count(report, |o| matches!(o, Outcome::Failed(_)));
+    let skipped = |report: &SpecReport| count(report, |o| matches!(o, Outcome::Pending));
+    let errors = |report: &SpecReport| usize::from(report.results.is_err());
+
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str(&format!(
+        "<testsuites name=\"qplug\" tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\" time=\"{:.3}\">\n",
+        reports.iter().map(tests).sum::<usize>(),
+        reports.iter().map(failures).sum::<usize>(),
+        reports.iter().map(errors).sum::<usize>(),
+        reports.iter().map(skipped).sum::<usize>(),
+        reports.iter().map(|report| report.seconds).sum::<f64>(),
+    ));
+
+    for report in reports {
+        let path = escape(&report.path);
+        out.push_str(&format!(
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\" time=\"{:.3}\">\n",
+            path,
+            tests(report),
+            failures(report),
+            errors(report),
+            skipped(report),
+            report.seconds,
+        ));
+        match &report.results {
+            Ok(results) => {
+                for result in results {
+                    let open = format!(
+                        "    <testcase name=\"{}\" classname=\"{}\" file=\"{}\" line=\"{}\"",
+                        escape(&result.name),
+                        path,
+                        path,
+                        result.line
+                    );
+                    match &result.outcome {
+                        Outcome::Passed => out.push_str(&format!("{}/>\n", open)),
+                        Outcome::Pending => {
+                            out.push_str(&format!("{}>\n      <skipped/>\n    </testcase>\n", open))
+                        }
+                        Outcome::Failed(message) => out.push_str(&format!(
+                            "{}>\n      <failure message=\"{}\">{}</failure>\n    </testcase>\n",
+                            open,
+                            escape(message.lines().next().unwrap_or_default()),
+                            escape(message)
+                        )),
+                    }
+                }
+            }
+            Err(message) => out.push_str(&format!(
+                "    <testcase name=\"{}\" classname=\"{}\" file=\"{}\">\n      <error message=\"{}\">{}</error>\n    </testcase>\n",
+                path,
+                path,
+                path,
+                escape(message.lines().next().unwrap_or_default()),
+                escape(message)
+            )),
+        }
+        out.push_str("  </testsuite>\n");
+    }
+    out.push_str("</testsuites>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    fn reports() -> Vec<SpecReport> {
+        vec![
+            SpecReport {
+                path: "tests/frame_spec.lua".to_string(),
+                results: Ok(vec![
+                    TestResult {
+                        name: "frame parses".to_string(),
+                        line: 2,
+                        outcome: Outcome::Passed,
+                    },
+                    TestResult {
+                        name: "frame rejects <bad> sums".to_string(),
+                        line: 6,
+                        outcome: Outcome::Failed(
+                            "tests/frame_spec.lua:7: expected 1, got 2".to_string(),
+                        ),
+                    },
+                    TestResult {
+                        name: "frame splits".to_string(),
+                        line: 9,
+                        outcome: Outcome::Pending,
+                    },
+                ]),
+                seconds: 0.0125,
+            },
+            SpecReport {
+                path: "tests/broken_spec.lua".to_string(),
+                results: Err("tests/broken_spec.lua:1: unexpected symbol".to_string()),
+                seconds: 0.0,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_find_specs() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("protocol")).unwrap();
+        for name in [
+            "b_spec.lua",
+            "protocol/a_spec.lua",
+            "helpers.lua",
+            "spec.lua",
+        ] {
+            fs::write(dir.path().join(name), "").unwrap();
+        }
+        let found: Vec<_> = find_specs(dir.path())
+            .into_iter()
+            .map(|path| path.strip_prefix(dir.path()).unwrap().to_path_buf())
+            .collect();
+        assert_eq!(
+            found,
+            [
+                PathBuf::from("b_spec.lua"),
+                PathBuf::from("protocol/a_spec.lua")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_human_report() {
+        let reports = reports();
+        assert_eq!(
+            human_report(&reports),
+            "ok    tests/frame_spec.lua:2 frame parses\n\
+             FAIL  tests/frame_spec.lua:6 frame rejects <bad> sums\n      \
+             tests/frame_spec.lua:7: expected 1, got 2\n\
+             skip  tests/frame_spec.lua:9 frame splits\n\
+             ERROR tests/broken_spec.lua\n      \
+             tests/broken_spec.lua:1: unexpected symbol\n\
+             \n1 passed, 1 failed, 1 pending.\n\
+             1 spec failed to run.\n"
+        );
+        assert!(!passed(&reports[0]));
+        assert!(!passed(&reports[1]));
+    }
+
+    #[test]
+    fn test_junit_report() {
+        let xml = junit_report(&reports());
+        assert!(xml.contains(
+            "<testsuites name=\"qplug\" tests=\"4\" failures=\"1\" errors=\"1\" skipped=\"1\" time=\"0.013\">"
+        ));
+        assert!(xml.contains(
+            "<testcase name=\"frame parses\" classname=\"tests/frame_spec.lua\" file=\"tests/frame_spec.lua\" line=\"2\"/>"
+        ));
+        assert!(xml.contains("name=\"frame rejects &lt;bad&gt; sums\""));
+        assert!(xml.contains("<failure message=\"tests/frame_spec.lua:7: expected 1, got 2\">"));
+        assert!(xml.contains("<skipped/>"));
+        assert!(xml.contains("<error message=\"tests/broken_spec.lua:1: unexpected symbol\">"));
+        assert!(xml.ends_with("</testsuites>\n"));
+    }
+}
@@ -5,13 +5,63 @@ use mlua::{
 };
 use serde::Serialize;
 use std::{
+    env,
     fs::{self},
-    path::PathBuf,
+    path::{Path, PathBuf},
+    process::exit,
 };
 
-use crate::assets::TEMPLATE_DIR;
+use crate::{
+    assets::TEMPLATE_DIR,
+    lua::{error_message, since::QsysVersion},
+    template::is_git_url,
+};
+
+use super::files::{find_project_dir, find_workspace_file, pwd, MARKER_FILE};
+
+/// Environment variable pointing at a config file to use instead of the global one.
+pub const CONFIG_ENV: &str = "QPLUG_CONFIG";
+/// Environment variable overriding `external_template`.
+pub const TEMPLATE_ENV: &str = "QPLUG_TEMPLATE";
+
+/// Overrides that decide which config file gets loaded and what ends up in it.
+///
+/// Config file precedence (first match wins):
+///   1. `--no-config` - no global config file is loaded at all.
+///   2. `--config <path>`
+///   3. `QPLUG_CONFIG`
+///   4. `~/.config/qplug/qplug.lua` (platform config dir)
+///   5. `~/.qplug.lua`
+///
+/// Values are then layered: built-in defaults < global config file < workspace
+/// `.qplug-workspace` < project `.qplug` < `QPLUG_TEMPLATE`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConfigOverrides {
+    pub config_file: Option<PathBuf>,
+    pub no_config: bool,
+    pub template: Option<String>,
+}
 
-use super::files::{find_project_dir, pwd, MARKER_FILE};
+impl ConfigOverrides {
+    pub fn from_env() -> Self {
+        ConfigOverrides {
+            config_file: env::var_os(CONFIG_ENV)
+                .filter(|v| !v.is_empty())
+                .map(PathBuf::from),
+            no_config: false,
+            template: env::var(TEMPLATE_ENV).ok().filter(|v| !v.is_empty()),
+        }
+    }
+
+    /// Apply command line flags on top of the environment. Flags always win.
+    pub fn with_cli(mut self, config_file: Option<PathBuf>, no_config: bool) -> Self {
+        if config_file.is_some() {
+            self.config_file = config_file;
+        }
+        self.no_config = no_config;
+        self
+    }
+}
 
 pub struct UserEnv<'a> {
     pub lua: &'a Lua,
@@ -24,22 +74,114 @@ pub struct Author {
     pub company: Option<String>,
 }
 
+impl Author {
+    /// Who to credit in copyright notices, e.g. `Jane Doe <jane@example.com> / ACME`.
+    pub fn copyright_holder(&self) -> Option<String> {
+        let person = match (&self.name, &self.email) {
+            (Some(name), Some(email)) => Some(format!("{} <{}>", name, email)),
+            (Some(name), None) => Some(name.to_owned()),
+            (None, Some(email)) => Some(email.to_owned()),
+            (None, None) => None,
+        };
+        match (person, &self.company) {
+            (Some(person), Some(company)) => Some(format!("{} / {}", person, company)),
+            (Some(person), None) => Some(person),
+            (None, Some(company)) => Some(company.to_owned()),
+            (None, None) => None,
+        }
+    }
+
+    /// A copyright line in the same style as the template's LICENSE.txt.
+    pub fn copyright_line(&self, year: i32) -> Option<String> {
+        self.copyright_holder()
+            .map(|holder| format!("Copyright (c) {}, {}", holder, year))
+    }
+}
+
 pub enum Template<'a> {
     Url(String),
     FileSystem(PathBuf),
     InMemoryDir(&'a include_dir::Dir<'static>),
 }
 
+/// Where a project keeps its sources. Set `plugin_src` and `entry` in `.qplug` for projects
+/// that don't use the default layout.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProjectPaths {
+    /// Source directory, relative to the project root.
+    pub plugin_src: PathBuf,
+    /// The file `require`s are resolved from, relative to `plugin_src`.
+    pub entry: PathBuf,
+}
+
+impl Default for ProjectPaths {
+    fn default() -> Self {
+        ProjectPaths {
+            plugin_src: PathBuf::from("plugin_src"),
+            entry: PathBuf::from("init.lua"),
+        }
+    }
+}
+
+impl ProjectPaths {
+    pub fn plugin_path(&self, root_path: &Path) -> PathBuf {
+        root_path.join(&self.plugin_src)
+    }
+
+    pub fn entry_path(&self, root_path: &Path) -> PathBuf {
+        self.plugin_path(root_path).join(&self.entry)
+    }
+}
+
 pub struct Config<'lua, 'a> {
     pub build_tool: Box<dyn Fn() + 'lua>,
     pub template: Template<'a>,
     pub me: Author,
+    pub paths: ProjectPaths,
+    /// SPDX identifier or path of the license for new plugins.
+    pub license: Option<String>,
+    /// Prepend an SPDX header to new modules and the compiled plugin.
+    pub license_header: bool,
+    /// Oldest Q-SYS Designer release the plugin has to run on.
+    pub target_qsys: Option<QsysVersion>,
 }
 
 impl<'lua, 'a> Config<'lua, 'a> {
     pub fn from_user_config(user_config: &'lua UserConfig) -> Self {
+        let defaults = ProjectPaths::default();
+        let path_setting = |value: &Value, default: PathBuf| match value {
+            Value::String(s) => PathBuf::from(s.to_str().unwrap()),
+            _ => default,
+        };
+        let paths = ProjectPaths {
+            plugin_src: path_setting(&user_config.plugin_src, defaults.plugin_src),
+            entry: path_setting(&user_config.entry, defaults.entry),
+        };
+
+        let license = match &user_config.license {
+            Value::String(s) => Some(s.to_str().unwrap().to_owned()),
+            _ => None,
+        };
+        let license_header = matches!(user_config.license_header, Value::Boolean(true));
+        let target_qsys = target_qsys(&user_config.target_qsys).unwrap_or_else(|e| {
+            eprintln!("Ignoring target_qsys: {}", e);
+            None
+        });
+
         // Internal implementation as a callable
-        let default_build_tool = crate::cli::subcommands::compile::compile;
+        let default_paths = paths.clone();
+        let default_spdx = license
+            .as_deref()
+            .filter(|_| license_header)
+            .map(crate::license::spdx_id);
+        let default_target = target_qsys.clone();
+        let default_build_tool = move || {
+            crate::cli::subcommands::compile::compile(
+                &default_paths,
+                default_spdx.as_deref(),
+                default_target.as_ref(),
+            )
+        };
 
         // Determine which build_tool to use
         let build_tool: Box<dyn Fn()> = match &user_config.build_tool {
@@ -51,7 +193,7 @@ impl<'lua, 'a> Config<'lua, 'a> {
         let template: Template = match &user_config.external_template {
             Value::String(s) => {
                 let template_str = s.to_str().unwrap();
-                if template_str.starts_with("http") {
+                if is_git_url(template_str) {
                     Template::Url(template_str.to_owned())
                 } else {
                     Template::FileSystem(PathBuf::from(template_str))
@@ -60,11 +202,15 @@ impl<'lua, 'a> Config<'lua, 'a> {
             _ => Template::InMemoryDir(&TEMPLATE_DIR),
         };
 
+        // Missing (or non-string) keys are treated as unset rather than the string "nil".
+        let field = |t: &Table, key: &str| -> Option<String> {
+            t.get::<_, Option<String>>(key).ok().flatten()
+        };
         let me: Author = match &user_config.me {
             Value::Table(t) => Author {
-                name: Some(t.get("name").unwrap_or(Value::Nil).to_string().unwrap()),
-                email: Some(t.get("email").unwrap_or(Value::Nil).to_string().unwrap()),
-                company: Some(t.get("company").unwrap_or(Value::Nil).to_string().unwrap()),
+                name: field(t, "name"),
+                email: field(t, "email"),
+                company: field(t, "company"),
             },
             _ => Author {
                 name: None,
@@ -77,8 +223,33 @@ impl<'lua, 'a> Config<'lua, 'a> {
             build_tool,
             template,
             me,
+            paths,
+            license,
+            license_header,
+            target_qsys,
         }
     }
+
+    /// The SPDX identifier to stamp on the compiled plugin, if `license_header` is on.
+    pub fn spdx_header_id(&self) -> Option<String> {
+        self.license
+            .as_deref()
+            .filter(|_| self.license_header)
+            .map(crate::license::spdx_id)
+    }
+}
+
+/// The `target_qsys` setting. It has to be a string: as a Lua number `9.10` would read as `9.1`.
+pub fn target_qsys(value: &Value) -> Result<Option<QsysVersion>, String> {
+    match value {
+        Value::Nil => Ok(None),
+        Value::String(s) => s
+            .to_str()
+            .map_err(|_| "target_qsys is not valid UTF-8".to_string())?
+            .parse()
+            .map(Some),
+        _ => Err("target_qsys must be a string like \"9.4\"".to_string()),
+    }
 }
 
 #[derive(Serialize, Debug, Clone)]
@@ -86,36 +257,185 @@ pub struct UserConfig<'lua> {
     pub build_tool: Value<'lua>,        // default to built-in
     pub external_template: Value<'lua>, // can be path or url - default to built-in template
     pub me: Value<'lua>,
+    pub plugin_src: Value<'lua>,     // default to "plugin_src"
+    pub entry: Value<'lua>,          // default to "init.lua"
+    pub license: Value<'lua>,        // SPDX id or path, default to the template's license
+    pub license_header: Value<'lua>, // default to false
+    pub target_qsys: Value<'lua>,    // Designer version like "9.4", default to none
 }
 
 impl UserConfig<'_> {
     pub fn new(lua: &Lua) -> UserConfig<'_> {
-        let user_config = match find_config_file() {
-            Some(path) => {
-                // Create a function that will return the table form the user config and call it
-                lua.load(fs::read_to_string(&path).unwrap())
-                    .into_function()
-                    .unwrap()
-                    .call(Nil)
-                    .unwrap()
-            }
-            None => {
-                let lua_config = lua.create_table().expect("Table creation failed");
-                lua_config.set("external_template", Value::Nil).unwrap();
-                lua_config.set("build_tool", Value::Nil).unwrap();
-                lua_config.set("me", Value::Nil).unwrap();
-                lua_config
-            }
-        };
+        UserConfig::with_overrides(lua, &ConfigOverrides::from_env())
+    }
+
+    pub fn with_overrides<'lua>(lua: &'lua Lua, overrides: &ConfigOverrides) -> UserConfig<'lua> {
+        UserConfig::from_layers(lua, &config_layers(overrides, &pwd()), overrides)
+    }
 
-        overload_global_config(&user_config, None, lua);
+    /// Merge `layers` in order (later layers win), then apply the environment overrides.
+    pub fn from_layers<'lua>(
+        lua: &'lua Lua,
+        layers: &[ConfigLayer],
+        overrides: &ConfigOverrides,
+    ) -> UserConfig<'lua> {
+        let user_config = lua.create_table().expect("Table creation failed");
 
+        for layer in layers {
+            overload_config(&user_config, &load_config_file(lua, &layer.path));
+        }
+
+        if let Some(template) = &overrides.template {
+            user_config
+                .set("external_template", template.as_str())
+                .unwrap();
+        }
+
+        UserConfig::from_table(&user_config)
+    }
+
+    /// Read the settings out of a config table, as returned by a config file or merged from
+    /// several.
+    pub fn from_table<'lua>(config: &Table<'lua>) -> UserConfig<'lua> {
         UserConfig {
-            external_template: user_config.get("external_template").unwrap_or(Value::Nil),
-            build_tool: user_config.get("build_tool").unwrap_or(Value::Nil),
-            me: user_config.get("me").unwrap_or(Value::Nil),
+            external_template: config.get("external_template").unwrap_or(Value::Nil),
+            build_tool: config.get("build_tool").unwrap_or(Value::Nil),
+            me: config.get("me").unwrap_or(Value::Nil),
+            plugin_src: config.get("plugin_src").unwrap_or(Value::Nil),
+            entry: config.get("entry").unwrap_or(Value::Nil),
+            license: config.get("license").unwrap_or(Value::Nil),
+            license_header: config.get("license_header").unwrap_or(Value::Nil),
+            target_qsys: config.get("target_qsys").unwrap_or(Value::Nil),
         }
     }
+
+    /// Check that every setting that is set has the type [`Config::from_user_config`] reads.
+    /// Settings of the wrong type are otherwise ignored without a word.
+    pub fn validate(&self) -> Result<(), String> {
+        let expect = |key: &str, value: &Value, expected: &str| match value {
+            Value::Nil => Ok(()),
+            value if value.type_name() == expected => Ok(()),
+            value => Err(format!(
+                "`{}` must be a {}, got {}",
+                key,
+                expected,
+                value.type_name()
+            )),
+        };
+        expect("build_tool", &self.build_tool, "function")?;
+        expect("external_template", &self.external_template, "string")?;
+        expect("me", &self.me, "table")?;
+        expect("plugin_src", &self.plugin_src, "string")?;
+        expect("entry", &self.entry, "string")?;
+        expect("license", &self.license, "string")?;
+        expect("license_header", &self.license_header, "boolean")?;
+        if let Value::Table(me) = &self.me {
+            for key in ["name", "email", "company"] {
+                let value = me.get(key).unwrap_or(Value::Nil);
+                expect(&format!("me.{}", key), &value, "string")?;
+            }
+        }
+        target_qsys(&self.target_qsys).map(|_| ())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LayerKind {
+    Global,
+    Workspace,
+    Project,
+}
+
+impl std::fmt::Display for LayerKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LayerKind::Global => write!(f, "global"),
+            LayerKind::Workspace => write!(f, "workspace"),
+            LayerKind::Project => write!(f, "project"),
+        }
+    }
+}
+
+/// A config file that contributes to the final config.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigLayer {
+    pub kind: LayerKind,
+    pub path: PathBuf,
+}
+
+/// The config files that apply when running from `start`, in merge order:
+/// global config < workspace `.qplug-workspace` < project `.qplug`.
+pub fn config_layers(overrides: &ConfigOverrides, start: &Path) -> Vec<ConfigLayer> {
+    let mut layers = Vec::new();
+
+    if let Some(path) = resolve_config_file(overrides) {
+        layers.push(ConfigLayer {
+            kind: LayerKind::Global,
+            path,
+        });
+    }
+
+    let project_dir = find_project_dir(Some(start));
+    // The workspace file lives above the project, so start looking past the marker.
+    let workspace_start = match &project_dir {
+        Some(dir) => dir.parent().map(Path::to_path_buf),
+        None => Some(start.to_path_buf()),
+    };
+    if let Some(path) = workspace_start.and_then(|dir| find_workspace_file(&dir)) {
+        layers.push(ConfigLayer {
+            kind: LayerKind::Workspace,
+            path,
+        });
+    }
+
+    if let Some(dir) = project_dir {
+        layers.push(ConfigLayer {
+            kind: LayerKind::Project,
+            path: dir.join(MARKER_FILE),
+        });
+    }
+
+    layers
+}
+
+/// Run a config file and return the table it produces. Exits if it can't be read or run.
+pub fn load_config_file<'lua>(lua: &'lua Lua, path: &Path) -> Table<'lua> {
+    let contents = fs::read_to_string(path).unwrap_or_else(|e| {
+        eprintln!("Unable to read config file {}: {}", path.display(), e);
+        exit(1);
+    });
+    eval_config(lua, &contents, &path.to_string_lossy()).unwrap_or_else(|e| {
+        eprintln!("Invalid config file: {}", error_message(&e));
+        exit(1);
+    })
+}
+
+/// Run the config `source`, named `name` in errors, and return the table it produces. Only this
+/// one source is run, unlike [`UserConfig::with_overrides`], which merges every layer.
+pub fn eval_config<'lua>(lua: &'lua Lua, source: &str, name: &str) -> mlua::Result<Table<'lua>> {
+    // Create a function that will return the table form the user config and call it
+    lua.load(source)
+        .set_name(format!("@{}", name))
+        .into_function()?
+        .call(Nil)
+}
+
+/// Pick the config file to load, honoring `--no-config`, `--config` and `QPLUG_CONFIG`
+/// before falling back to [`find_config_file`].
+pub fn resolve_config_file(overrides: &ConfigOverrides) -> Option<PathBuf> {
+    if overrides.no_config {
+        return None;
+    }
+    match &overrides.config_file {
+        Some(path) => Some(path.to_owned()),
+        None => find_config_file(),
+    }
+}
+
+/// The first location [`find_config_file`] searches.
+pub fn default_config_path() -> PathBuf {
+    let base_dirs = BaseDirs::new().expect("No User Directory found");
+    base_dirs.config_dir().join("qplug/qplug.lua") // ~/.config on Linux/macOS, AppData/Roaming on Windows
 }
 
 pub fn find_config_file() -> Option<PathBuf> {
@@ -126,45 +446,23 @@ pub fn find_config_file() -> Option<PathBuf> {
         None
     }
     // Check in XDG config directories (Linux, macOS)
-    let base_dirs = BaseDirs::new().expect("No User Directory found");
-    let mut config_file = base_dirs.config_dir().join("qplug/qplug.lua"); // ~/.config on Linux/macOS, AppData/Roaming on Windows
-    match return_config(config_file) {
+    match return_config(default_config_path()) {
         Some(config) => Some(config),
         None => {
-            config_file = base_dirs.home_dir().join(".qplug.lua");
-            return_config(config_file)
+            let base_dirs = BaseDirs::new().expect("No User Directory found");
+            return_config(base_dirs.home_dir().join(".qplug.lua"))
         }
     }
 }
 
-fn overload_global_config<'a, 'lua>(
-    user_config: &'a Table<'a>,
-    local_config: Option<PathBuf>,
-    lua: &'lua Lua,
-) -> &'a Table<'a> {
-    // Either User provided config or find a marker file
-    let overload_config =
-        local_config.or_else(|| find_project_dir(Some(&pwd())).map(|path| path.join(MARKER_FILE)));
-
-    if overload_config.is_none() {
-        return user_config;
-    }
-
-    let new_config: Table<'lua> = lua
-        .load(fs::read_to_string(overload_config.unwrap()).unwrap())
-        .into_function()
-        .unwrap()
-        .call(Nil)
-        .unwrap();
-
+fn overload_config(user_config: &Table, new_config: &Table) {
     new_config
+        .clone()
         .for_each(|key: Value, val: Value| {
             user_config.set(key, val).unwrap();
             Ok(())
         })
         .unwrap();
-
-    user_config
 }
 
 #[cfg(test)]
@@ -173,12 +471,60 @@ mod tests {
     use std::fs;
     use tempfile::tempdir;
 
-    // FIX: Fix this test. Currently it is entirely up to the user's machine.
+    fn isolated() -> ConfigOverrides {
+        ConfigOverrides {
+            no_config: true,
+            ..Default::default()
+        }
+    }
+
     #[test]
     fn test_find_config_file_none() {
-        let result = find_config_file();
+        let result = resolve_config_file(&isolated());
         assert!(result.is_none());
     }
+
+    #[test]
+    fn test_no_config_beats_explicit_config() {
+        let overrides = isolated().with_cli(Some(PathBuf::from("some/config.lua")), true);
+        assert!(resolve_config_file(&overrides).is_none());
+    }
+
+    #[test]
+    fn test_explicit_config_file() {
+        let temp_dir = tempdir().unwrap();
+        let config_file = temp_dir.path().join("ci.lua");
+        fs::write(&config_file, get_dummy_config()).unwrap();
+
+        let overrides = ConfigOverrides::default().with_cli(Some(config_file.clone()), false);
+        assert_eq!(resolve_config_file(&overrides), Some(config_file));
+
+        let lua = Lua::new();
+        let config = UserConfig::with_overrides(&lua, &overrides);
+        assert_eq!(
+            config.external_template.to_string().unwrap(),
+            "My/path/to/template"
+        );
+    }
+
+    #[test]
+    fn test_template_override() {
+        let temp_dir = tempdir().unwrap();
+        let config_file = temp_dir.path().join("ci.lua");
+        fs::write(&config_file, get_dummy_config()).unwrap();
+
+        let overrides = ConfigOverrides {
+            config_file: Some(config_file),
+            no_config: false,
+            template: Some("https://example.com/template.git".to_string()),
+        };
+        let lua = Lua::new();
+        let config = UserConfig::with_overrides(&lua, &overrides);
+        assert_eq!(
+            config.external_template.to_string().unwrap(),
+            "https://example.com/template.git"
+        );
+    }
     fn test_return_config(config_file: PathBuf) -> Option<PathBuf> {
         if config_file.exists() {
             return Some(config_file);
@@ -237,11 +583,108 @@ mod tests {
         // tempdir automatically cleans up when it goes out of scope
     }
 
+    #[test]
+    fn test_missing_author_fields_are_none() {
+        let temp_dir = tempdir().unwrap();
+        let config_file = temp_dir.path().join("qplug.lua");
+        fs::write(&config_file, r#"return { me = { name = "Jane" } }"#).unwrap();
+
+        let lua = Lua::new();
+        let overrides = ConfigOverrides::default().with_cli(Some(config_file), false);
+        let user_config = UserConfig::with_overrides(&lua, &overrides);
+        let config = Config::from_user_config(&user_config);
+
+        assert_eq!(config.me.name.as_deref(), Some("Jane"));
+        assert_eq!(config.me.email, None);
+        assert_eq!(config.me.company, None);
+    }
+
+    #[test]
+    fn test_target_qsys() {
+        let lua = Lua::new();
+        let value = |source: &str| lua.load(source).eval::<Value>().unwrap();
+
+        assert_eq!(target_qsys(&Value::Nil), Ok(None));
+        assert_eq!(
+            target_qsys(&value("'9.10'")),
+            Ok(Some("9.10".parse().unwrap()))
+        );
+        assert_eq!(
+            target_qsys(&value("9.4")).unwrap_err(),
+            "target_qsys must be a string like \"9.4\""
+        );
+        assert!(target_qsys(&value("'\\xff'")).is_err());
+        assert!(target_qsys(&value("'latest'")).is_err());
+    }
+
+    #[test]
+    fn test_copyright_line() {
+        let author = Author {
+            name: Some("Jane Doe".to_string()),
+            email: Some("jane@example.com".to_string()),
+            company: Some("ACME".to_string()),
+        };
+        assert_eq!(
+            author.copyright_line(2024).unwrap(),
+            "Copyright (c) Jane Doe <jane@example.com> / ACME, 2024"
+        );
+
+        let nobody = Author {
+            name: None,
+            email: None,
+            company: None,
+        };
+        assert!(nobody.copyright_line(2024).is_none());
+    }
+
+    #[test]
+    fn test_workspace_layer() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path();
+        let project = root.join("plugins/amp");
+        let nested = project.join("plugin_src");
+        fs::create_dir_all(&nested).unwrap();
+
+        let global = root.join("global.lua");
+        fs::write(
+            &global,
+            r#"return { external_template = "global", me = { name = "Global" } }"#,
+        )
+        .unwrap();
+        fs::write(
+            root.join(crate::files::WORKSPACE_FILE),
+            r#"return { external_template = "workspace" }"#,
+        )
+        .unwrap();
+        fs::write(
+            project.join(MARKER_FILE),
+            r#"return { me = { name = "Project" } }"#,
+        )
+        .unwrap();
+
+        let overrides = ConfigOverrides::default().with_cli(Some(global.clone()), false);
+        let layers = config_layers(&overrides, &nested);
+        let kinds: Vec<LayerKind> = layers.iter().map(|l| l.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![LayerKind::Global, LayerKind::Workspace, LayerKind::Project]
+        );
+
+        let lua = Lua::new();
+        let user_config = UserConfig::from_layers(&lua, &layers, &overrides);
+        let config = Config::from_user_config(&user_config);
+        assert_eq!(
+            user_config.external_template.to_string().unwrap(),
+            "workspace"
+        );
+        assert_eq!(config.me.name.as_deref(), Some("Project"));
+    }
+
     // Test the `get_config` function when no config file is found (default values).
     #[test]
     fn test_get_config_default() {
         let lua = Lua::new();
-        let config = UserConfig::new(&lua);
+        let config = UserConfig::with_overrides(&lua, &isolated());
 
         assert_eq!(config.build_tool, Value::Nil);
         assert_eq!(config.external_template, Value::Nil);
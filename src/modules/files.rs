@@ -3,9 +3,13 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use super::config::Template;
+use super::{
+    config::Template,
+    template::{clone_template, GitSource},
+};
 
 pub const MARKER_FILE: &str = ".qplug";
+pub const WORKSPACE_FILE: &str = ".qplug-workspace";
 
 pub enum Entry {
     FileSystem(fs::DirEntry),
@@ -75,7 +79,8 @@ impl Extractable for Template<'_> {
         match self {
             Template::FileSystem(p) => p.extract(dest),
             Template::InMemoryDir(d) => d.extract(dest),
-            Template::Url(_) => unimplemented!(),
+            Template::Url(url) => clone_template(&GitSource::parse(url), dest)
+                .map_err(|e| io::Error::other(e.to_string())),
         }
     }
 }
@@ -86,7 +91,21 @@ pub fn copy_dir(source: &Template, dest: &Path) -> Result<(), io::Error> {
 }
 
 pub fn create_marker_file(root_path: &Path) {
-    fs::write(root_path.join(MARKER_FILE), "return {}").expect("Failed to write marker file");
+    write_marker_file(root_path, &[]);
+}
+
+/// Write the marker with project settings, given as Lua expressions.
+pub fn write_marker_file(root_path: &Path, settings: &[(&str, String)]) {
+    let contents = if settings.is_empty() {
+        "return {}".to_string()
+    } else {
+        let fields: Vec<String> = settings
+            .iter()
+            .map(|(key, value)| format!("  {} = {},", key, value))
+            .collect();
+        format!("return {{\n{}\n}}\n", fields.join("\n"))
+    };
+    fs::write(root_path.join(MARKER_FILE), contents).expect("Failed to write marker file");
 }
 
 pub fn find_project_dir(path: Option<&Path>) -> Option<PathBuf> {
@@ -109,6 +128,13 @@ pub fn find_project_dir(path: Option<&Path>) -> Option<PathBuf> {
     None
 }
 
+/// Walk up from `path` looking for a workspace config shared by several plugins.
+pub fn find_workspace_file(path: &Path) -> Option<PathBuf> {
+    path.ancestors()
+        .map(|dir| dir.join(WORKSPACE_FILE))
+        .find(|file| file.is_file())
+}
+
 pub fn find_file_recursively(dir: &Path, file_name: &str) -> Option<PathBuf> {
     if dir.is_dir() {
         for entry in fs::read_dir(dir).ok()? {
@@ -126,6 +152,32 @@ pub fn find_file_recursively(dir: &Path, file_name: &str) -> Option<PathBuf> {
     None
 }
 
+/// Every file below `dir`, sorted so callers get a stable order.
+pub fn walk_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    if let Ok(entries) = fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                files.extend(walk_files(&path));
+            } else {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+/// FNV-1a, as hex. Only used to notice edits and tell names apart, so it just needs to be stable
+/// between releases.
+pub fn hash(contents: &[u8]) -> String {
+    let hash = contents.iter().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{:016x}", hash)
+}
+
 pub fn pwd() -> PathBuf {
     std::env::current_dir()
         .expect("Failed to get current directory. Please check your permissions.")
@@ -214,6 +266,17 @@ mod tests {
         assert!(expected_file_path.is_some());
     }
 
+    #[test]
+    fn test_walk_files() {
+        let temp_dir = tempdir().unwrap();
+        copy_dir(&Template::InMemoryDir(&TEMPLATE_DIR), temp_dir.path()).unwrap();
+
+        let files = walk_files(temp_dir.path());
+        assert!(files.contains(&temp_dir.path().join("init.lua")));
+        assert!(files.contains(&temp_dir.path().join("setup/info.lua")));
+        assert!(files.iter().all(|f| f.is_file()));
+    }
+
     #[test]
     fn test_create_marker_file() {
         let temp_dir = tempdir().unwrap();
@@ -0,0 +1,209 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    assets::LICENSES_DIR,
+    files::walk_files,
+    template::{render, TemplateVars},
+};
+
+const SPDX_PREFIX: &str = "-- SPDX-License-Identifier:";
+
+/// A license to write into a new plugin, either bundled with qplug or read from a file.
+#[derive(Debug, PartialEq)]
+pub struct License {
+    /// SPDX identifier. Custom license files get a `LicenseRef-` identifier named after the file.
+    pub id: String,
+    /// License text. May use the usual `{{ var }}` template variables, including `{{ copyright }}`.
+    pub text: String,
+}
+
+impl License {
+    /// Look `spec` up as a bundled SPDX identifier (case insensitive), then as a path.
+    pub fn resolve(spec: &str) -> Result<License, String> {
+        if let Some(id) = bundled_id(spec) {
+            let text = LICENSES_DIR
+                .get_file(format!("{}.txt", id))
+                .and_then(|file| file.contents_utf8())
+                .unwrap();
+            return Ok(License {
+                id: id.to_string(),
+                text: text.to_string(),
+            });
+        }
+
+        let path = Path::new(spec);
+        if path.is_file() {
+            let text = fs::read_to_string(path)
+                .map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+            return Ok(License {
+                id: spdx_id(spec),
+                text,
+            });
+        }
+
+        Err(format!(
+            "Unknown license `{}`. Use one of {} or the path to a license file.",
+            spec,
+            bundled_ids().join(", ")
+        ))
+    }
+
+    pub fn render(&self, vars: &TemplateVars) -> String {
+        render(&self.text, vars)
+    }
+}
+
+/// Identifiers of the licenses bundled with qplug.
+pub fn bundled_ids() -> Vec<&'static str> {
+    let mut ids: Vec<&str> = LICENSES_DIR
+        .files()
+        .filter_map(|file| file.path().file_stem()?.to_str())
+        .collect();
+    ids.sort();
+    ids
+}
+
+fn bundled_id(spec: &str) -> Option<&'static str> {
+    bundled_ids()
+        .into_iter()
+        .find(|id| id.eq_ignore_ascii_case(spec))
+}
+
+/// The SPDX identifier for a license setting. Paths become `LicenseRef-<file name>`, anything
+/// else is assumed to already be an identifier.
+pub fn spdx_id(spec: &str) -> String {
+    if let Some(id) = bundled_id(spec) {
+        return id.to_string();
+    }
+
+    let path = Path::new(spec);
+    if path.is_file() {
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let name: String = stem
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                    c
+                } else {
+                    '-'
+                }
+            })
+            .collect();
+        return format!("LicenseRef-{}", name);
+    }
+
+    spec.to_string()
+}
+
+/// `-- SPDX-License-Identifier: <id>`
+pub fn spdx_header(id: &str) -> String {
+    format!("{} {}", SPDX_PREFIX, id)
+}
+
+pub fn is_spdx_header(line: &str) -> bool {
+    line.trim_start().starts_with(SPDX_PREFIX)
+}
+
+/// Prepend the SPDX header to every Lua file under `plugin_path` that doesn't have one.
+pub fn stamp_spdx_header(plugin_path: &Path, id: &str) -> io::Result<()> {
+    for file in walk_files(plugin_path) {
+        if file.extension().is_some_and(|ext| ext == "lua") {
+            let contents = fs::read_to_string(&file)?;
+            if !contents.lines().next().is_some_and(is_spdx_header) {
+                fs::write(&file, format!("{}\n{}", spdx_header(id), contents))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Replace the template's LICENSE files with `text`. Without one, write `LICENSE` in `root_path`.
+pub fn write_license(plugin_path: &Path, root_path: &Path, text: &str) -> io::Result<PathBuf> {
+    let existing: Vec<PathBuf> = walk_files(plugin_path)
+        .into_iter()
+        .filter(|file| {
+            file.file_name()
+                .is_some_and(|name| name.to_string_lossy().to_uppercase().starts_with("LICENSE"))
+        })
+        .collect();
+
+    let target = existing
+        .first()
+        .cloned()
+        .unwrap_or_else(|| root_path.join("LICENSE"));
+    for file in &existing {
+        fs::remove_file(file)?;
+    }
+    fs::write(&target, text)?;
+    Ok(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_resolve_bundled() {
+        let license = License::resolve("mit").unwrap();
+        assert_eq!(license.id, "MIT");
+
+        let mut vars = TemplateVars::new();
+        vars.insert(
+            "copyright".to_string(),
+            "Copyright (c) Jane Doe, 2024".to_string(),
+        );
+        let text = license.render(&vars);
+        assert!(text.starts_with("MIT License\n\nCopyright (c) Jane Doe, 2024\n"));
+    }
+
+    #[test]
+    fn test_resolve_file() {
+        let dir = tempdir().unwrap();
+        let eula = dir.path().join("ACME EULA.txt");
+        fs::write(&eula, "{{ company }} only.").unwrap();
+
+        let license = License::resolve(eula.to_str().unwrap()).unwrap();
+        assert_eq!(license.id, "LicenseRef-ACME-EULA");
+        assert_eq!(license.text, "{{ company }} only.");
+        assert!(License::resolve("Not-A-License").is_err());
+    }
+
+    #[test]
+    fn test_stamp_spdx_header() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("init.lua"), "print(1)\n").unwrap();
+        fs::write(dir.path().join("README.md"), "# Readme\n").unwrap();
+
+        stamp_spdx_header(dir.path(), "MIT").unwrap();
+        stamp_spdx_header(dir.path(), "MIT").unwrap();
+
+        assert_eq!(
+            fs::read_to_string(dir.path().join("init.lua")).unwrap(),
+            "-- SPDX-License-Identifier: MIT\nprint(1)\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join("README.md")).unwrap(),
+            "# Readme\n"
+        );
+    }
+
+    #[test]
+    fn test_write_license_replaces_template_license() {
+        let dir = tempdir().unwrap();
+        let plugin_path = dir.path().join("plugin_src");
+        fs::create_dir_all(&plugin_path).unwrap();
+        fs::write(plugin_path.join("LICENSE.txt"), "old").unwrap();
+
+        let target = write_license(&plugin_path, dir.path(), "new").unwrap();
+        assert_eq!(target, plugin_path.join("LICENSE.txt"));
+        assert_eq!(fs::read_to_string(target).unwrap(), "new");
+
+        fs::remove_file(plugin_path.join("LICENSE.txt")).unwrap();
+        let target = write_license(&plugin_path, dir.path(), "new").unwrap();
+        assert_eq!(target, dir.path().join("LICENSE"));
+    }
+}
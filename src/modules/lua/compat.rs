@@ -0,0 +1,175 @@
+use std::fmt;
+
+use super::resolve::{resolve, Access, SyntaxError};
+
+/// Libraries and functions the Q-SYS sandbox removes.
+const SANDBOXED: [&str; 5] = ["io", "debug", "dofile", "loadfile", "package"];
+
+/// The parts of `os` the sandbox keeps.
+const OS_ALLOWED: [&str; 4] = ["clock", "date", "difftime", "time"];
+
+/// Functions that only exist from Lua 5.4 on. Q-SYS runs 5.3.
+const LUA54_FUNCTIONS: [(&str, Option<&str>); 2] = [("warn", None), ("coroutine", Some("close"))];
+
+/// Modules a plugin can `require` on a Core. The plugin's own modules are inlined by compile.
+const QSYS_MODULES: [&str; 7] = [
+    "EzSVG",
+    "LuaXML",
+    "bitstring",
+    "date",
+    "json",
+    "lpeg",
+    "rapidjson",
+];
+
+#[derive(Debug, PartialEq)]
+pub enum Incompatibility {
+    /// A `<const>` or `<close>` local.
+    Lua54Attribute(String),
+    Lua54Function(String),
+    /// A library or function the sandbox takes away.
+    Sandboxed(String),
+    /// `require` of something that is neither inlined nor provided by Q-SYS.
+    UnknownModule(String),
+    Syntax(String),
+}
+
+impl fmt::Display for Incompatibility {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Incompatibility::Lua54Attribute(attrib) => write!(
+                f,
+                "`<{}>` locals are Lua 5.4 syntax; Q-SYS runs Lua 5.3",
+                attrib
+            ),
+            Incompatibility::Lua54Function(name) => {
+                write!(f, "`{}` was added in Lua 5.4; Q-SYS runs Lua 5.3", name)
+            }
+            Incompatibility::Sandboxed(name) => {
+                write!(f, "`{}` is not available in the Q-SYS sandbox", name)
+            }
+            Incompatibility::UnknownModule(name) => write!(
+                f,
+                "require(\"{}\"): not a plugin module and not provided by Q-SYS",
+                name
+            ),
+            Incompatibility::Syntax(message) => write!(f, "syntax error: {}", message),
+        }
+    }
+}
+
+/// Check a whole plugin chunk for anything that won't run on a Core. Returns every problem with
+/// its line in `source`.
+pub fn check_compat(source: &str) -> Vec<(usize, Incompatibility)> {
+    let resolved = match resolve(source) {
+        Ok(resolved) => resolved,
+        Err(SyntaxError { line, message }) => {
+            return vec![(line, Incompatibility::Syntax(message))]
+        }
+    };
+
+    let mut problems: Vec<(usize, Incompatibility)> = resolved
+        .attribs
+        .into_iter()
+        .map(|(line, attrib)| (line, Incompatibility::Lua54Attribute(attrib)))
+        .collect();
+
+    for global in resolved.globals {
+        if global.access != Access::Read {
+            continue;
+        }
+        let name = global.name.as_str();
+        let field = global.field.as_deref();
+        let qualified = match field {
+            Some(field) => format!("{}.{}", name, field),
+            None => name.to_string(),
+        };
+
+        let sandboxed = SANDBOXED.contains(&name)
+            || (name == "os" && field.is_some_and(|field| !OS_ALLOWED.contains(&field)))
+            // Bytecode can't be loaded on a Core, so there's nothing to dump it for.
+            || (name == "string" && field == Some("dump"));
+        let problem = if sandboxed {
+            Some(Incompatibility::Sandboxed(qualified))
+        } else if LUA54_FUNCTIONS.contains(&(name, field)) {
+            Some(Incompatibility::Lua54Function(qualified))
+        } else {
+            None
+        };
+        problems.extend(problem.map(|problem| (global.line, problem)));
+    }
+
+    for (line, module) in resolved.requires {
+        if !QSYS_MODULES.contains(&module.as_str()) {
+            problems.push((line, Incompatibility::UnknownModule(module)));
+        }
+    }
+
+    problems.sort_by_key(|(line, _)| *line);
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_compat() {
+        let source = r#"local json = require("rapidjson")
+local limit <const> = 10
+local f = io.open("x")
+local t = os.time() + os.clock()
+os.execute("rm -rf /")
+warn("hi")
+local co = coroutine.create(print)
+coroutine.close(co)
+local code = string.dump(print)
+local http = require("socket.http")
+local io = { open = print }
+io.open("fine, it's local")
+"#;
+        let problems: Vec<(usize, String)> = check_compat(source)
+            .into_iter()
+            .map(|(line, problem)| (line, problem.to_string()))
+            .collect();
+        assert_eq!(
+            problems,
+            [
+                (
+                    2,
+                    "`<const>` locals are Lua 5.4 syntax; Q-SYS runs Lua 5.3".to_string()
+                ),
+                (
+                    3,
+                    "`io.open` is not available in the Q-SYS sandbox".to_string()
+                ),
+                (
+                    5,
+                    "`os.execute` is not available in the Q-SYS sandbox".to_string()
+                ),
+                (
+                    6,
+                    "`warn` was added in Lua 5.4; Q-SYS runs Lua 5.3".to_string()
+                ),
+                (
+                    8,
+                    "`coroutine.close` was added in Lua 5.4; Q-SYS runs Lua 5.3".to_string()
+                ),
+                (
+                    9,
+                    "`string.dump` is not available in the Q-SYS sandbox".to_string()
+                ),
+                (
+                    10,
+                    "require(\"socket.http\"): not a plugin module and not provided by Q-SYS"
+                        .to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_compat_clean() {
+        assert!(check_compat("local t = os.date('%H')\nprint(t)\n").is_empty());
+    }
+}
@@ -0,0 +1,357 @@
+use regex::Regex;
+
+/// Where each top-level plugin definition lives in the built-in template.
+pub(super) const SECTIONS: [(&str, &str); 13] = [
+    ("PluginInfo", "setup.info"),
+    ("GetColor", "setup.colors"),
+    ("GetPrettyName", "setup.pretty_name"),
+    ("PageNames", "setup.pages"),
+    ("GetPages", "setup.pages"),
+    ("GetModel", "setup.model"),
+    ("GetProperties", "properties.properties"),
+    ("GetPins", "properties.pins"),
+    ("RectifyProperties", "properties.rectify_properties"),
+    ("GetComponents", "control_components.components"),
+    ("GetWiring", "control_components.wiring"),
+    ("GetControls", "control_components.controls"),
+    ("GetControlLayout", "layout.layout"),
+];
+
+const RUNTIME_MODULE: &str = "runtime.runtime";
+
+/// One contiguous piece of a flat plugin, destined for its own module.
+#[derive(Debug, PartialEq)]
+pub struct Section {
+    /// Module name as it would be `require`d, e.g. `setup.info`.
+    pub module: String,
+    pub source: String,
+}
+
+impl Section {
+    /// Path of the module relative to `plugin_src`.
+    pub fn file(&self) -> String {
+        format!("{}.lua", self.module.replace('.', "/"))
+    }
+}
+
+/// A flat plugin split into modules, in their original order.
+#[derive(Debug, PartialEq)]
+pub struct Decompiled {
+    /// Anything before the first section. Kept in `init.lua`.
+    pub header: String,
+    pub sections: Vec<Section>,
+}
+
+impl Decompiled {
+    /// An `init.lua` that compiles back to the original plugin.
+    pub fn init_lua(&self) -> String {
+        let mut init = self.header.to_owned();
+        if !init.is_empty() && !init.ends_with('\n') {
+            init.push('\n');
+        }
+        for section in &self.sections {
+            init.push_str(&format!("require(\"{}\")\n", section.module));
+        }
+        init
+    }
+}
+
+/// Split a flat `.qplug` into the template's module layout. Every top-level `PluginInfo`,
+/// `GetControls`, ... starts a section; anything else stays with the section before it, and
+/// everything from `if Controls then` on is runtime. Order is preserved, so a section that comes
+/// back after another one gets its own numbered module.
+pub fn split_plugin(source: &str) -> Result<Decompiled, String> {
+    let lines: Vec<&str> = source.split_inclusive('\n').collect();
+    let top_level = top_level_lines(source, lines.len());
+
+    let definition =
+        Regex::new(r"^(?:local\s+)?(?:function\s+([A-Za-z_][\w.:]*)|([A-Za-z_]\w*)\s*=)").unwrap();
+    let runtime = Regex::new(r"^if\s+Controls\b").unwrap();
+
+    // (first line, module) for every section start.
+    let mut starts: Vec<(usize, &str)> = Vec::new();
+    let mut in_runtime = false;
+    for (index, line) in lines.iter().enumerate() {
+        let trimmed = line.trim();
+        if !top_level[index] || trimmed.is_empty() || trimmed.starts_with("--") || in_runtime {
+            continue;
+        }
+
+        let module = if runtime.is_match(trimmed) {
+            in_runtime = true;
+            Some(RUNTIME_MODULE)
+        } else {
+            definition.captures(trimmed).and_then(|cap| {
+                let name = cap.get(1).or(cap.get(2)).unwrap().as_str();
+                SECTIONS
+                    .iter()
+                    .find(|(section, _)| *section == name)
+                    .map(|(_, module)| *module)
+            })
+        };
+
+        let Some(module) = module else { continue };
+        if starts.last().is_some_and(|(_, current)| *current == module) {
+            continue;
+        }
+        starts.push((leading_comments(&lines, &top_level, index), module));
+    }
+
+    if starts.is_empty() {
+        return Err("No plugin definitions (PluginInfo, GetControls, ...) found.".to_string());
+    }
+
+    let header = lines[..starts[0].0].concat();
+    let mut sections: Vec<Section> = Vec::new();
+    for (i, (start, module)) in starts.iter().enumerate() {
+        let end = starts.get(i + 1).map_or(lines.len(), |(next, _)| *next);
+        let repeats = sections
+            .iter()
+            .filter(|s| s.module == *module || s.module.starts_with(&format!("{}_", module)))
+            .count();
+        let module = match repeats {
+            0 => module.to_string(),
+            n => format!("{}_{}", module, n + 1),
+        };
+        sections.push(Section {
+            module,
+            source: lines[*start..end].concat(),
+        });
+    }
+
+    Ok(Decompiled { header, sections })
+}
+
+/// Walk back over the comment lines directly above `index` so they move with it.
+fn leading_comments(lines: &[&str], top_level: &[bool], index: usize) -> usize {
+    let mut start = index;
+    while start > 0 && top_level[start - 1] && lines[start - 1].trim_start().starts_with("--") {
+        start -= 1;
+    }
+    start
+}
+
+/// For every line, whether it starts outside of any block, bracket, string or long comment.
+fn top_level_lines(source: &str, line_count: usize) -> Vec<bool> {
+    let bytes = source.as_bytes();
+    let mut top_level = vec![false; line_count.max(1)];
+    top_level[0] = true;
+    let mut line = 0;
+    let mut depth: i32 = 0;
+    let mut i = 0;
+
+    // Count the newlines a skipped token spans. Lines starting inside it are never top level.
+    let skip_to = |from: usize, to: usize, line: &mut usize| {
+        *line += bytes[from..to].iter().filter(|b| **b == b'\n').count();
+        to
+    };
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\n' => {
+                line += 1;
+                if line < top_level.len() {
+                    top_level[line] = depth == 0;
+                }
+                i += 1;
+            }
+            b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                let end = match long_bracket(bytes, i + 2) {
+                    Some(level) => close_long_bracket(bytes, i + 2, level),
+                    None => bytes[i..]
+                        .iter()
+                        .position(|b| *b == b'\n')
+                        .map_or(bytes.len(), |p| i + p),
+                };
+                i = skip_to(i, end, &mut line);
+            }
+            b'[' if long_bracket(bytes, i).is_some() => {
+                let level = long_bracket(bytes, i).unwrap();
+                i = skip_to(i, close_long_bracket(bytes, i, level), &mut line);
+            }
+            quote @ (b'"' | b'\'') => {
+                let mut end = i + 1;
+                while end < bytes.len() && bytes[end] != quote && bytes[end] != b'\n' {
+                    end += if bytes[end] == b'\\' { 2 } else { 1 };
+                }
+                i = skip_to(i, (end + 1).min(bytes.len()), &mut line);
+            }
+            b'{' | b'(' | b'[' => {
+                depth += 1;
+                i += 1;
+            }
+            b'}' | b')' | b']' => {
+                depth -= 1;
+                i += 1;
+            }
+            b if b.is_ascii_alphabetic() || b == b'_' => {
+                let end = bytes[i..]
+                    .iter()
+                    .position(|b| !(b.is_ascii_alphanumeric() || *b == b'_'))
+                    .map_or(bytes.len(), |p| i + p);
+                match &source[i..end] {
+                    "function" | "if" | "do" | "repeat" => depth += 1,
+                    "end" | "until" => depth -= 1,
+                    _ => {}
+                }
+                i = end;
+            }
+            _ => i += 1,
+        }
+    }
+
+    top_level
+}
+
+/// The level of a long bracket (`[[` is 0, `[==[` is 2) opening at `i`, if there is one.
+pub(super) fn long_bracket(bytes: &[u8], i: usize) -> Option<usize> {
+    if bytes.get(i) != Some(&b'[') {
+        return None;
+    }
+    let level = bytes[i + 1..].iter().take_while(|b| **b == b'=').count();
+    (bytes.get(i + 1 + level) == Some(&b'[')).then_some(level)
+}
+
+/// The index just past the long bracket of `level` closing the one opened at `i`.
+pub(super) fn close_long_bracket(bytes: &[u8], i: usize, level: usize) -> usize {
+    let close = format!("]{}]", "=".repeat(level));
+    let body = i + level + 2;
+    bytes[body..]
+        .windows(close.len())
+        .position(|w| w == close.as_bytes())
+        .map_or(bytes.len(), |p| body + p + close.len())
+}
+
+/// Compare two plugins ignoring blank lines and trailing whitespace. Returns the first line of
+/// `original` that differs.
+pub fn first_difference(original: &str, recompiled: &str) -> Option<usize> {
+    let significant = |source: &str| -> Vec<(usize, String)> {
+        source
+            .lines()
+            .enumerate()
+            .map(|(n, line)| (n + 1, line.trim_end().to_string()))
+            .filter(|(_, line)| !line.is_empty())
+            .collect()
+    };
+    let original = significant(original);
+    let recompiled = significant(recompiled);
+
+    original
+        .iter()
+        .zip(recompiled.iter())
+        .find(|(a, b)| a.1 != b.1)
+        .map(|(a, _)| a.0)
+        .or_else(|| match original.len().cmp(&recompiled.len()) {
+            std::cmp::Ordering::Equal => None,
+            std::cmp::Ordering::Greater => Some(original[recompiled.len()].0),
+            std::cmp::Ordering::Less => Some(original.last().map_or(1, |(n, _)| n + 1)),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FLAT: &str = r#"-- Legacy plugin
+
+PluginInfo = {
+  Name = "Legacy",
+  Version = "1.0",
+}
+
+PageNames = { "Control", "Setup" }
+function GetPages(props)
+  local pages = {}
+  for ix, name in ipairs(PageNames) do
+    table.insert(pages, { name = PageNames[ix] })
+  end
+  return pages
+end
+
+local function helper(x)
+  return x .. [[
+function GetControls() end
+]]
+end
+
+-- Controls
+function GetControls(props)
+  return { { Name = helper("a"), ControlType = "Button" } }
+end
+
+function GetControlLayout(props)
+  local layout = {}
+  if props then
+    layout["a"] = { Style = "Button" } -- end
+  end
+  return layout, {}
+end
+
+if Controls then
+  function GetPages() end
+  Controls.a.EventHandler = function() print("'") end
+end
+print("done")
+"#;
+
+    #[test]
+    fn test_split_plugin() {
+        let decompiled = split_plugin(FLAT).unwrap();
+        let modules: Vec<&str> = decompiled
+            .sections
+            .iter()
+            .map(|s| s.module.as_str())
+            .collect();
+        assert_eq!(
+            modules,
+            [
+                "setup.info",
+                "setup.pages",
+                "control_components.controls",
+                "layout.layout",
+                "runtime.runtime"
+            ]
+        );
+        assert_eq!(decompiled.header, "-- Legacy plugin\n\n");
+
+        // The helper stays with the section it follows, the comment moves with GetControls.
+        assert!(decompiled.sections[1]
+            .source
+            .contains("local function helper"));
+        assert!(decompiled.sections[2].source.starts_with("-- Controls\n"));
+        assert!(decompiled.sections[4].source.ends_with("print(\"done\")\n"));
+    }
+
+    #[test]
+    fn test_split_plugin_round_trip() {
+        let decompiled = split_plugin(FLAT).unwrap();
+        let mut recompiled = decompiled.header.clone();
+        for section in &decompiled.sections {
+            recompiled.push_str(&section.source);
+        }
+        assert_eq!(recompiled, FLAT);
+    }
+
+    #[test]
+    fn test_split_plugin_repeated_section() {
+        let source = "PageNames = {}\nfunction GetControls() end\nfunction GetPages() end\n";
+        let decompiled = split_plugin(source).unwrap();
+        assert_eq!(decompiled.sections[2].module, "setup.pages_2");
+        assert_eq!(decompiled.sections[2].file(), "setup/pages_2.lua");
+        assert!(decompiled
+            .init_lua()
+            .contains("require(\"setup.pages_2\")\n"));
+    }
+
+    #[test]
+    fn test_split_plugin_not_a_plugin() {
+        assert!(split_plugin("print('hello')\n").is_err());
+    }
+
+    #[test]
+    fn test_first_difference() {
+        assert_eq!(first_difference("a\n\nb\n", "a  \nb"), None);
+        assert_eq!(first_difference("a\nb\n", "a\nc\n"), Some(2));
+        assert_eq!(first_difference("a\nb\n", "a\n"), Some(2));
+    }
+}
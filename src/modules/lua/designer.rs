@@ -0,0 +1,425 @@
+use include_dir::Dir;
+use mlua::{IntoLuaMulti, Lua, LuaSerdeExt, MultiValue, Table, Value};
+use serde_json::{Map, Number, Value as JsonValue};
+
+use super::{error_message, plugin::callback};
+
+/// Globals the definitions declare that only exist at runtime. Designer runs the design-time
+/// callbacks without them, which is how plugins tell the two apart (`if Controls then`).
+const RUNTIME_GLOBALS: [&str; 2] = ["Controls", "Properties"];
+
+/// Tables nested deeper than this are cut off when converted, so cycles can't recurse forever.
+const MAX_DEPTH: usize = 32;
+
+/// What one design-time callback did.
+#[derive(Debug, PartialEq)]
+pub struct CallbackResult {
+    pub callback: &'static str,
+    /// The page being laid out, for `GetControlLayout` on a plugin with pages.
+    pub page: Option<String>,
+    /// What the callback returned, or the error it raised.
+    pub result: Result<JsonValue, String>,
+}
+
+/// Give `lua` what a plugin has in Designer: the APIs the definitions declare, as the stubs they
+/// are, and no runtime globals. `print` goes to stderr so it doesn't mix with qplug's output.
+pub fn seed_design_time(lua: &Lua, definitions: &Dir) -> mlua::Result<()> {
+    load_definitions(lua, definitions);
+    for name in RUNTIME_GLOBALS {
+        lua.globals().set(name, Value::Nil)?;
+    }
+    print_to_stderr(lua)
+}
+
+/// Send the plugin's `print`s to stderr, leaving stdout to what qplug reports.
+pub(super) fn print_to_stderr(lua: &Lua) -> mlua::Result<()> {
+    lua.load(
+        r#"print = function(...)
+            local parts = table.pack(...)
+            for i = 1, parts.n do
+                parts[i] = tostring(parts[i])
+            end
+            io.stderr:write(table.concat(parts, "\t", 1, parts.n), "\n")
+        end"#,
+    )
+    .exec()
+}
+
+pub(super) fn load_definitions(lua: &Lua, dir: &Dir) {
+    for file in dir.files() {
+        let is_lua = file.path().extension().is_some_and(|ext| ext == "lua");
+        let Some(source) = file.contents_utf8().filter(|_| is_lua) else {
+            continue;
+        };
+        // A definitions file that doesn't load only costs its own stubs.
+        let _ = lua
+            .load(source)
+            .set_name(file.path().to_string_lossy())
+            .exec();
+    }
+    for sub_dir in dir.dirs() {
+        load_definitions(lua, sub_dir);
+    }
+}
+
+/// Call the design-time callbacks the way Designer does when the plugin is dropped into a design:
+/// `GetProperties`, `RectifyProperties`, `GetPages`, `GetControls`, `GetControlLayout` once per
+/// page, `GetPins`, `GetComponents` and `GetWiring`. Callbacks the plugin doesn't define are left
+/// out. An error in one callback doesn't stop the rest. `values` are property values set over the
+/// defaults, as if changed in Designer's property pane.
+pub fn run_design_time(
+    lua: &Lua,
+    values: &Map<String, JsonValue>,
+) -> mlua::Result<Vec<CallbackResult>> {
+    let mut results = Vec::new();
+
+    let properties = call(lua, "GetProperties", ());
+    let list = match record(&mut results, "GetProperties", None, properties) {
+        Some(Value::Table(list)) => Some(list),
+        _ => None,
+    };
+    let mut props = design_props(lua, list.as_ref(), values)?;
+
+    let rectified = call(lua, "RectifyProperties", props.clone());
+    if let Some(Value::Table(rectified)) =
+        record(&mut results, "RectifyProperties", None, rectified)
+    {
+        props = rectified;
+    }
+
+    let pages = call(lua, "GetPages", props.clone());
+    let mut page_names: Vec<String> = match record(&mut results, "GetPages", None, pages) {
+        Some(Value::Table(pages)) => pages
+            .sequence_values::<Table>()
+            .flatten()
+            .filter_map(|page| page.get::<_, String>("name").ok())
+            .collect(),
+        _ => Vec::new(),
+    };
+    if page_names.is_empty() {
+        if let Ok(Some(names)) = lua.globals().get::<_, Option<Vec<String>>>("PageNames") {
+            page_names = names;
+        }
+    }
+
+    let controls = call(lua, "GetControls", props.clone());
+    record(&mut results, "GetControls", None, controls);
+
+    let pages: Vec<Option<String>> = if page_names.is_empty() {
+        vec![None]
+    } else {
+        page_names.into_iter().map(Some).collect()
+    };
+    for (index, page) in pages.into_iter().enumerate() {
+        props.set(
+            "page_index",
+            designer_property(lua, "page_index", "integer", index + 1)?,
+        )?;
+        let layout = call(lua, "GetControlLayout", props.clone());
+        record(&mut results, "GetControlLayout", page, layout);
+    }
+
+    for name in ["GetPins", "GetComponents", "GetWiring"] {
+        let returned = call(lua, name, props.clone());
+        record(&mut results, name, None, returned);
+    }
+    Ok(results)
+}
+
+/// The `props` Designer passes the callbacks: each property in the `GetProperties` list under
+/// its name, with `values` set over the defaults. Designer adds its own `plugin_show_debug`.
+pub fn design_props<'lua>(
+    lua: &'lua Lua,
+    list: Option<&Table<'lua>>,
+    values: &Map<String, JsonValue>,
+) -> mlua::Result<Table<'lua>> {
+    let props = lua.create_table()?;
+    if let Some(list) = list {
+        for property in list.clone().sequence_values::<Table>().flatten() {
+            if let Ok(name) = property.get::<_, String>("Name") {
+                props.set(name, property)?;
+            }
+        }
+    }
+    props.set(
+        "plugin_show_debug",
+        designer_property(lua, "plugin_show_debug", "boolean", false)?,
+    )?;
+
+    for (name, value) in values {
+        let Some(property) = props.get::<_, Option<Table>>(name.as_str())? else {
+            return Err(mlua::Error::RuntimeError(format!(
+                "there's no property named `{}`",
+                name
+            )));
+        };
+        let choices: Option<Vec<String>> = property.get("Choices")?;
+        if let (Some(choices), Some(choice)) = (choices, value.as_str()) {
+            if !choices.iter().any(|c| c == choice) {
+                return Err(mlua::Error::RuntimeError(format!(
+                    "`{}` isn't one of the choices for `{}`: {}",
+                    choice,
+                    name,
+                    choices.join(", ")
+                )));
+            }
+        }
+        property.set("Value", lua.to_value(value)?)?;
+    }
+    Ok(props)
+}
+
+/// `design_props` through the plugin's `RectifyProperties`, if it has one: the `props` every
+/// callback after it gets. A `RectifyProperties` that returns nothing leaves them as they were.
+pub fn rectified_props<'lua>(
+    lua: &'lua Lua,
+    list: Option<&Table<'lua>>,
+    values: &Map<String, JsonValue>,
+) -> mlua::Result<Table<'lua>> {
+    let props = design_props(lua, list, values)?;
+    let Some(rectify) = callback(lua, "RectifyProperties")? else {
+        return Ok(props);
+    };
+    match rectify.call::<_, Value>(props.clone())? {
+        Value::Table(rectified) => Ok(rectified),
+        _ => Ok(props),
+    }
+}
+
+fn designer_property<'lua>(
+    lua: &'lua Lua,
+    name: &str,
+    property_type: &str,
+    value: impl mlua::IntoLua<'lua>,
+) -> mlua::Result<Table<'lua>> {
+    let property = lua.create_table()?;
+    property.set("Name", name)?;
+    property.set("Type", property_type)?;
+    property.set("Value", value)?;
+    Ok(property)
+}
+
+/// Call the global callback `name`. `Ok(None)` if the plugin doesn't define it.
+fn call<'lua>(
+    lua: &'lua Lua,
+    name: &str,
+    args: impl IntoLuaMulti<'lua>,
+) -> Result<Option<MultiValue<'lua>>, String> {
+    match callback(lua, name).map_err(|e| error_message(&e))? {
+        Some(f) => f.call(args).map(Some).map_err(|e| error_message(&e)),
+        None => Ok(None),
+    }
+}
+
+/// Add what `callback` did to `results` and hand back its first return value.
+fn record<'lua>(
+    results: &mut Vec<CallbackResult>,
+    callback: &'static str,
+    page: Option<String>,
+    outcome: Result<Option<MultiValue<'lua>>, String>,
+) -> Option<Value<'lua>> {
+    let values: Vec<Value> = match outcome {
+        Ok(Some(values)) => values.into_vec(),
+        Ok(None) => return None,
+        Err(message) => {
+            results.push(CallbackResult {
+                callback,
+                page,
+                result: Err(message),
+            });
+            return None;
+        }
+    };
+
+    let first = values.first().cloned().unwrap_or(Value::Nil);
+    let returned = if callback == "GetControlLayout" {
+        // The only callback with two results.
+        let mut layout = Map::new();
+        layout.insert("layout".to_string(), to_json(&first));
+        let graphics = values.get(1).cloned().unwrap_or(Value::Nil);
+        layout.insert("graphics".to_string(), to_json(&graphics));
+        JsonValue::Object(layout)
+    } else {
+        to_json(&first)
+    };
+    results.push(CallbackResult {
+        callback,
+        page,
+        result: Ok(returned),
+    });
+    Some(first)
+}
+
+/// A Lua value as JSON. Sequences become arrays, including the empty table, and other tables
+/// objects. Functions and anything else JSON can't hold become strings like `"<function>"`.
+pub fn to_json(value: &Value) -> JsonValue {
+    value_to_json(value, 0)
+}
+
+fn value_to_json(value: &Value, depth: usize) -> JsonValue {
+    match value {
+        Value::Nil => JsonValue::Null,
+        Value::Boolean(b) => JsonValue::Bool(*b),
+        Value::Integer(i) => JsonValue::from(*i),
+        Value::Number(n) => Number::from_f64(*n)
+            .map(JsonValue::Number)
+            .unwrap_or_else(|| JsonValue::String(n.to_string())),
+        Value::String(s) => JsonValue::String(s.to_string_lossy().into_owned()),
+        Value::Table(_) if depth >= MAX_DEPTH => JsonValue::String("<too deep>".to_string()),
+        Value::Table(table) => table_to_json(table, depth + 1),
+        other => JsonValue::String(format!("<{}>", other.type_name())),
+    }
+}
+
+fn table_to_json(table: &Table, depth: usize) -> JsonValue {
+    let pairs: Vec<(Value, Value)> = table.clone().pairs().flatten().collect();
+    let len = pairs.len() as i64;
+    let is_sequence = pairs
+        .iter()
+        .all(|(key, _)| matches!(key, Value::Integer(i) if (1..=len).contains(i)));
+    if is_sequence {
+        let mut items: Vec<(i64, JsonValue)> = pairs
+            .iter()
+            .map(|(key, value)| (key.as_i64().unwrap(), value_to_json(value, depth)))
+            .collect();
+        items.sort_by_key(|(index, _)| *index);
+        return JsonValue::Array(items.into_iter().map(|(_, value)| value).collect());
+    }
+
+    let object = pairs
+        .iter()
+        .map(|(key, value)| {
+            let key = match key {
+                Value::String(s) => s.to_string_lossy().into_owned(),
+                Value::Integer(i) => i.to_string(),
+                Value::Number(n) => n.to_string(),
+                Value::Boolean(b) => b.to_string(),
+                other => format!("<{}>", other.type_name()),
+            };
+            (key, value_to_json(value, depth))
+        })
+        .collect();
+    JsonValue::Object(object)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{assets::DEFINITIONS_DIR, lua::plugin::load_plugin};
+
+    const PLUGIN: &str = r#"
+        PageNames = { "Control", "Setup" }
+        function GetProperties()
+            return {
+                { Name = "Count", Type = "integer", Value = 2, Min = 1, Max = 8 },
+                { Name = "Debug", Type = "enum", Choices = { "None", "All" }, Value = "None" },
+            }
+        end
+        function RectifyProperties(props)
+            if props.plugin_show_debug.Value == false then
+                props.Debug.IsHidden = true
+            end
+            return props
+        end
+        function GetPages(props)
+            local pages = {}
+            for _, name in ipairs(PageNames) do
+                table.insert(pages, { name = name })
+            end
+            return pages
+        end
+        function GetControls(props)
+            return { { Name = "Gain", ControlType = "Knob", Count = props.Count.Value } }
+        end
+        function GetControlLayout(props)
+            local layout, graphics = {}, {}
+            if PageNames[props.page_index.Value] == "Control" then
+                layout.Gain = { Position = { 0, 0 }, Size = { 36, 36 } }
+                table.insert(graphics, { Type = "Text", Text = "Gain" })
+            end
+            return layout, graphics
+        end
+        function GetPins(props)
+            return nil + 1
+        end
+        if Controls then
+            Controls.Gain[1].EventHandler = print
+        end
+    "#;
+
+    #[test]
+    fn test_run_design_time() {
+        let lua = Lua::new();
+        seed_design_time(&lua, &DEFINITIONS_DIR).unwrap();
+        load_plugin(&lua, PLUGIN, "plugin").unwrap();
+        let results = run_design_time(&lua, &Map::new()).unwrap();
+
+        let called: Vec<(&str, Option<&str>)> = results
+            .iter()
+            .map(|result| (result.callback, result.page.as_deref()))
+            .collect();
+        assert_eq!(
+            called,
+            [
+                ("GetProperties", None),
+                ("RectifyProperties", None),
+                ("GetPages", None),
+                ("GetControls", None),
+                ("GetControlLayout", Some("Control")),
+                ("GetControlLayout", Some("Setup")),
+                ("GetPins", None),
+            ]
+        );
+        assert_eq!(
+            results[1].result.as_ref().unwrap()["Debug"]["IsHidden"],
+            json!(true)
+        );
+        assert_eq!(
+            results[3].result,
+            Ok(json!([{ "Name": "Gain", "ControlType": "Knob", "Count": 2 }]))
+        );
+        assert_eq!(
+            results[4].result,
+            Ok(json!({
+                "layout": { "Gain": { "Position": [0, 0], "Size": [36, 36] } },
+                "graphics": [{ "Type": "Text", "Text": "Gain" }],
+            }))
+        );
+        assert_eq!(
+            results[5].result,
+            Ok(json!({ "layout": [], "graphics": [] }))
+        );
+        let error = results[6].result.as_ref().unwrap_err();
+        assert_eq!(
+            error,
+            "[string \"plugin\"]:34: attempt to perform arithmetic on a nil value"
+        );
+    }
+
+    #[test]
+    fn test_seed_design_time() {
+        let lua = Lua::new();
+        seed_design_time(&lua, &DEFINITIONS_DIR).unwrap();
+        let globals = lua.globals();
+        assert!(globals.get::<_, Table>("TcpSocket").is_ok());
+        assert!(globals.get::<_, Table>("Timer").is_ok());
+        assert_eq!(globals.get::<_, Value>("Controls").unwrap(), Value::Nil);
+    }
+
+    #[test]
+    fn test_to_json() {
+        let lua = Lua::new();
+        let value: Value = lua
+            .load(r#"{ 1, "two", { x = 1.5, [3] = false }, f = print }"#)
+            .eval()
+            .unwrap();
+        assert_eq!(
+            to_json(&value),
+            json!({ "1": 1, "2": "two", "3": { "x": 1.5, "3": false }, "f": "<function>" })
+        );
+        let sequence: Value = lua.load("{ 1, 2, {} }").eval().unwrap();
+        assert_eq!(to_json(&sequence), json!([1, 2, []]));
+    }
+}
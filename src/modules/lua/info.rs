@@ -22,23 +22,34 @@ pub struct PluginInfo {
     pub author: String,
     #[serde(rename = "Description")]
     pub description: String,
+    #[serde(
+        rename = "Manufacturer",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub manufacturer: Option<String>,
 }
 
 impl UserData for PluginInfo {}
 
 impl IntoIterator for PluginInfo {
     type Item = (String, String);
-    type IntoIter = std::array::IntoIter<(String, String), 6>;
+    type IntoIter = std::vec::IntoIter<(String, String)>;
 
     fn into_iter(self) -> Self::IntoIter {
-        IntoIterator::into_iter([
+        let mut fields = vec![
             ("Name".to_string(), self.name),
             ("Version".to_string(), self.version),
             ("BuildVersion".to_string(), self.build_version),
             ("Id".to_string(), self.id),
             ("Author".to_string(), self.author),
             ("Description".to_string(), self.description),
-        ])
+        ];
+        // Manufacturer is optional in Q-Sys, so leave it out rather than writing an empty string.
+        if let Some(manufacturer) = self.manufacturer {
+            fields.push(("Manufacturer".to_string(), manufacturer));
+        }
+        fields.into_iter()
     }
 }
 
@@ -67,7 +78,7 @@ impl PluginInfo {
         info
     }
 
-    pub fn to_lua_table(self, lua: &Lua) -> Table {
+    pub fn to_lua_table(self, lua: &Lua) -> Table<'_> {
         let table = lua.create_table().unwrap();
         for pairs in self.into_iter() {
             let (k, v) = pairs;
@@ -84,6 +95,7 @@ impl PluginInfo {
             "Id" => self.id = value,
             "Author" => self.author = value,
             "Description" => self.description = value,
+            "Manufacturer" => self.manufacturer = Some(value),
             _ => eprintln!("Invalid field: {}", field),
         }
     }
@@ -96,6 +108,7 @@ impl PluginInfo {
             "Id" => self.id.to_string(),
             "Author" => self.author.to_string(),
             "Description" => self.description.to_string(),
+            "Manufacturer" => self.manufacturer.clone().unwrap_or_default(),
             _ => panic!("Invalid field: {}", field),
         }
     }
@@ -163,7 +176,6 @@ impl PluginInfo {
 
 #[cfg(test)]
 mod tests {
-    use std::path::Path;
     use tempfile;
 
     use crate::assets::INFO_LUA;
@@ -217,6 +229,21 @@ mod tests {
         assert_eq!(&updated.id, &info.id);
     }
     #[test]
+    fn test_manufacturer_round_trip() {
+        let lua = Lua::new();
+        let info_path = INFO_LUA.clone().unwrap();
+        let mut info = PluginInfo::from_file(&info_path, &lua).unwrap();
+        assert_eq!(info.manufacturer, None);
+
+        info.update_field("Manufacturer", "ACME".to_string());
+        let destination = tempfile::tempdir().unwrap();
+        let written = destination.path().join("info.lua");
+        info.write_to_file(written.clone(), &lua).unwrap();
+
+        let reread = PluginInfo::from_file(&written, &Lua::new()).unwrap();
+        assert_eq!(reread.manufacturer.as_deref(), Some("ACME"));
+    }
+    #[test]
     fn test_write_info() {
         let lua = Lua::new();
         let info_path = INFO_LUA.clone().unwrap();
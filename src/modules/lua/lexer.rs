@@ -0,0 +1,212 @@
+use super::decompile::{close_long_bracket, long_bracket};
+
+const KEYWORDS: [&str; 22] = [
+    "and", "break", "do", "else", "elseif", "end", "false", "for", "function", "goto", "if", "in",
+    "local", "nil", "not", "or", "repeat", "return", "then", "true", "until", "while",
+];
+
+/// Longest first, so `...` isn't read as `..` and `.`.
+const SYMBOLS: [&str; 33] = [
+    "...", "..", "==", "~=", "<=", ">=", "<<", ">>", "//", "::", "+", "-", "*", "/", "%", "^", "#",
+    "&", "~", "|", "<", ">", "=", "(", ")", "{", "}", "[", "]", ";", ":", ",", ".",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Tok {
+    Name(String),
+    Keyword(&'static str),
+    Symbol(&'static str),
+    /// The string's source text between its delimiters, escapes left as written.
+    String(String),
+    Number,
+    Eof,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub tok: Tok,
+    /// 1-based line the token starts on.
+    pub line: usize,
+}
+
+/// Split Lua source into tokens, dropping comments and whitespace. Errors carry the line number.
+pub fn tokenize(source: &str) -> Result<Vec<Token>, (usize, String)> {
+    let bytes = source.as_bytes();
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut i = 0;
+    let newlines = |from: usize, to: usize| bytes[from..to].iter().filter(|b| **b == b'\n').count();
+
+    while i < bytes.len() {
+        let start = i;
+        let b = bytes[i];
+        if b == b'\n' {
+            line += 1;
+            i += 1;
+        } else if b.is_ascii_whitespace() {
+            i += 1;
+        } else if b == b'-' && bytes.get(i + 1) == Some(&b'-') {
+            i = match long_bracket(bytes, i + 2) {
+                Some(level) => close_long_bracket(bytes, i + 2, level),
+                None => bytes[i..]
+                    .iter()
+                    .position(|b| *b == b'\n')
+                    .map_or(bytes.len(), |p| i + p),
+            };
+            line += newlines(start, i);
+        } else if b == b'[' && long_bracket(bytes, i).is_some() {
+            let level = long_bracket(bytes, i).unwrap();
+            i = close_long_bracket(bytes, i, level);
+            if !source[start..i].ends_with(&format!("]{}]", "=".repeat(level))) {
+                return Err((line, "unfinished long string".to_string()));
+            }
+            let body = start + level + 2;
+            tokens.push(Token {
+                tok: Tok::String(source[body..i - level - 2].to_string()),
+                line,
+            });
+            line += newlines(start, i);
+        } else if b == b'"' || b == b'\'' {
+            let string_line = line;
+            i += 1;
+            loop {
+                match bytes.get(i) {
+                    None | Some(b'\n') => return Err((line, "unfinished string".to_string())),
+                    Some(b'\\') => match bytes.get(i + 1) {
+                        // `\z` skips the whitespace after it, newlines included.
+                        Some(b'z') => {
+                            i += 2;
+                            while let Some(c) = bytes.get(i).filter(|c| c.is_ascii_whitespace()) {
+                                if *c == b'\n' {
+                                    line += 1;
+                                }
+                                i += 1;
+                            }
+                        }
+                        // An escaped newline continues the string. `\r\n` and `\n\r` are one.
+                        Some(&c @ (b'\n' | b'\r')) => {
+                            line += 1;
+                            let pair = if c == b'\n' { b'\r' } else { b'\n' };
+                            i += if bytes.get(i + 2) == Some(&pair) {
+                                3
+                            } else {
+                                2
+                            };
+                        }
+                        _ => i += 2,
+                    },
+                    Some(c) if *c == b => {
+                        i += 1;
+                        break;
+                    }
+                    Some(_) => i += 1,
+                }
+            }
+            tokens.push(Token {
+                tok: Tok::String(source[start + 1..i - 1].to_string()),
+                line: string_line,
+            });
+        } else if b.is_ascii_digit()
+            || (b == b'.' && bytes.get(i + 1).is_some_and(|c| c.is_ascii_digit()))
+        {
+            let hex = b == b'0' && matches!(bytes.get(i + 1), Some(b'x' | b'X'));
+            if hex {
+                i += 2;
+            }
+            while let Some(&c) = bytes.get(i) {
+                let exponent = if hex { b"pP" } else { b"eE" };
+                if exponent.contains(&c) && matches!(bytes.get(i + 1), Some(b'+' | b'-')) {
+                    i += 2;
+                } else if c.is_ascii_alphanumeric() || c == b'.' {
+                    i += 1;
+                } else {
+                    break;
+                }
+            }
+            tokens.push(Token {
+                tok: Tok::Number,
+                line,
+            });
+        } else if b.is_ascii_alphabetic() || b == b'_' {
+            while bytes
+                .get(i)
+                .is_some_and(|c| c.is_ascii_alphanumeric() || *c == b'_')
+            {
+                i += 1;
+            }
+            let word = &source[start..i];
+            let tok = match KEYWORDS.iter().find(|k| **k == word) {
+                Some(keyword) => Tok::Keyword(keyword),
+                None => Tok::Name(word.to_string()),
+            };
+            tokens.push(Token { tok, line });
+        } else {
+            let Some(symbol) = SYMBOLS.iter().find(|s| source[i..].starts_with(**s)) else {
+                let c = source[i..].chars().next().unwrap();
+                return Err((line, format!("unexpected symbol `{}`", c)));
+            };
+            i += symbol.len();
+            tokens.push(Token {
+                tok: Tok::Symbol(symbol),
+                line,
+            });
+        }
+    }
+
+    tokens.push(Token {
+        tok: Tok::Eof,
+        line,
+    });
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize() {
+        let tokens =
+            tokenize("local x <const> = 0x1F + 1e-3 -- hi\n..[[a\nb]] ~= 'c\\'d' ...").unwrap();
+        let toks: Vec<&Tok> = tokens.iter().map(|t| &t.tok).collect();
+        assert_eq!(
+            toks,
+            [
+                &Tok::Keyword("local"),
+                &Tok::Name("x".to_string()),
+                &Tok::Symbol("<"),
+                &Tok::Name("const".to_string()),
+                &Tok::Symbol(">"),
+                &Tok::Symbol("="),
+                &Tok::Number,
+                &Tok::Symbol("+"),
+                &Tok::Number,
+                &Tok::Symbol(".."),
+                &Tok::String("a\nb".to_string()),
+                &Tok::Symbol("~="),
+                &Tok::String("c\\'d".to_string()),
+                &Tok::Symbol("..."),
+                &Tok::Eof,
+            ]
+        );
+        assert_eq!(tokens[9].line, 2);
+        assert_eq!(tokens[11].line, 3);
+    }
+
+    #[test]
+    fn test_tokenize_escaped_newlines() {
+        let tokens = tokenize("x = \"a\\z\n   \n  b\"\ny = 'c\\\r\nd'\nz = 1").unwrap();
+        let lines: Vec<(&Tok, usize)> = tokens.iter().map(|t| (&t.tok, t.line)).collect();
+        assert_eq!(lines[2], (&Tok::String("a\\z\n   \n  b".to_string()), 1));
+        assert_eq!(lines[3], (&Tok::Name("y".to_string()), 4));
+        assert_eq!(lines[5], (&Tok::String("c\\\r\nd".to_string()), 4));
+        assert_eq!(lines[6], (&Tok::Name("z".to_string()), 6));
+    }
+
+    #[test]
+    fn test_tokenize_errors() {
+        assert_eq!(tokenize("x = 1\ny = 'oops\n").unwrap_err().0, 2);
+        assert!(tokenize("x = [[never closed").is_err());
+        assert!(tokenize("x = 1 @ 2").is_err());
+    }
+}
@@ -0,0 +1,280 @@
+use std::{collections::HashSet, fmt};
+
+use include_dir::Dir;
+
+use super::{
+    decompile::SECTIONS,
+    resolve::{resolve, Access, SyntaxError},
+};
+
+/// The Lua 5.3 standard library as Q-SYS loads it. What the sandbox takes away is up to the
+/// compatibility check, not the linter.
+const LUA_GLOBALS: [&str; 34] = [
+    "_G",
+    "_VERSION",
+    "assert",
+    "collectgarbage",
+    "coroutine",
+    "debug",
+    "dofile",
+    "error",
+    "getmetatable",
+    "io",
+    "ipairs",
+    "load",
+    "loadfile",
+    "math",
+    "next",
+    "os",
+    "package",
+    "pairs",
+    "pcall",
+    "print",
+    "rawequal",
+    "rawget",
+    "rawlen",
+    "rawset",
+    "require",
+    "select",
+    "setmetatable",
+    "string",
+    "table",
+    "tonumber",
+    "tostring",
+    "type",
+    "utf8",
+    "xpcall",
+];
+
+/// Globals Designer hands a plugin at design time that the definitions don't declare.
+const DESIGNER_GLOBALS: [&str; 2] = ["PluginInfo", "PageNames"];
+
+#[derive(Debug, PartialEq)]
+pub enum Problem {
+    /// Read of a global nothing defines. Carries the closest known name, if one is close.
+    UndefinedGlobal(String, Option<String>),
+    /// Assignment to a global the plugin isn't expected to define.
+    UnexpectedGlobal(String),
+    Syntax(String),
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::UndefinedGlobal(name, Some(suggestion)) => write!(
+                f,
+                "read of undefined global `{}` (did you mean `{}`?)",
+                name, suggestion
+            ),
+            Problem::UndefinedGlobal(name, None) => {
+                write!(f, "read of undefined global `{}`", name)
+            }
+            Problem::UnexpectedGlobal(name) => write!(
+                f,
+                "write to unexpected global `{}` (make it `local`?)",
+                name
+            ),
+            Problem::Syntax(message) => write!(f, "syntax error: {}", message),
+        }
+    }
+}
+
+/// The globals a plugin may read: the Lua standard library, everything the definitions in
+/// `definitions` assign at the top level, and the plugin's own design-time globals.
+pub fn known_globals(definitions: &Dir) -> HashSet<String> {
+    let mut known: HashSet<String> = LUA_GLOBALS
+        .iter()
+        .chain(DESIGNER_GLOBALS.iter())
+        .chain(SECTIONS.iter().map(|(name, _)| name))
+        .map(|name| name.to_string())
+        .collect();
+    known.extend(definition_globals(definitions));
+    known
+}
+
+fn definition_globals(dir: &Dir) -> Vec<String> {
+    let mut globals = Vec::new();
+    for file in dir.files() {
+        let is_lua = file.path().extension().is_some_and(|ext| ext == "lua");
+        let Some(source) = file.contents_utf8().filter(|_| is_lua) else {
+            continue;
+        };
+        // A definitions file that doesn't parse only costs its own globals.
+        if let Ok(resolved) = resolve(source) {
+            globals.extend(
+                resolved
+                    .globals
+                    .into_iter()
+                    .filter(|global| global.access == Access::Write)
+                    .map(|global| global.name),
+            );
+        }
+    }
+    for sub_dir in dir.dirs() {
+        globals.extend(definition_globals(sub_dir));
+    }
+    globals
+}
+
+/// Lint a whole plugin chunk. Returns every problem with its line in `source`.
+pub fn lint(source: &str, known: &HashSet<String>) -> Vec<(usize, Problem)> {
+    let resolved = match resolve(source) {
+        Ok(resolved) => resolved,
+        Err(SyntaxError { line, message }) => return vec![(line, Problem::Syntax(message))],
+    };
+
+    let expected_writes: HashSet<&str> = DESIGNER_GLOBALS
+        .iter()
+        .copied()
+        .chain(SECTIONS.iter().map(|(name, _)| *name))
+        .collect();
+    // Globals the plugin defines itself are reported where they're written, not where they're
+    // read.
+    let written: HashSet<&str> = resolved
+        .globals
+        .iter()
+        .filter(|global| global.access == Access::Write)
+        .map(|global| global.name.as_str())
+        .collect();
+
+    let mut problems = Vec::new();
+    for global in &resolved.globals {
+        let name = global.name.as_str();
+        match global.access {
+            Access::Write if !expected_writes.contains(name) => {
+                problems.push((global.line, Problem::UnexpectedGlobal(name.to_string())));
+            }
+            Access::Read if !known.contains(name) && !written.contains(name) => {
+                problems.push((
+                    global.line,
+                    Problem::UndefinedGlobal(name.to_string(), closest(name, known)),
+                ));
+            }
+            _ => {}
+        }
+    }
+    problems
+}
+
+/// The known name within a couple of edits of `name`, for typos like `TcpSocekt`.
+fn closest(name: &str, known: &HashSet<String>) -> Option<String> {
+    let max = if name.len() > 4 { 2 } else { 1 };
+    known
+        .iter()
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max)
+        .min()
+        .map(|(_, candidate)| candidate.to_owned())
+}
+
+/// Optimal string alignment distance: Levenshtein, with swapped neighbours counting as one edit.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assets::DEFINITIONS_DIR;
+
+    #[test]
+    fn test_known_globals() {
+        let known = known_globals(&DEFINITIONS_DIR);
+        for name in [
+            "TcpSocket",
+            "Timer",
+            "Controls",
+            "Component",
+            "print",
+            "GetControls",
+        ] {
+            assert!(known.contains(name), "{} should be known", name);
+        }
+        // Modules are `require`d, not globals.
+        assert!(!known.contains("rapidjson"));
+    }
+
+    #[test]
+    fn test_definitions_parse() {
+        for file in DEFINITIONS_DIR.files() {
+            if file.path().extension().is_some_and(|ext| ext == "lua") {
+                let source = file.contents_utf8().unwrap();
+                if let Err(e) = resolve(source) {
+                    panic!("{}:{}: {}", file.path().display(), e.line, e.message);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_lint() {
+        let known = known_globals(&DEFINITIONS_DIR);
+        let source = r#"PluginInfo = { Name = "Test" }
+function GetControls(props)
+  return {}
+end
+function helper() end
+if Controls then
+  local sock = TcpSocekt.New()
+  helper()
+  Tmer.CallAfter(function() end, 1)
+  print(undefined_thing)
+end
+"#;
+        let problems: Vec<(usize, String)> = lint(source, &known)
+            .into_iter()
+            .map(|(line, problem)| (line, problem.to_string()))
+            .collect();
+        assert_eq!(
+            problems,
+            [
+                (
+                    5,
+                    "write to unexpected global `helper` (make it `local`?)".to_string()
+                ),
+                (
+                    7,
+                    "read of undefined global `TcpSocekt` (did you mean `TcpSocket`?)".to_string()
+                ),
+                (
+                    9,
+                    "read of undefined global `Tmer` (did you mean `Timer`?)".to_string()
+                ),
+                (10, "read of undefined global `undefined_thing`".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lint_syntax_error() {
+        let problems = lint("if x then\n", &HashSet::new());
+        assert!(matches!(problems[..], [(2, Problem::Syntax(_))]));
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("TcpSocekt", "TcpSocket"), 1);
+        assert_eq!(edit_distance("Tmer", "Timer"), 1);
+        assert_eq!(edit_distance("abc", "xyz"), 3);
+    }
+}
@@ -1,3 +1,30 @@
 pub mod api;
+pub mod compat;
+pub mod decompile;
+pub mod designer;
 pub mod info;
+pub mod lexer;
+pub mod lint;
 pub mod parser;
+pub mod plugin;
+pub mod resolve;
+pub mod runtime;
+pub mod since;
+pub mod spec;
+pub mod sweep;
+
+/// The message a Lua error was raised with, `chunk:line: ...`, without mlua's framing or the
+/// stack traceback.
+pub fn error_message(error: &mlua::Error) -> String {
+    let message = match error {
+        mlua::Error::RuntimeError(message) | mlua::Error::SyntaxError { message, .. } => {
+            message.clone()
+        }
+        mlua::Error::CallbackError { cause, .. } => return error_message(cause),
+        other => other.to_string(),
+    };
+    match message.split_once("\nstack traceback:") {
+        Some((message, _)) => message.to_string(),
+        None => message,
+    }
+}
@@ -1,21 +1,43 @@
-use std::{fs, path::PathBuf};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
 
 use mlua::{Lua, Table, Value};
 use regex::{Captures, Regex};
 
-use crate::assets::INIT_LUA;
+use crate::license::{is_spdx_header, spdx_header};
 
 pub fn name_table(table_name: &str, table: &str) -> String {
     format!("{} = {}", table_name, table).to_string()
 }
 
+/// Quote `s` as a Lua string literal, escaping anything that would break out of it.
+pub fn quote_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{{{:X}}}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
 pub fn serialize_value(lua: &Lua, value: &Value) -> String {
     match value {
         Value::Nil => "nil".to_string(),
         Value::Boolean(b) => b.to_string(),
         Value::Integer(i) => i.to_string(),
         Value::Number(n) => n.to_string(),
-        Value::String(s) => format!(r#""{}""#, s.to_str().unwrap_or("")),
+        Value::String(s) => quote_string(s.to_str().unwrap_or("")),
         Value::Table(t) => serialize_table(lua, t),
         _ => "unsupported".to_string(), // Handle more types if needed
     }
@@ -39,24 +61,123 @@ pub fn serialize_table(lua: &Lua, table: &Table) -> String {
 }
 
 pub fn find_lua_requirements(haystack: &str, plugin_path: PathBuf) -> String {
+    inline_modules(haystack, &plugin_path, &|_, contents| contents)
+}
+
+/// Inline includes and requires of the plugin's own modules, passing each inlined file through
+/// `wrap` first.
+fn inline_modules(
+    haystack: &str,
+    plugin_path: &Path,
+    wrap: &dyn Fn(&Path, String) -> String,
+) -> String {
+    // QSC-style frameworks splice files in with `--[[ #include "path/file.lua" ]]`.
+    let include = Regex::new(r#"--\[\[\s*#include\s+"([^"]+)"\s*\]\]"#).unwrap();
+    let haystack = include.replace_all(haystack, |cap: &Captures| {
+        let include_path = plugin_path.join(&cap[1]);
+        if include_path.exists() {
+            wrap(&include_path, fs::read_to_string(&include_path).unwrap())
+        } else {
+            eprintln!("Include {:?} not found", &cap[1]);
+            String::new()
+        }
+    });
+
     // Regex to match require statements (assumes simple pattern like require('module'))
     let re = Regex::new(r#"require\(['"]([^'"]+)['"]\)"#).unwrap();
 
-    let result = re.replace_all(haystack, |cap: &Captures| {
+    let result = re.replace_all(&haystack, |cap: &Captures| {
         let mod_path = plugin_path.join(format!("{}.lua", cap[1].to_string().replace('.', "/")));
         if mod_path.exists() {
-            fs::read_to_string(mod_path).unwrap()
+            wrap(&mod_path, fs::read_to_string(&mod_path).unwrap())
         } else {
-            eprintln!("Module {:?} not found", &cap[1]);
-            String::new()
+            // Not one of ours, e.g. Q-SYS's built-in `rapidjson`. Leave it for the runtime.
+            cap[0].to_string()
         }
     });
 
     result.to_string()
 }
 
-pub fn merge_lua_files(root_path: PathBuf, plugin_path: PathBuf) -> std::io::Result<()> {
-    let init_file = INIT_LUA.clone().expect("Failed to load init.lua");
+/// The plugin as a single chunk: `init_file` with every module inlined.
+pub fn merge_plugin(plugin_path: &Path, init_file: &Path) -> std::io::Result<String> {
+    // Read the skeleton Lua file
+    let init_content = fs::read_to_string(init_file)?;
+
+    // Update the init file with the modules.
+    Ok(find_lua_requirements(
+        &init_content,
+        plugin_path.to_path_buf(),
+    ))
+}
+
+const MODULE_BEGIN: &str = "--[[qplug:begin ";
+const MODULE_END: &str = "--[[qplug:end]]";
+
+/// The file and line every line of a merged plugin came from.
+pub type SourceMap = Vec<(PathBuf, usize)>;
+
+/// Like `merge_plugin`, plus where each line came from. Inlined modules are wrapped in marker
+/// comments, so the chunk runs the same as the compiled plugin but isn't byte for byte equal.
+pub fn merge_plugin_mapped(
+    plugin_path: &Path,
+    init_file: &Path,
+) -> std::io::Result<(String, SourceMap)> {
+    let init_content = fs::read_to_string(init_file)?;
+    let merged = inline_modules(&init_content, plugin_path, &|path, contents| {
+        format!(
+            "{}{}]]\n{}\n{}",
+            MODULE_BEGIN,
+            path.display(),
+            contents,
+            MODULE_END
+        )
+    });
+
+    let mut map = SourceMap::new();
+    let mut files = vec![(init_file.to_path_buf(), 1)];
+    for line in merged.split('\n') {
+        // A module ends where its `require` was, so the rest of that line is the requiring file's.
+        let mut rest = line;
+        while let Some(after) = rest.strip_prefix(MODULE_END) {
+            if files.len() > 1 {
+                files.pop();
+            }
+            rest = after;
+        }
+
+        let (file, line_number) = files.last_mut().unwrap();
+        map.push((file.clone(), *line_number));
+        match rest.find(MODULE_BEGIN) {
+            Some(start) => {
+                let path = rest[start + MODULE_BEGIN.len()..].trim_end_matches("]]");
+                files.push((PathBuf::from(path), 1));
+            }
+            None => *line_number += 1,
+        }
+    }
+
+    Ok((merged, map))
+}
+
+/// `file:line` relative to `root_path` for a line of the merged plugin.
+pub fn locate(root_path: &Path, map: &SourceMap, line: usize) -> String {
+    match map.get(line - 1) {
+        Some((file, file_line)) => format!(
+            "{}:{}",
+            file.strip_prefix(root_path).unwrap_or(file).display(),
+            file_line
+        ),
+        None => line.to_string(),
+    }
+}
+
+pub fn merge_lua_files(
+    root_path: PathBuf,
+    plugin_path: PathBuf,
+    init_file: PathBuf,
+    spdx_id: Option<&str>,
+) -> std::io::Result<()> {
     let plugin_name = root_path
         .file_name()
         .expect("Failed to parse plugin name from path");
@@ -64,11 +185,20 @@ pub fn merge_lua_files(root_path: PathBuf, plugin_path: PathBuf) -> std::io::Res
         plugin_name.to_str().expect("Oops").to_string() + ".qplug",
     ));
 
-    // Read the skeleton Lua file
-    let mut init_content = fs::read_to_string(init_file)?;
-
-    // Update the init file with the modules.
-    init_content = find_lua_requirements(&init_content, plugin_path);
+    let mut init_content = merge_plugin(&plugin_path, &init_file)?;
+
+    // One header for the whole plugin instead of one per module.
+    if let Some(id) = spdx_id {
+        let body: Vec<&str> = init_content
+            .lines()
+            .filter(|line| !is_spdx_header(line))
+            .collect();
+        init_content = format!(
+            "{}\n{}\n",
+            spdx_header(id),
+            body.join("\n").trim_end_matches('\n')
+        );
+    }
 
     // Write the result to a new file
     fs::write(qplug_file, init_content)?;
@@ -161,6 +291,92 @@ mod tests {
         assert_eq!(&serialized, expected);
     }
 
+    #[test]
+    fn test_quote_string_round_trip() {
+        let lua = Lua::new();
+        let original = "say \"hi\"\\ to\nC:\\Users\t\u{7}";
+        let loaded: String = lua
+            .load(format!("return {}", quote_string(original)))
+            .eval()
+            .unwrap();
+        assert_eq!(loaded, original);
+    }
+
+    #[test]
+    fn test_merge_lua_files_spdx_header() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root_path = temp_dir.path().join("demo");
+        let plugin_path = root_path.join("plugin_src");
+        fs::create_dir_all(&plugin_path).unwrap();
+        fs::write(
+            plugin_path.join("init.lua"),
+            "-- SPDX-License-Identifier: MIT\nrequire(\"controls\")\n",
+        )
+        .unwrap();
+        fs::write(
+            plugin_path.join("controls.lua"),
+            "-- SPDX-License-Identifier: MIT\nControls = 1\n",
+        )
+        .unwrap();
+
+        merge_lua_files(
+            root_path.clone(),
+            plugin_path.clone(),
+            plugin_path.join("init.lua"),
+            Some("MIT"),
+        )
+        .unwrap();
+        assert_eq!(
+            fs::read_to_string(root_path.join("demo.qplug")).unwrap(),
+            "-- SPDX-License-Identifier: MIT\nControls = 1\n"
+        );
+    }
+
+    #[test]
+    fn test_find_lua_includes() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(temp_dir.path().join("src")).unwrap();
+        fs::write(temp_dir.path().join("src/controls.lua"), "Controls = 1").unwrap();
+        fs::write(temp_dir.path().join("info.lua"), "Info = 1").unwrap();
+
+        let merged = find_lua_requirements(
+            "--[[ #include \"info.lua\" ]]\n--[[ #include \"src/controls.lua\" ]]",
+            temp_dir.path().to_path_buf(),
+        );
+        assert_eq!(merged, "Info = 1\nControls = 1");
+    }
+
+    #[test]
+    fn test_merge_plugin_mapped() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("setup")).unwrap();
+        fs::write(root.join("setup/info.lua"), "-- info\nPluginInfo = {}\n").unwrap();
+        fs::write(root.join("util.lua"), "return {}").unwrap();
+        fs::write(
+            root.join("init.lua"),
+            "require(\"setup.info\")\nlocal util = require(\"util\") or {}\nprint(util)\n",
+        )
+        .unwrap();
+
+        let (merged, map) = merge_plugin_mapped(root, &root.join("init.lua")).unwrap();
+        let line_of = |needle: &str| {
+            let index = merged.lines().position(|l| l.contains(needle)).unwrap();
+            let (file, line) = &map[index];
+            (
+                file.strip_prefix(root)
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned(),
+                *line,
+            )
+        };
+        assert_eq!(line_of("PluginInfo"), ("setup/info.lua".to_string(), 2));
+        assert_eq!(line_of("return {}"), ("util.lua".to_string(), 1));
+        assert_eq!(line_of("or {}"), ("init.lua".to_string(), 2));
+        assert_eq!(line_of("print(util)"), ("init.lua".to_string(), 3));
+    }
+
     // #[test]
     // fn test_find_lua_requirements() {
     //     let content = r#"
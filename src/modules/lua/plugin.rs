@@ -0,0 +1,245 @@
+use std::path::Path;
+
+use mlua::{Function, Lua, Table, Value};
+use serde_json::Map;
+
+use super::{
+    designer::{rectified_props, seed_design_time},
+    parser::merge_plugin,
+};
+use crate::assets::DEFINITIONS_DIR;
+
+/// A control as declared by `GetControls`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ControlInfo {
+    pub name: String,
+    /// `ControlType`: Button, Knob, Indicator or Text.
+    pub control_type: String,
+    /// Controls with a count above one are indexed: `Controls.Name[1]`.
+    pub count: u32,
+}
+
+/// A property as declared by `GetProperties`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PropertyInfo {
+    pub name: String,
+    /// `Type`: string, integer, double, boolean or enum.
+    pub property_type: String,
+    /// The choices of an enum property.
+    pub choices: Vec<String>,
+}
+
+/// Load the project's plugin into `lua` the way Designer does, with the definitions stubbed in
+/// as for `qplug inspect`. `Controls` is nil, so a runtime section behind `if Controls then`
+/// doesn't run.
+pub fn load_project(lua: &Lua, plugin_path: &Path, entry_path: &Path) -> mlua::Result<()> {
+    let source = merge_plugin(plugin_path, entry_path).map_err(mlua::Error::external)?;
+    let name = entry_path.file_name().unwrap_or_default().to_string_lossy();
+    seed_design_time(lua, &DEFINITIONS_DIR)?;
+    load_plugin(lua, &source, &name)
+}
+
+/// Run a compiled plugin's top-level code. Modules Q-SYS provides (`rapidjson`, ...) load as
+/// empty tables.
+pub fn load_plugin(lua: &Lua, source: &str, name: &str) -> mlua::Result<()> {
+    lua.load(
+        r#"table.insert(package.searchers, function(name)
+            return function() return {} end
+        end)"#,
+    )
+    .exec()?;
+    lua.load(source).set_name(name).exec()
+}
+
+/// Call `GetProperties()` and list what it declares, as `RectifyProperties` leaves it.
+pub fn get_properties(lua: &Lua) -> mlua::Result<Vec<PropertyInfo>> {
+    let Some(list) = property_list(lua)? else {
+        return Ok(Vec::new());
+    };
+    let props = rectified_props(lua, Some(&list), &Map::new())?;
+
+    list.sequence_values::<Table>()
+        .map(|declared| {
+            let declared = declared?;
+            let name: String = declared.get("Name")?;
+            let property = props
+                .get::<_, Option<Table>>(name.as_str())?
+                .unwrap_or(declared);
+            Ok(PropertyInfo {
+                name,
+                property_type: property
+                    .get::<_, Option<String>>("Type")?
+                    .unwrap_or_else(|| "string".to_string()),
+                choices: property
+                    .get::<_, Option<Vec<String>>>("Choices")?
+                    .unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+/// Call `GetControls(props)` with the `props` Designer passes it, the `GetProperties` defaults
+/// and `plugin_show_debug` through `RectifyProperties`, and list what it declares.
+pub fn get_controls(lua: &Lua) -> mlua::Result<Vec<ControlInfo>> {
+    let Some(get_controls) = callback(lua, "GetControls")? else {
+        return Ok(Vec::new());
+    };
+    let list = property_list(lua)?;
+    let props = rectified_props(lua, list.as_ref(), &Map::new())?;
+
+    let list: Table = get_controls.call(props)?;
+    list.sequence_values::<Table>()
+        .map(|control| {
+            let control = control?;
+            Ok(ControlInfo {
+                name: control.get("Name")?,
+                control_type: control
+                    .get::<_, Option<String>>("ControlType")?
+                    .unwrap_or_else(|| "Button".to_string()),
+                count: control.get::<_, Option<u32>>("Count")?.unwrap_or(1),
+            })
+        })
+        .collect()
+}
+
+/// What `GetProperties()` returns, if the plugin defines it.
+fn property_list(lua: &Lua) -> mlua::Result<Option<Table<'_>>> {
+    match callback(lua, "GetProperties")? {
+        Some(get_properties) => get_properties.call(()),
+        None => Ok(None),
+    }
+}
+
+/// A global callback such as `GetControls`, if the plugin defines it.
+pub fn callback<'lua>(lua: &'lua Lua, name: &str) -> mlua::Result<Option<Function<'lua>>> {
+    match lua.globals().get::<_, Value>(name)? {
+        Value::Function(f) => Ok(Some(f)),
+        Value::Nil => Ok(None),
+        other => Err(mlua::Error::RuntimeError(format!(
+            "{} should be a function, not a {}",
+            name,
+            other.type_name()
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLUGIN: &str = r#"
+        local json = require("rapidjson")
+        function GetProperties()
+            return { { Name = "Input Count", Type = "integer", Value = 4 } }
+        end
+        function GetControls(props)
+            return {
+                { Name = "Gain", ControlType = "Knob", Count = props["Input Count"].Value },
+                { Name = "Mute" },
+            }
+        end
+        if Controls then
+            Controls.Gain[1].EventHandler = nil
+        end
+    "#;
+
+    #[test]
+    fn test_get_controls() {
+        let lua = Lua::new();
+        load_plugin(&lua, PLUGIN, "plugin").unwrap();
+        let controls = get_controls(&lua).unwrap();
+
+        assert_eq!(
+            controls,
+            [
+                ControlInfo {
+                    name: "Gain".to_string(),
+                    control_type: "Knob".to_string(),
+                    count: 4,
+                },
+                ControlInfo {
+                    name: "Mute".to_string(),
+                    control_type: "Button".to_string(),
+                    count: 1,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_get_controls_with_designer_props() {
+        let lua = Lua::new();
+        load_plugin(
+            &lua,
+            r#"function GetProperties()
+                return {
+                    { Name = "Mode", Type = "enum", Choices = { "A", "B" }, Value = "A" },
+                    { Name = "Channels", Type = "integer", Value = 2 },
+                }
+            end
+            function RectifyProperties(props)
+                props.Channels.Value = props.Mode.Value == "A" and 3 or 1
+                return props
+            end
+            function GetControls(props)
+                local controls = { { Name = "Gain", Count = props.Channels.Value } }
+                if props.plugin_show_debug.Value then
+                    table.insert(controls, { Name = "Debug", ControlType = "Text" })
+                end
+                return controls
+            end"#,
+            "plugin",
+        )
+        .unwrap();
+
+        let controls = get_controls(&lua).unwrap();
+        assert_eq!(controls.len(), 1);
+        assert_eq!(controls[0].count, 3);
+    }
+
+    #[test]
+    fn test_get_properties() {
+        let lua = Lua::new();
+        load_plugin(
+            &lua,
+            r#"function GetProperties()
+                return {
+                    { Name = "Mode", Type = "enum", Choices = { "A", "B" }, Value = "A" },
+                    { Name = "Port", Type = "integer", Value = 23 },
+                }
+            end
+            function RectifyProperties(props)
+                props.Port.IsHidden = not props.plugin_show_debug.Value
+                return props
+            end"#,
+            "plugin",
+        )
+        .unwrap();
+
+        let properties = get_properties(&lua).unwrap();
+        assert_eq!(properties[0].name, "Mode");
+        assert_eq!(properties[0].choices, ["A", "B"]);
+        assert_eq!(properties[1].property_type, "integer");
+        assert!(properties[1].choices.is_empty());
+    }
+
+    #[test]
+    fn test_load_project_design_time() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("init.lua"),
+            "assert(Timer, 'no definitions')\nassert(Controls == nil)\nfunction GetControls() return { { Name = \"A\" } } end\n",
+        )
+        .unwrap();
+        let lua = Lua::new();
+        load_project(&lua, dir.path(), &dir.path().join("init.lua")).unwrap();
+        assert_eq!(get_controls(&lua).unwrap()[0].name, "A");
+    }
+
+    #[test]
+    fn test_load_plugin_error_location() {
+        let lua = Lua::new();
+        let err = load_plugin(&lua, "\nlocal x = nil + 1", "plugin.qplug").unwrap_err();
+        assert!(err.to_string().contains("plugin.qplug\"]:2:"));
+    }
+}
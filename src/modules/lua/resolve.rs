@@ -0,0 +1,700 @@
+use std::collections::HashSet;
+
+use super::lexer::{tokenize, Tok, Token};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+/// A global variable referenced by the chunk.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlobalAccess {
+    pub name: String,
+    pub line: usize,
+    pub access: Access,
+    /// The field read right after the global, e.g. `open` in `io.open(...)`.
+    pub field: Option<String>,
+}
+
+/// What the resolver found in a chunk.
+#[derive(Debug, Default, PartialEq)]
+pub struct Resolved {
+    /// Every global read and write, in source order.
+    pub globals: Vec<GlobalAccess>,
+    /// Lua 5.4 local attributes (`<const>`, `<close>`) with their line.
+    pub attribs: Vec<(usize, String)>,
+    /// Modules loaded with the global `require` and a literal name, with their line.
+    pub requires: Vec<(usize, String)>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SyntaxError {
+    pub line: usize,
+    pub message: String,
+}
+
+/// Parse `source` as a Lua chunk and resolve every name against its scope, collecting the globals
+/// it uses. Accepts Lua 5.4 syntax so the callers can report what Q-SYS doesn't support.
+pub fn resolve(source: &str) -> Result<Resolved, SyntaxError> {
+    let tokens = tokenize(source).map_err(|(line, message)| SyntaxError { line, message })?;
+    let mut resolver = Resolver {
+        tokens,
+        pos: 0,
+        scopes: vec![HashSet::new()],
+        resolved: Resolved::default(),
+    };
+    resolver.block()?;
+    resolver.expect_eof()?;
+    Ok(resolver.resolved)
+}
+
+/// What a suffixed expression turned out to be, which decides if it can be assigned to.
+enum Suffixed {
+    /// A bare name, not yet counted as a read or a write.
+    Name(String, usize),
+    Indexed,
+    Call,
+}
+
+struct Resolver {
+    tokens: Vec<Token>,
+    pos: usize,
+    scopes: Vec<HashSet<String>>,
+    resolved: Resolved,
+}
+
+impl Resolver {
+    fn peek(&self) -> &Tok {
+        &self.tokens[self.pos].tok
+    }
+
+    fn peek_at(&self, offset: usize) -> &Tok {
+        let index = (self.pos + offset).min(self.tokens.len() - 1);
+        &self.tokens[index].tok
+    }
+
+    fn line(&self) -> usize {
+        self.tokens[self.pos].line
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.pos].clone();
+        if self.pos < self.tokens.len() - 1 {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn is_symbol(&self, symbol: &str) -> bool {
+        matches!(self.peek(), Tok::Symbol(s) if *s == symbol)
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Tok::Keyword(k) if *k == keyword)
+    }
+
+    fn accept_symbol(&mut self, symbol: &str) -> bool {
+        let found = self.is_symbol(symbol);
+        if found {
+            self.next();
+        }
+        found
+    }
+
+    fn accept_keyword(&mut self, keyword: &str) -> bool {
+        let found = self.is_keyword(keyword);
+        if found {
+            self.next();
+        }
+        found
+    }
+
+    fn error<T>(&self, expected: &str) -> Result<T, SyntaxError> {
+        let found = match self.peek() {
+            Tok::Name(name) => format!("`{}`", name),
+            Tok::Keyword(keyword) => format!("`{}`", keyword),
+            Tok::Symbol(symbol) => format!("`{}`", symbol),
+            Tok::String(_) => "a string".to_string(),
+            Tok::Number => "a number".to_string(),
+            Tok::Eof => "the end of the file".to_string(),
+        };
+        Err(SyntaxError {
+            line: self.line(),
+            message: format!("expected {} near {}", expected, found),
+        })
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> Result<(), SyntaxError> {
+        if self.accept_symbol(symbol) {
+            Ok(())
+        } else {
+            self.error(&format!("`{}`", symbol))
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), SyntaxError> {
+        if self.accept_keyword(keyword) {
+            Ok(())
+        } else {
+            self.error(&format!("`{}`", keyword))
+        }
+    }
+
+    fn expect_name(&mut self) -> Result<String, SyntaxError> {
+        match self.peek().clone() {
+            Tok::Name(name) => {
+                self.next();
+                Ok(name)
+            }
+            _ => self.error("a name"),
+        }
+    }
+
+    fn expect_eof(&self) -> Result<(), SyntaxError> {
+        match self.peek() {
+            Tok::Eof => Ok(()),
+            _ => self.error("the end of the file"),
+        }
+    }
+
+    fn declare(&mut self, name: String) {
+        self.scopes.last_mut().unwrap().insert(name);
+    }
+
+    fn is_local(&self, name: &str) -> bool {
+        self.scopes.iter().any(|scope| scope.contains(name))
+    }
+
+    fn global(&mut self, name: String, line: usize, access: Access, field: Option<String>) {
+        if !self.is_local(&name) {
+            self.resolved.globals.push(GlobalAccess {
+                name,
+                line,
+                access,
+                field,
+            });
+        }
+    }
+
+    /// A block in its own scope.
+    fn scoped_block(&mut self) -> Result<(), SyntaxError> {
+        self.scopes.push(HashSet::new());
+        let result = self.block();
+        self.scopes.pop();
+        result
+    }
+
+    fn block(&mut self) -> Result<(), SyntaxError> {
+        loop {
+            match self.peek() {
+                Tok::Eof => return Ok(()),
+                Tok::Keyword("end" | "else" | "elseif" | "until") => return Ok(()),
+                Tok::Keyword("return") => {
+                    self.next();
+                    if !self.block_follows() && !self.is_symbol(";") {
+                        self.expression_list()?;
+                    }
+                    self.accept_symbol(";");
+                    return Ok(());
+                }
+                _ => self.statement()?,
+            }
+        }
+    }
+
+    fn block_follows(&self) -> bool {
+        matches!(
+            self.peek(),
+            Tok::Eof | Tok::Keyword("end" | "else" | "elseif" | "until")
+        )
+    }
+
+    fn statement(&mut self) -> Result<(), SyntaxError> {
+        match self.peek().clone() {
+            Tok::Symbol(";") => {
+                self.next();
+            }
+            Tok::Symbol("::") => {
+                self.next();
+                self.expect_name()?;
+                self.expect_symbol("::")?;
+            }
+            Tok::Keyword("break") => {
+                self.next();
+            }
+            Tok::Keyword("goto") => {
+                self.next();
+                self.expect_name()?;
+            }
+            Tok::Keyword("do") => {
+                self.next();
+                self.scoped_block()?;
+                self.expect_keyword("end")?;
+            }
+            Tok::Keyword("while") => {
+                self.next();
+                self.expression()?;
+                self.expect_keyword("do")?;
+                self.scoped_block()?;
+                self.expect_keyword("end")?;
+            }
+            Tok::Keyword("repeat") => {
+                self.next();
+                // The condition can see the body's locals.
+                self.scopes.push(HashSet::new());
+                let result = self.block().and_then(|_| {
+                    self.expect_keyword("until")?;
+                    self.expression()
+                });
+                self.scopes.pop();
+                result?;
+            }
+            Tok::Keyword("if") => {
+                self.next();
+                self.expression()?;
+                self.expect_keyword("then")?;
+                self.scoped_block()?;
+                while self.accept_keyword("elseif") {
+                    self.expression()?;
+                    self.expect_keyword("then")?;
+                    self.scoped_block()?;
+                }
+                if self.accept_keyword("else") {
+                    self.scoped_block()?;
+                }
+                self.expect_keyword("end")?;
+            }
+            Tok::Keyword("for") => {
+                self.next();
+                self.for_statement()?;
+            }
+            Tok::Keyword("function") => {
+                self.next();
+                self.function_statement()?;
+            }
+            Tok::Keyword("local") => {
+                self.next();
+                if self.accept_keyword("function") {
+                    // The name is in scope inside the body, so local functions can recurse.
+                    let name = self.expect_name()?;
+                    self.declare(name);
+                    self.function_body(false)?;
+                } else {
+                    self.local_statement()?;
+                }
+            }
+            _ => self.expression_statement()?,
+        }
+        Ok(())
+    }
+
+    fn for_statement(&mut self) -> Result<(), SyntaxError> {
+        let mut names = vec![self.expect_name()?];
+        if self.accept_symbol("=") {
+            self.expression()?;
+            self.expect_symbol(",")?;
+            self.expression()?;
+            if self.accept_symbol(",") {
+                self.expression()?;
+            }
+        } else {
+            while self.accept_symbol(",") {
+                names.push(self.expect_name()?);
+            }
+            self.expect_keyword("in")?;
+            self.expression_list()?;
+        }
+        self.expect_keyword("do")?;
+
+        self.scopes.push(names.into_iter().collect());
+        let result = self.block();
+        self.scopes.pop();
+        result?;
+        self.expect_keyword("end")
+    }
+
+    /// `function a.b.c:m(...)`: a write to `a` without dots, a read of it with them.
+    fn function_statement(&mut self) -> Result<(), SyntaxError> {
+        let line = self.line();
+        let name = self.expect_name()?;
+        let mut field = None;
+        let mut method = false;
+        while self.is_symbol(".") || self.is_symbol(":") {
+            method = self.is_symbol(":");
+            self.next();
+            let key = self.expect_name()?;
+            field.get_or_insert(key);
+            if method {
+                break;
+            }
+        }
+        match field {
+            None => self.global(name, line, Access::Write, None),
+            Some(field) => self.global(name, line, Access::Read, Some(field)),
+        }
+        self.function_body(method)
+    }
+
+    fn local_statement(&mut self) -> Result<(), SyntaxError> {
+        let mut names = Vec::new();
+        loop {
+            names.push(self.expect_name()?);
+            if self.is_symbol("<") {
+                let line = self.line();
+                self.next();
+                let attrib = self.expect_name()?;
+                self.expect_symbol(">")?;
+                self.resolved.attribs.push((line, attrib));
+            }
+            if !self.accept_symbol(",") {
+                break;
+            }
+        }
+        // `local x = x` reads the outer `x`, so declare after the values.
+        if self.accept_symbol("=") {
+            self.expression_list()?;
+        }
+        for name in names {
+            self.declare(name);
+        }
+        Ok(())
+    }
+
+    fn expression_statement(&mut self) -> Result<(), SyntaxError> {
+        let first = self.suffixed_expression()?;
+        if !(self.is_symbol("=") || self.is_symbol(",")) {
+            return match first {
+                Suffixed::Call => Ok(()),
+                _ => self.error("a function call or assignment"),
+            };
+        }
+
+        let mut targets = vec![first];
+        while self.accept_symbol(",") {
+            targets.push(self.suffixed_expression()?);
+        }
+        self.expect_symbol("=")?;
+        self.expression_list()?;
+
+        for target in targets {
+            match target {
+                Suffixed::Name(name, line) => self.global(name, line, Access::Write, None),
+                Suffixed::Indexed => {}
+                Suffixed::Call => {
+                    return Err(SyntaxError {
+                        line: self.line(),
+                        message: "cannot assign to a function call".to_string(),
+                    })
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn function_body(&mut self, method: bool) -> Result<(), SyntaxError> {
+        let mut params: HashSet<String> = HashSet::new();
+        if method {
+            params.insert("self".to_string());
+        }
+        self.expect_symbol("(")?;
+        if !self.is_symbol(")") {
+            loop {
+                if self.accept_symbol("...") {
+                    break;
+                }
+                params.insert(self.expect_name()?);
+                if !self.accept_symbol(",") {
+                    break;
+                }
+            }
+        }
+        self.expect_symbol(")")?;
+
+        self.scopes.push(params);
+        let result = self.block();
+        self.scopes.pop();
+        result?;
+        self.expect_keyword("end")
+    }
+
+    fn expression_list(&mut self) -> Result<(), SyntaxError> {
+        self.expression()?;
+        while self.accept_symbol(",") {
+            self.expression()?;
+        }
+        Ok(())
+    }
+
+    /// Operator precedence doesn't change which names are used, so operands are parsed flat.
+    fn expression(&mut self) -> Result<(), SyntaxError> {
+        loop {
+            while matches!(
+                self.peek(),
+                Tok::Keyword("not") | Tok::Symbol("-" | "#" | "~")
+            ) {
+                self.next();
+            }
+            self.simple_expression()?;
+
+            let binary = matches!(
+                self.peek(),
+                Tok::Keyword("and" | "or")
+                    | Tok::Symbol(
+                        "+" | "-"
+                            | "*"
+                            | "/"
+                            | "//"
+                            | "%"
+                            | "^"
+                            | ".."
+                            | "=="
+                            | "~="
+                            | "<"
+                            | "<="
+                            | ">"
+                            | ">="
+                            | "&"
+                            | "|"
+                            | "~"
+                            | "<<"
+                            | ">>"
+                    )
+            );
+            if !binary {
+                return Ok(());
+            }
+            self.next();
+        }
+    }
+
+    fn simple_expression(&mut self) -> Result<(), SyntaxError> {
+        match self.peek() {
+            Tok::Number | Tok::String(_) | Tok::Keyword("nil" | "true" | "false") => {
+                self.next();
+                Ok(())
+            }
+            Tok::Symbol("...") => {
+                self.next();
+                Ok(())
+            }
+            Tok::Symbol("{") => self.table_constructor(),
+            Tok::Keyword("function") => {
+                self.next();
+                self.function_body(false)
+            }
+            _ => {
+                if let Suffixed::Name(name, line) = self.suffixed_expression()? {
+                    self.global(name, line, Access::Read, None);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn primary_expression(&mut self) -> Result<Suffixed, SyntaxError> {
+        match self.peek().clone() {
+            Tok::Name(name) => {
+                let line = self.line();
+                self.next();
+                Ok(Suffixed::Name(name, line))
+            }
+            Tok::Symbol("(") => {
+                self.next();
+                self.expression()?;
+                self.expect_symbol(")")?;
+                // A parenthesized call is a value, not a statement.
+                Ok(Suffixed::Indexed)
+            }
+            _ => self.error("an expression"),
+        }
+    }
+
+    fn suffixed_expression(&mut self) -> Result<Suffixed, SyntaxError> {
+        let mut current = self.primary_expression()?;
+        loop {
+            let kind = match self.peek() {
+                Tok::Symbol(".") | Tok::Symbol("[") => Suffixed::Indexed,
+                Tok::Symbol(":") | Tok::Symbol("(") | Tok::Symbol("{") | Tok::String(_) => {
+                    Suffixed::Call
+                }
+                _ => return Ok(current),
+            };
+
+            // A name with a suffix is read. Note the field for `io.open` style lookups.
+            if let Suffixed::Name(name, line) = current {
+                if name == "require" && !self.is_local(&name) {
+                    let module = match (self.peek(), self.peek_at(1), self.peek_at(2)) {
+                        (Tok::String(module), _, _) => Some(module.clone()),
+                        (Tok::Symbol("("), Tok::String(module), Tok::Symbol(")")) => {
+                            Some(module.clone())
+                        }
+                        _ => None,
+                    };
+                    self.resolved
+                        .requires
+                        .extend(module.map(|module| (line, module)));
+                }
+                let field = match (self.peek(), self.peek_at(1)) {
+                    (Tok::Symbol("." | ":"), Tok::Name(field)) => Some(field.clone()),
+                    _ => None,
+                };
+                self.global(name, line, Access::Read, field);
+            }
+
+            if self.accept_symbol(".") {
+                self.expect_name()?;
+            } else if self.accept_symbol("[") {
+                self.expression()?;
+                self.expect_symbol("]")?;
+            } else if self.accept_symbol(":") {
+                self.expect_name()?;
+                self.call_arguments()?;
+            } else {
+                self.call_arguments()?;
+            }
+            current = kind;
+        }
+    }
+
+    fn call_arguments(&mut self) -> Result<(), SyntaxError> {
+        match self.peek() {
+            Tok::String(_) => {
+                self.next();
+                Ok(())
+            }
+            Tok::Symbol("{") => self.table_constructor(),
+            Tok::Symbol("(") => {
+                self.next();
+                if !self.is_symbol(")") {
+                    self.expression_list()?;
+                }
+                self.expect_symbol(")")
+            }
+            _ => self.error("function arguments"),
+        }
+    }
+
+    fn table_constructor(&mut self) -> Result<(), SyntaxError> {
+        self.expect_symbol("{")?;
+        while !self.is_symbol("}") {
+            if self.accept_symbol("[") {
+                self.expression()?;
+                self.expect_symbol("]")?;
+                self.expect_symbol("=")?;
+                self.expression()?;
+            } else if matches!(self.peek(), Tok::Name(_))
+                && matches!(self.peek_at(1), Tok::Symbol("="))
+            {
+                self.next();
+                self.next();
+                self.expression()?;
+            } else {
+                self.expression()?;
+            }
+            if !(self.accept_symbol(",") || self.accept_symbol(";")) {
+                break;
+            }
+        }
+        self.expect_symbol("}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn accesses(source: &str) -> Vec<(String, usize, Access)> {
+        resolve(source)
+            .unwrap()
+            .globals
+            .into_iter()
+            .map(|g| (g.name, g.line, g.access))
+            .collect()
+    }
+
+    #[test]
+    fn test_resolve_scopes() {
+        let source = r#"local json = require("rapidjson")
+function GetControls(props)
+  local list = {}
+  for i, name in ipairs(props) do
+    list[i] = { Name = name, Count = Count }
+  end
+  return list
+end
+local function helper(x) return helper, x, y end
+if Controls then
+  local t = Timer.New()
+  t.EventHandler = function(timer) print(timer, t) end
+  typo = TcpSocekt.New()
+end
+local z = z
+"#;
+        assert_eq!(
+            accesses(source),
+            [
+                ("require".to_string(), 1, Access::Read),
+                ("GetControls".to_string(), 2, Access::Write),
+                ("ipairs".to_string(), 4, Access::Read),
+                ("Count".to_string(), 5, Access::Read),
+                ("y".to_string(), 9, Access::Read),
+                ("Controls".to_string(), 10, Access::Read),
+                ("Timer".to_string(), 11, Access::Read),
+                ("print".to_string(), 12, Access::Read),
+                ("TcpSocekt".to_string(), 13, Access::Read),
+                ("typo".to_string(), 13, Access::Write),
+                ("z".to_string(), 15, Access::Read),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resolve_fields_and_methods() {
+        let resolved =
+            resolve("local s = io.open('x')\nfunction Plugin.Helper:Run() return self end\n")
+                .unwrap();
+        assert_eq!(resolved.globals[0].field.as_deref(), Some("open"));
+        assert_eq!(resolved.globals[1].name, "Plugin");
+        assert_eq!(resolved.globals[1].access, Access::Read);
+        assert_eq!(resolved.globals.len(), 2);
+    }
+
+    #[test]
+    fn test_resolve_repeat_and_attribs() {
+        let resolved = resolve(
+            "repeat local done <const> = true until done\nlocal f <close> = nil\ngoto skip\n::skip::\n",
+        )
+        .unwrap();
+        assert!(resolved.globals.is_empty());
+        assert_eq!(
+            resolved.attribs,
+            [(1, "const".to_string()), (2, "close".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_resolve_requires() {
+        let resolved = resolve(
+            "local a = require(\"rapidjson\")\nlocal b = require 'json'\nlocal c = require(name)\n",
+        )
+        .unwrap();
+        assert_eq!(
+            resolved.requires,
+            [(1, "rapidjson".to_string()), (2, "json".to_string())]
+        );
+        assert!(resolve("local require = f\nrequire('x')\n")
+            .unwrap()
+            .requires
+            .is_empty());
+    }
+
+    #[test]
+    fn test_resolve_syntax_error() {
+        let err = resolve("local x = 1\nif x then\n  print(x)\n").unwrap_err();
+        assert_eq!(err.line, 4);
+        assert!(err.message.contains("`end`"));
+        assert_eq!(resolve("x.y\n").unwrap_err().line, 2);
+    }
+}
@@ -0,0 +1,152 @@
+use include_dir::Dir;
+use mlua::{Function, Lua, LuaSerdeExt, Table, Value};
+use serde_json::{Map, Value as JsonValue};
+
+use super::{
+    designer::{load_definitions, print_to_stderr, rectified_props, seed_design_time, to_json},
+    plugin::{callback, load_plugin},
+};
+use crate::assets::RUNTIME_EMULATOR;
+
+/// Start the plugin the way a Core does: `Properties` and `Controls` built from the plugin's own
+/// `GetProperties`, `RectifyProperties` and `GetControls(props)`, with `values` over the property
+/// defaults, then the
+/// whole plugin run. `Timer` and the clock are emulated, see `assets/emulator/runtime.lua`, and
+/// `print` goes to stderr.
+pub fn start_runtime(
+    definitions: &Dir,
+    source: &str,
+    chunk: &str,
+    values: &Map<String, JsonValue>,
+) -> mlua::Result<Lua> {
+    let (properties, controls) = declarations(definitions, source, chunk, values)?;
+
+    let lua = Lua::new();
+    load_definitions(&lua, definitions);
+    print_to_stderr(&lua)?;
+    {
+        let setup: Function = lua
+            .load(RUNTIME_EMULATOR)
+            .set_name("=qplug runtime")
+            .eval()?;
+        setup.call::<_, ()>((lua.to_value(&properties)?, lua.to_value(&controls)?))?;
+    }
+    load_plugin(&lua, source, chunk)?;
+    Ok(lua)
+}
+
+/// The plugin's properties and controls, worked out at design time in a VM of their own.
+fn declarations(
+    definitions: &Dir,
+    source: &str,
+    chunk: &str,
+    values: &Map<String, JsonValue>,
+) -> mlua::Result<(JsonValue, JsonValue)> {
+    let lua = Lua::new();
+    seed_design_time(&lua, definitions)?;
+    load_plugin(&lua, source, chunk)?;
+
+    let list: Option<Table> = match callback(&lua, "GetProperties")? {
+        Some(get_properties) => get_properties.call(())?,
+        None => None,
+    };
+    let props = rectified_props(&lua, list.as_ref(), values)?;
+    let controls = match callback(&lua, "GetControls")? {
+        Some(get_controls) => to_json(&get_controls.call::<_, Value>(props.clone())?),
+        None => JsonValue::Array(Vec::new()),
+    };
+    let properties = to_json(&Value::Table(props));
+    Ok((properties, controls))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::assets::DEFINITIONS_DIR;
+
+    const PLUGIN: &str = r#"
+        function GetProperties()
+            return { { Name = "Poll Interval", Type = "integer", Value = 5 } }
+        end
+        function GetControls(props)
+            return {
+                { Name = "Gain", ControlType = "Knob", Min = -100, Max = 20, Count = 2 },
+                { Name = "Mute", ControlType = "Button", ButtonType = "Toggle" },
+                { Name = "Status", ControlType = "Indicator", IndicatorType = "Text" },
+                { Name = "Polls", ControlType = "Text" },
+            }
+        end
+        if Controls then
+            local polls = 0
+            Controls.Mute.EventHandler = function(ctl)
+                Controls.Status.String = ctl.Boolean and "Muted" or "Live"
+            end
+            local poll = Timer.New()
+            poll.EventHandler = function()
+                polls = polls + 1
+                Controls.Polls.String = tostring(polls)
+            end
+            poll:Start(Properties["Poll Interval"].Value)
+            Timer.CallAfter(function() Controls.Status.String = "Ready" end, 1)
+        end
+    "#;
+
+    #[test]
+    fn test_start_runtime() {
+        let values = json!({ "Poll Interval": 2 });
+        let lua = start_runtime(
+            &DEFINITIONS_DIR,
+            PLUGIN,
+            "plugin",
+            values.as_object().unwrap(),
+        )
+        .unwrap();
+        let eval = |code: &str| -> String { lua.load(code).eval().unwrap() };
+
+        assert_eq!(eval("return Controls.Gain[2].String"), "-100");
+        assert_eq!(eval("return tostring(Controls.Gain[1].Position)"), "0.0");
+        lua.load("Controls.Gain[1].Value = 20").exec().unwrap();
+        assert_eq!(eval("return tostring(Controls.Gain[1].Position)"), "1.0");
+
+        assert_eq!(eval("return Controls.Status.String"), "");
+        lua.load("qplug.advance(1)").exec().unwrap();
+        assert_eq!(eval("return Controls.Status.String"), "Ready");
+
+        lua.load("qplug.press('Mute')").exec().unwrap();
+        assert_eq!(eval("return Controls.Status.String"), "Muted");
+        lua.load("Controls.Mute.Boolean = false").exec().unwrap();
+        assert_eq!(eval("return Controls.Status.String"), "Muted");
+
+        lua.load("qplug.advance(5)").exec().unwrap();
+        assert_eq!(eval("return Controls.Polls.String"), "3");
+        assert_eq!(eval("return tostring(os.clock())"), "6");
+        assert_eq!(
+            eval("return os.date('!%Y-%m-%d %H:%M:%S')"),
+            "2024-01-01 00:00:06"
+        );
+    }
+
+    #[test]
+    fn test_unknown_property() {
+        let values = json!({ "Poll Intervall": 2 });
+        let err = start_runtime(
+            &DEFINITIONS_DIR,
+            PLUGIN,
+            "plugin",
+            values.as_object().unwrap(),
+        )
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("there's no property named `Poll Intervall`"));
+    }
+
+    #[test]
+    fn test_unknown_control() {
+        let lua = start_runtime(&DEFINITIONS_DIR, PLUGIN, "plugin", &Map::new()).unwrap();
+        let err = lua.load("qplug.set('Gian', 1)").exec().unwrap_err();
+        assert!(err.to_string().contains("no control named \"Gian\""));
+    }
+}
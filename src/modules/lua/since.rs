@@ -0,0 +1,368 @@
+use std::{cmp::Ordering, fmt, str::FromStr};
+
+use regex::Regex;
+
+use super::decompile::{close_long_bracket, long_bracket};
+
+const SINCE_TAG: &str = "---@since";
+
+/// A Q-SYS Designer release, e.g. `9.4` or `9.10.1`. Missing parts count as zero.
+#[derive(Debug, Clone, Eq)]
+pub struct QsysVersion(Vec<u32>);
+
+impl FromStr for QsysVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Result<Vec<u32>, _> = s.trim().split('.').map(str::parse).collect();
+        match parts {
+            Ok(parts) if !parts.is_empty() => Ok(QsysVersion(parts)),
+            _ => Err(format!(
+                "`{}` is not a Q-SYS version. Expected something like \"9.4\".",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for QsysVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts: Vec<String> = self.0.iter().map(u32::to_string).collect();
+        write!(f, "{}", parts.join("."))
+    }
+}
+
+impl Ord for QsysVersion {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let len = self.0.len().max(other.0.len());
+        let part = |v: &QsysVersion, i: usize| v.0.get(i).copied().unwrap_or(0);
+        (0..len)
+            .map(|i| part(self, i).cmp(&part(other, i)))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for QsysVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for QsysVersion {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+/// An API the definitions tag with `---@since <version>`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaggedApi {
+    /// `WebSocket`, `rapidjson.SchemaDocument`, `TcpSocket:Write`, or a module name for
+    /// `---@module` blocks.
+    pub name: String,
+    pub since: QsysVersion,
+    /// Whether `name` is a module, used through `require`.
+    pub module: bool,
+}
+
+/// Collect the `---@since` tags in a definitions file. A tag applies to the declaration that ends
+/// its doc comment: `function A.b()`, `A = {}`, or the `local` following `---@module 'name'`.
+pub fn tagged_apis(source: &str) -> Vec<TaggedApi> {
+    let declaration =
+        Regex::new(r"^(?:local\s+)?(?:function\s+([A-Za-z_][\w.:]*)|([A-Za-z_][\w.]*)\s*=)")
+            .unwrap();
+    let module = Regex::new(r#"^---@module\s+["']([^"']+)["']"#).unwrap();
+
+    let mut apis = Vec::new();
+    let mut since: Option<QsysVersion> = None;
+    let mut module_name: Option<String> = None;
+    for line in source.lines() {
+        let line = line.trim();
+        if let Some(version) = line.strip_prefix(SINCE_TAG) {
+            since = version.trim().parse().ok();
+            continue;
+        }
+        if let Some(cap) = module.captures(line) {
+            module_name = Some(cap[1].to_string());
+            continue;
+        }
+        if line.starts_with("--") {
+            continue;
+        }
+        if line.is_empty() {
+            // Tags never carry over a blank line.
+            since = None;
+            module_name = None;
+            continue;
+        }
+
+        if let Some(since) = since.take() {
+            let api = match module_name.take() {
+                Some(name) => Some(TaggedApi {
+                    name,
+                    since,
+                    module: true,
+                }),
+                None => declaration.captures(line).map(|cap| TaggedApi {
+                    name: cap.get(1).or(cap.get(2)).unwrap().as_str().to_string(),
+                    since,
+                    module: false,
+                }),
+            };
+            apis.extend(api);
+        }
+        module_name = None;
+    }
+    apis
+}
+
+/// Mark every API tagged as newer than `target` deprecated, so the language server strikes it
+/// through and warns wherever it's used.
+pub fn annotate_for_target(source: &str, target: &QsysVersion) -> String {
+    let mut annotated = String::with_capacity(source.len());
+    for line in source.split_inclusive('\n') {
+        annotated.push_str(line);
+        let Some(version) = line.trim().strip_prefix(SINCE_TAG) else {
+            continue;
+        };
+        let Ok(since) = version.trim().parse::<QsysVersion>() else {
+            continue;
+        };
+        if since > *target {
+            if !line.ends_with('\n') {
+                annotated.push('\n');
+            }
+            annotated.push_str(&format!(
+                "---@deprecated Added in Q-SYS {}. This project targets {}.\n",
+                since, target
+            ));
+        }
+    }
+    annotated
+}
+
+/// A use of an API that needs a newer Q-SYS than the project targets.
+#[derive(Debug, PartialEq)]
+pub struct NewerApiUse<'a> {
+    /// 1-based line in the searched source.
+    pub line: usize,
+    pub api: &'a TaggedApi,
+}
+
+/// Find uses of `apis` newer than `target` in plugin code. Only names spelled out in full are
+/// found: `WebSocket.New()` and `rapidjson.SchemaDocument(...)` are, a method called on an instance
+/// (`ws:Connect()`) isn't. Only tagged APIs are checked, and the bundled definitions tag few of
+/// them, so finding nothing doesn't mean the plugin runs on `target`; see `coverage_note`.
+pub fn newer_api_uses<'a>(
+    source: &str,
+    apis: &'a [TaggedApi],
+    target: &QsysVersion,
+) -> Vec<NewerApiUse<'a>> {
+    let code = mask_comments_and_strings(source);
+    let mut uses = Vec::new();
+    for api in apis.iter().filter(|api| api.since > *target) {
+        let pattern = if api.module {
+            format!(r#"\brequire\s*\(?\s*["']{}["']"#, regex::escape(&api.name))
+        } else {
+            let parts: Vec<String> = api.name.split(['.', ':']).map(regex::escape).collect();
+            format!(r"(?m)(?:^|[^\w.:])({})\b", parts.join(r"\s*[.:]\s*"))
+        };
+        // Module names are matched inside the string passed to `require`, so search the source
+        // with strings left in and comments masked separately.
+        let haystack = if api.module { source } else { &code };
+        let regex = Regex::new(&pattern).unwrap();
+        for cap in regex.captures_iter(haystack) {
+            let start = cap.get(1).unwrap_or(cap.get(0).unwrap()).start();
+            if api.module && is_masked(&code, start) {
+                continue;
+            }
+            let line = haystack[..start].matches('\n').count() + 1;
+            uses.push(NewerApiUse { line, api });
+        }
+    }
+    uses.sort_by_key(|found| found.line);
+    uses
+}
+
+/// Said after checking a plugin against `target`, so a clean check isn't taken to mean the plugin
+/// runs there: only the tagged `apis` are checked.
+pub fn coverage_note(apis: &[TaggedApi], target: &QsysVersion) -> String {
+    format!(
+        "note: only the {} API{} tagged `---@since` in the definitions {} checked against Q-SYS {}. \
+         Anything else the plugin uses isn't, so no warning doesn't mean it runs on {}.",
+        apis.len(),
+        if apis.len() == 1 { "" } else { "s" },
+        if apis.len() == 1 { "is" } else { "are" },
+        target,
+        target
+    )
+}
+
+/// Whether `index` was blanked by `mask_comments_and_strings`, i.e. sits in a comment.
+fn is_masked(code: &str, index: usize) -> bool {
+    code.as_bytes().get(index) == Some(&b' ')
+}
+
+/// Replace comments and string contents with spaces, keeping newlines so lines still line up.
+pub fn mask_comments_and_strings(source: &str) -> String {
+    let bytes = source.as_bytes();
+    let mut masked = bytes.to_vec();
+    let blank = |masked: &mut Vec<u8>, from: usize, to: usize| {
+        for byte in &mut masked[from..to] {
+            if *byte != b'\n' {
+                *byte = b' ';
+            }
+        }
+    };
+
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                let end = match long_bracket(bytes, i + 2) {
+                    Some(level) => close_long_bracket(bytes, i + 2, level),
+                    None => bytes[i..]
+                        .iter()
+                        .position(|b| *b == b'\n')
+                        .map_or(bytes.len(), |p| i + p),
+                };
+                blank(&mut masked, i, end);
+                i = end;
+            }
+            b'[' if long_bracket(bytes, i).is_some() => {
+                let level = long_bracket(bytes, i).unwrap();
+                let end = close_long_bracket(bytes, i, level);
+                blank(
+                    &mut masked,
+                    i + level + 2,
+                    end.saturating_sub(level + 2).max(i + level + 2),
+                );
+                i = end;
+            }
+            quote @ (b'"' | b'\'') => {
+                let mut end = i + 1;
+                while end < bytes.len() && bytes[end] != quote && bytes[end] != b'\n' {
+                    end += if bytes[end] == b'\\' { 2 } else { 1 };
+                }
+                let end = end.min(bytes.len());
+                blank(&mut masked, i + 1, end);
+                i = end + 1;
+            }
+            _ => i += 1,
+        }
+    }
+    String::from_utf8_lossy(&masked).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEFS: &str = r#"---@meta
+
+---Some module.
+---@module 'newjson'
+---@since 9.8
+local newjson
+
+---@param value any
+---@since 9.6
+function newjson.encode(value) end
+
+function newjson.decode(value) end
+
+---@class Socket
+---@since 9.4
+Socket = {}
+
+---@since 9.10
+function Socket:Ping() end
+"#;
+
+    fn version(s: &str) -> QsysVersion {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_qsys_version() {
+        assert!(version("9.10") > version("9.4"));
+        assert!(version("9.4.1") > version("9.4"));
+        assert_eq!(version("9.4"), version("9.4.0"));
+        assert_eq!(version("9.4").to_string(), "9.4");
+        assert!("nine".parse::<QsysVersion>().is_err());
+        assert!("9.".parse::<QsysVersion>().is_err());
+    }
+
+    #[test]
+    fn test_tagged_apis() {
+        let names: Vec<(String, String, bool)> = tagged_apis(DEFS)
+            .into_iter()
+            .map(|api| (api.name, api.since.to_string(), api.module))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("newjson".to_string(), "9.8".to_string(), true),
+                ("newjson.encode".to_string(), "9.6".to_string(), false),
+                ("Socket".to_string(), "9.4".to_string(), false),
+                ("Socket:Ping".to_string(), "9.10".to_string(), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_annotate_for_target() {
+        let annotated = annotate_for_target(DEFS, &version("9.6"));
+        assert!(annotated.contains(
+            "---@since 9.8\n---@deprecated Added in Q-SYS 9.8. This project targets 9.6.\nlocal newjson"
+        ));
+        assert!(annotated.contains("---@since 9.6\nfunction newjson.encode"));
+        assert!(annotated.contains("---@since 9.4\nSocket = {}"));
+        assert_eq!(annotated.matches("@deprecated").count(), 2);
+    }
+
+    #[test]
+    fn test_newer_api_uses() {
+        let apis = tagged_apis(DEFS);
+        let plugin = r#"local json = require("newjson")
+-- Socket.New() in a comment
+local s = Socket.New()
+print("Socket:Ping()")
+s:Ping()
+Socket:Ping()
+local x = json.encode({}) .. MySocket.New()
+"#;
+        let uses: Vec<(usize, &str)> = newer_api_uses(plugin, &apis, &version("9.2"))
+            .into_iter()
+            .map(|found| (found.line, found.api.name.as_str()))
+            .collect();
+        assert_eq!(
+            uses,
+            [
+                (1, "newjson"),
+                (3, "Socket"),
+                (6, "Socket"),
+                (6, "Socket:Ping"),
+            ]
+        );
+        assert!(newer_api_uses(plugin, &apis, &version("9.10")).is_empty());
+    }
+
+    #[test]
+    fn test_coverage_note() {
+        let apis = tagged_apis(DEFS);
+        let note = coverage_note(&apis, &version("9.2"));
+        assert!(note.starts_with(&format!(
+            "note: only the {} APIs tagged `---@since` in the definitions are checked against Q-SYS 9.2.",
+            apis.len()
+        )));
+        assert!(coverage_note(&apis[..1], &version("9.2")).contains("1 API tagged"));
+    }
+
+    #[test]
+    fn test_mask_comments_and_strings() {
+        let masked = mask_comments_and_strings("a = \"b\" -- c\n--[[ d\ne ]] f = [[g]]\n");
+        assert_eq!(masked, "a = \" \"     \n      \n     f = [[ ]]\n");
+    }
+}
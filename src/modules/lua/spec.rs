@@ -0,0 +1,172 @@
+use std::{fs, path::Path};
+
+use mlua::{Function, Lua, Table, Value};
+
+use crate::assets::SPEC_HELPERS;
+
+/// How one `it` went.
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    Passed,
+    /// The error the test raised, with its location.
+    Failed(String),
+    /// An `it` without a function, or a `pending`.
+    Pending,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct TestResult {
+    /// The names of the `describe`s around the test and its own, joined by spaces.
+    pub name: String,
+    /// Where the `it` is in the spec file.
+    pub line: usize,
+    pub outcome: Outcome,
+}
+
+/// Let `require` load the plugin's own modules from `plugin_path` as chunks of their own, the way
+/// `compile` finds them, so a spec can test a module that returns a table. They're named after
+/// their path from `root_path`, so errors in them point at the file.
+pub fn add_module_searcher(lua: &Lua, plugin_path: &Path, root_path: &Path) -> mlua::Result<()> {
+    let plugin_path = plugin_path.to_path_buf();
+    let root_path = root_path.to_path_buf();
+    let searcher = lua.create_function(move |lua, name: String| {
+        let path = plugin_path.join(format!("{}.lua", name.replace('.', "/")));
+        let Ok(source) = fs::read_to_string(&path) else {
+            return Ok(Value::String(
+                lua.create_string(format!("\n\tno file '{}'", path.display()))?,
+            ));
+        };
+        let shown = path.strip_prefix(&root_path).unwrap_or(&path);
+        let loader = lua
+            .load(source)
+            .set_name(format!("@{}", shown.display()))
+            .into_function()?;
+        Ok(Value::Function(loader))
+    })?;
+
+    // Ahead of the standard searchers, which look in the working directory.
+    let searchers: Table = lua.globals().get::<_, Table>("package")?.get("searchers")?;
+    lua.load("local searchers, searcher = ...; table.insert(searchers, 2, searcher)")
+        .call::<_, ()>((searchers, searcher))
+}
+
+/// Run the spec `source`, named `chunk`, in `lua` with the `describe`/`it`/`assert` helpers from
+/// `assets/emulator/spec.lua`. Errors if the spec itself fails to load or to declare its tests;
+/// a failing test is an `Outcome::Failed`.
+pub fn run_spec(lua: &Lua, source: &str, chunk: &str) -> mlua::Result<Vec<TestResult>> {
+    let run: Function = lua.load(SPEC_HELPERS).set_name("=qplug spec").eval()?;
+    let spec = lua.load(source).set_name(chunk).into_function()?;
+    let line_at = lua.create_function(|lua, level: usize| {
+        Ok(lua.inspect_stack(level).map(|frame| frame.curr_line()))
+    })?;
+    let results: Table = run.call((spec, line_at))?;
+
+    results
+        .sequence_values::<Table>()
+        .map(|result| {
+            let result = result?;
+            let outcome = match result.get::<_, String>("status")?.as_str() {
+                "passed" => Outcome::Passed,
+                "pending" => Outcome::Pending,
+                _ => Outcome::Failed(result.get("message")?),
+            };
+            Ok(TestResult {
+                name: result.get("name")?,
+                line: result.get("line")?,
+                outcome,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPEC: &str = r#"
+        describe("parse", function()
+            local frames
+            before_each(function() frames = {} end)
+
+            it("reads a frame", function()
+                table.insert(frames, 1)
+                assert.are.equal(1, #frames)
+                assert.same({ a = { 1 } }, { a = { 1 } })
+            end)
+
+            it("starts from scratch", function()
+                assert.is_true(#frames == 0)
+                assert.is_not.Nil(frames)
+                assert.has_error(function() error("bad frame") end, "bad frame")
+                assert(true)
+            end)
+
+            it("rejects bad checksums", function()
+                assert.equals(false, true)
+            end)
+
+            pending("handles partial frames")
+        end)
+    "#;
+
+    #[test]
+    fn test_run_spec() {
+        let lua = Lua::new();
+        let results = run_spec(&lua, SPEC, "@tests/parse_spec.lua").unwrap();
+        let summary: Vec<_> = results
+            .iter()
+            .map(|result| (result.name.as_str(), result.line))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("parse reads a frame", 6),
+                ("parse starts from scratch", 12),
+                ("parse rejects bad checksums", 19),
+                ("parse handles partial frames", 23),
+            ]
+        );
+        assert_eq!(results[0].outcome, Outcome::Passed);
+        assert_eq!(results[1].outcome, Outcome::Passed);
+        assert_eq!(
+            results[2].outcome,
+            Outcome::Failed("tests/parse_spec.lua:20: expected false, got true".to_string())
+        );
+        assert_eq!(results[3].outcome, Outcome::Pending);
+    }
+
+    #[test]
+    fn test_spec_load_error() {
+        let lua = Lua::new();
+        let err = run_spec(&lua, "describe('x', function() nil() end)", "@a_spec.lua");
+        assert!(err.unwrap_err().to_string().contains("a_spec.lua:1:"));
+    }
+
+    #[test]
+    fn test_module_searcher() {
+        let dir = tempfile::tempdir().unwrap();
+        let plugin_path = dir.path().join("plugin_src");
+        fs::create_dir_all(plugin_path.join("protocol")).unwrap();
+        fs::write(
+            plugin_path.join("protocol/frame.lua"),
+            "local M = {}\nfunction M.parse(s) return s:upper() end\nfunction M.fail() error('no') end\nreturn M\n",
+        )
+        .unwrap();
+
+        let lua = Lua::new();
+        add_module_searcher(&lua, &plugin_path, dir.path()).unwrap();
+        let parsed: String = lua
+            .load("return require('protocol.frame').parse('ok')")
+            .eval()
+            .unwrap();
+        assert_eq!(parsed, "OK");
+        let err = lua
+            .load("require('protocol.frame').fail()")
+            .exec()
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("plugin_src/protocol/frame.lua:3: no"));
+        assert!(lua.load("require('missing')").exec().is_err());
+    }
+}
@@ -0,0 +1,636 @@
+use std::{collections::HashSet, fmt};
+
+use include_dir::Dir;
+use mlua::{Lua, Value};
+use serde_json::{Map, Value as JsonValue};
+
+use super::{
+    designer::{run_design_time, seed_design_time, to_json, CallbackResult},
+    plugin::{callback, load_plugin},
+};
+
+const PROPERTY_TYPES: [&str; 5] = ["string", "integer", "double", "boolean", "enum"];
+const CONTROL_TYPES: [&str; 4] = ["Button", "Knob", "Indicator", "Text"];
+const PIN_DIRECTIONS: [&str; 2] = ["input", "output"];
+
+/// Each property the sweep varies, with the values it's tried at. The default is always first.
+pub type PropertySpace = Vec<(String, Vec<JsonValue>)>;
+
+/// Something wrong with one callback for one combination of properties.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Problem {
+    /// The callback raised an error.
+    Error { callback: String, message: String },
+    /// It returned something Designer can't use.
+    Malformed { callback: String, message: String },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::Error { callback, message } => write!(f, "{} failed: {}", callback, message),
+            Problem::Malformed { callback, message } => write!(f, "{} {}", callback, message),
+        }
+    }
+}
+
+/// What the plugin's `GetProperties` returns, as JSON. `Null` if it has none.
+pub fn property_list(definitions: &Dir, source: &str, chunk: &str) -> mlua::Result<JsonValue> {
+    let lua = Lua::new();
+    seed_design_time(&lua, definitions)?;
+    load_plugin(&lua, source, chunk)?;
+    let list = match callback(&lua, "GetProperties")? {
+        Some(get_properties) => to_json(&get_properties.call::<_, Value>(())?),
+        None => JsonValue::Null,
+    };
+    Ok(list)
+}
+
+/// The values worth trying for each property in `list`: every enum choice, both booleans, and an
+/// integer's bounds and the values next to them. Doubles get their bounds and strings only their
+/// default. Designer's own `plugin_show_debug` is included, as plugins branch on it too.
+pub fn property_space(list: &JsonValue) -> PropertySpace {
+    let mut space: PropertySpace = list
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|property| {
+            let name = property["Name"].as_str()?;
+            Some((name.to_string(), property_values(property)))
+        })
+        .collect();
+    space.push((
+        "plugin_show_debug".to_string(),
+        vec![JsonValue::Bool(false), JsonValue::Bool(true)],
+    ));
+    space
+}
+
+fn property_values(property: &JsonValue) -> Vec<JsonValue> {
+    let default = &property["Value"];
+    let mut values = vec![default.clone()];
+    match property["Type"].as_str() {
+        Some("enum") => {
+            values.extend(
+                property["Choices"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .cloned(),
+            );
+        }
+        Some("boolean") => {
+            values.push(JsonValue::Bool(!default.as_bool().unwrap_or(false)));
+        }
+        Some("integer") => {
+            if let (Some(min), Some(max)) = (property["Min"].as_i64(), property["Max"].as_i64()) {
+                if default.is_null() {
+                    values.clear();
+                }
+                for value in [min, min + 1, max - 1, max] {
+                    if (min..=max).contains(&value) {
+                        values.push(JsonValue::from(value));
+                    }
+                }
+            }
+        }
+        Some("double") => {
+            if default.is_null() {
+                values.clear();
+            }
+            values.extend([property["Min"].clone(), property["Max"].clone()]);
+        }
+        _ => {}
+    }
+
+    let mut seen = HashSet::new();
+    values.retain(|value| !value.is_null() && seen.insert(value.to_string()));
+    if values.is_empty() {
+        values.push(default.clone());
+    }
+    values
+}
+
+/// The number of combinations in `space`, saturating.
+pub fn combination_count(space: &PropertySpace) -> usize {
+    space.iter().fold(1usize, |total, (_, values)| {
+        total.saturating_mul(values.len())
+    })
+}
+
+/// Up to `limit` combinations of property values. Every one of them when there are few enough,
+/// otherwise the defaults, each value on its own with the rest at their defaults, and then a
+/// spread of mixed combinations. The sample is the same every run.
+pub fn combinations(space: &PropertySpace, limit: usize) -> Vec<Map<String, JsonValue>> {
+    let sizes: Vec<usize> = space.iter().map(|(_, values)| values.len()).collect();
+    let mut picks: Vec<Vec<usize>> = Vec::new();
+
+    if combination_count(space) <= limit {
+        let mut pick = vec![0; sizes.len()];
+        loop {
+            picks.push(pick.clone());
+            // Count up like an odometer, the last property turning fastest.
+            let Some(position) = (0..sizes.len()).rev().find(|&i| pick[i] + 1 < sizes[i]) else {
+                break;
+            };
+            pick[position] += 1;
+            pick[position + 1..].iter_mut().for_each(|index| *index = 0);
+        }
+    } else {
+        let mut seen = HashSet::new();
+        let mut add = |pick: Vec<usize>, picks: &mut Vec<Vec<usize>>| {
+            if picks.len() < limit && seen.insert(pick.clone()) {
+                picks.push(pick);
+            }
+        };
+        add(vec![0; sizes.len()], &mut picks);
+        for (property, &size) in sizes.iter().enumerate() {
+            for value in 1..size {
+                let mut pick = vec![0; sizes.len()];
+                pick[property] = value;
+                add(pick, &mut picks);
+            }
+        }
+        // xorshift64 with a fixed seed, so a failure found once is found again.
+        let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+        for _ in 0..limit * 16 {
+            if picks.len() >= limit {
+                break;
+            }
+            let pick = sizes
+                .iter()
+                .map(|&size| {
+                    state ^= state << 13;
+                    state ^= state >> 7;
+                    state ^= state << 17;
+                    (state % size as u64) as usize
+                })
+                .collect();
+            add(pick, &mut picks);
+        }
+    }
+
+    picks
+        .into_iter()
+        .map(|pick| {
+            space
+                .iter()
+                .zip(pick)
+                .map(|((name, values), index)| (name.clone(), values[index].clone()))
+                .collect()
+        })
+        .collect()
+}
+
+/// Run the design-time callbacks in a fresh VM with `values` and report what went wrong. Errors
+/// only if the plugin doesn't load.
+pub fn check_combination(
+    definitions: &Dir,
+    source: &str,
+    chunk: &str,
+    values: &Map<String, JsonValue>,
+) -> mlua::Result<Vec<Problem>> {
+    let lua = Lua::new();
+    seed_design_time(&lua, definitions)?;
+    load_plugin(&lua, source, chunk)?;
+    Ok(problems(&run_design_time(&lua, values)?))
+}
+
+/// The errors in `results`, and the results that aren't the shape Designer expects.
+pub fn problems(results: &[CallbackResult]) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    let mut controls: Option<HashSet<String>> = None;
+
+    for result in results {
+        let callback = match &result.page {
+            Some(page) => format!("{} ({})", result.callback, page),
+            None => result.callback.to_string(),
+        };
+        let returned = match &result.result {
+            Ok(returned) => returned,
+            Err(message) => {
+                problems.push(Problem::Error {
+                    callback,
+                    message: message.clone(),
+                });
+                continue;
+            }
+        };
+
+        let messages = match result.callback {
+            "GetProperties" => check_properties(returned),
+            "RectifyProperties" => check_table(returned, "the props table"),
+            "GetPages" => check_list(returned, "pages", |page| {
+                require_string(page, "name").into_iter().collect()
+            }),
+            "GetControls" => {
+                controls = Some(control_names(returned));
+                check_controls(returned)
+            }
+            "GetControlLayout" => check_layout(returned, controls.as_ref()),
+            "GetPins" => check_list(returned, "pins", check_pin),
+            "GetComponents" => check_list(returned, "components", |component| {
+                ["Name", "Type"]
+                    .iter()
+                    .filter_map(|key| require_string(component, key))
+                    .collect()
+            }),
+            "GetWiring" => check_wiring(returned),
+            _ => Vec::new(),
+        };
+        problems.extend(messages.into_iter().map(|message| Problem::Malformed {
+            callback: callback.clone(),
+            message,
+        }));
+    }
+    problems
+}
+
+fn check_table(returned: &JsonValue, expected: &str) -> Vec<String> {
+    if returned.is_object() || returned.is_array() {
+        Vec::new()
+    } else {
+        vec![format!(
+            "returned {} instead of {}",
+            describe(returned),
+            expected
+        )]
+    }
+}
+
+/// `returned` should be a list, and each item a table `check_item` is happy with. Problems name
+/// the item they're about.
+fn check_list(
+    returned: &JsonValue,
+    what: &str,
+    check_item: impl Fn(&Map<String, JsonValue>) -> Vec<String>,
+) -> Vec<String> {
+    let Some(items) = list(returned) else {
+        return vec![format!(
+            "returned {} instead of a list of {}",
+            describe(returned),
+            what
+        )];
+    };
+    let mut messages = Vec::new();
+    for (index, item) in items.iter().enumerate() {
+        let label = item["Name"]
+            .as_str()
+            .or(item["name"].as_str())
+            .map(|name| format!("`{}`", name))
+            .unwrap_or_else(|| format!("item {}", index + 1));
+        match item.as_object() {
+            Some(item) => messages.extend(
+                check_item(item)
+                    .into_iter()
+                    .map(|message| format!("returned {}: {}", label, message)),
+            ),
+            None => messages.push(format!("returned {} as {}", label, describe(&items[index]))),
+        }
+    }
+    messages
+}
+
+fn check_properties(returned: &JsonValue) -> Vec<String> {
+    let mut messages = check_list(returned, "properties", |property| {
+        let mut messages: Vec<String> = require_string(property, "Name").into_iter().collect();
+        match property.get("Type").and_then(JsonValue::as_str) {
+            Some(kind) if PROPERTY_TYPES.contains(&kind) => {}
+            _ => messages.push(format!(
+                "Type should be one of {}",
+                PROPERTY_TYPES.join(", ")
+            )),
+        }
+        if property.get("Type").and_then(JsonValue::as_str) == Some("enum") {
+            let choices = property.get("Choices").and_then(list);
+            match choices {
+                Some(choices) if !choices.is_empty() => {
+                    let value = property.get("Value").unwrap_or(&JsonValue::Null);
+                    if !value.is_null() && !choices.contains(value) {
+                        messages.push(format!("Value {} isn't one of its Choices", value));
+                    }
+                }
+                _ => messages.push("an enum needs a list of Choices".to_string()),
+            }
+        }
+        if let (Some(min), Some(max)) = (
+            property.get("Min").and_then(JsonValue::as_f64),
+            property.get("Max").and_then(JsonValue::as_f64),
+        ) {
+            if min > max {
+                messages.push(format!("Min {} is above Max {}", min, max));
+            }
+        }
+        messages
+    });
+    messages.extend(duplicates(returned, "Name"));
+    messages
+}
+
+fn check_controls(returned: &JsonValue) -> Vec<String> {
+    let mut messages = check_list(returned, "controls", |control| {
+        let mut messages: Vec<String> = require_string(control, "Name").into_iter().collect();
+        match control.get("ControlType").and_then(JsonValue::as_str) {
+            Some(kind) if CONTROL_TYPES.contains(&kind) => {}
+            _ => messages.push(format!(
+                "ControlType should be one of {}",
+                CONTROL_TYPES.join(", ")
+            )),
+        }
+        if let Some(count) = control.get("Count") {
+            if count.as_i64().is_none_or(|count| count < 1) {
+                messages.push(format!("Count is {}, it should be at least 1", count));
+            }
+        }
+        messages
+    });
+    messages.extend(duplicates(returned, "Name"));
+    messages
+}
+
+fn check_pin(pin: &Map<String, JsonValue>) -> Vec<String> {
+    let mut messages: Vec<String> = require_string(pin, "Name").into_iter().collect();
+    let direction = pin.get("Direction").and_then(JsonValue::as_str);
+    if !direction
+        .is_some_and(|direction| PIN_DIRECTIONS.contains(&direction.to_lowercase().as_str()))
+    {
+        messages.push("Direction should be input or output".to_string());
+    }
+    messages
+}
+
+/// The names controls are laid out under: `Name`, or `Name 1` to `Name N` with a `Count`.
+fn control_names(returned: &JsonValue) -> HashSet<String> {
+    let mut names = HashSet::new();
+    for control in list(returned).into_iter().flatten() {
+        let Some(name) = control["Name"].as_str() else {
+            continue;
+        };
+        match control["Count"].as_i64() {
+            Some(count) if count > 1 => {
+                names.extend((1..=count).map(|index| format!("{} {}", name, index)))
+            }
+            _ => {
+                names.insert(name.to_string());
+            }
+        }
+    }
+    names
+}
+
+fn check_layout(returned: &JsonValue, controls: Option<&HashSet<String>>) -> Vec<String> {
+    let mut messages = Vec::new();
+    let layout = &returned["layout"];
+    match layout {
+        JsonValue::Object(layout) => {
+            for (name, entry) in layout {
+                if controls.is_some_and(|controls| !controls.contains(name)) {
+                    messages.push(format!("lays out `{}`, which isn't in GetControls", name));
+                }
+                let Some(entry) = entry.as_object() else {
+                    messages.push(format!("lays out `{}` as {}", name, describe(entry)));
+                    continue;
+                };
+                for key in ["Position", "Size"] {
+                    if entry.get(key).is_some_and(|pair| !is_pair(pair)) {
+                        messages.push(format!("`{}`'s {} should be two numbers", name, key));
+                    }
+                }
+            }
+        }
+        // An empty layout converts as an empty list.
+        JsonValue::Array(entries) if entries.is_empty() => {}
+        other => messages.push(format!("returned {} instead of a layout", describe(other))),
+    }
+
+    messages.extend(
+        check_list(&returned["graphics"], "graphics", |graphic| {
+            let mut messages: Vec<String> = require_string(graphic, "Type").into_iter().collect();
+            for key in ["Position", "Size"] {
+                if graphic.get(key).is_some_and(|pair| !is_pair(pair)) {
+                    messages.push(format!("{} should be two numbers", key));
+                }
+            }
+            messages
+        })
+        .into_iter()
+        .map(|message| message.replacen("returned ", "returned graphic ", 1)),
+    );
+    messages
+}
+
+fn check_wiring(returned: &JsonValue) -> Vec<String> {
+    let Some(wires) = list(returned) else {
+        return vec![format!(
+            "returned {} instead of a list of wires",
+            describe(returned)
+        )];
+    };
+    wires
+        .iter()
+        .enumerate()
+        .filter(|(_, wire)| {
+            !list(wire).is_some_and(|ends| ends.len() >= 2 && ends.iter().all(JsonValue::is_string))
+        })
+        .map(|(index, _)| {
+            format!(
+                "returned wire {}: it should list two or more pins",
+                index + 1
+            )
+        })
+        .collect()
+}
+
+fn require_string(item: &Map<String, JsonValue>, key: &str) -> Option<String> {
+    match item.get(key) {
+        Some(JsonValue::String(_)) => None,
+        _ => Some(format!("{} should be a string", key)),
+    }
+}
+
+fn duplicates(returned: &JsonValue, key: &str) -> Vec<String> {
+    let mut seen = HashSet::new();
+    list(returned)
+        .into_iter()
+        .flatten()
+        .filter_map(|item| item[key].as_str())
+        .filter(|name| !seen.insert(*name))
+        .map(|name| format!("returned `{}` more than once", name))
+        .collect()
+}
+
+/// A Lua sequence, which converts as a JSON array. An empty table is one too.
+fn list(value: &JsonValue) -> Option<&Vec<JsonValue>> {
+    value.as_array()
+}
+
+fn is_pair(value: &JsonValue) -> bool {
+    list(value).is_some_and(|pair| pair.len() == 2 && pair.iter().all(JsonValue::is_number))
+}
+
+fn describe(value: &JsonValue) -> &'static str {
+    match value {
+        JsonValue::Null => "nil",
+        JsonValue::Bool(_) => "a boolean",
+        JsonValue::Number(_) => "a number",
+        JsonValue::String(_) => "a string",
+        JsonValue::Array(_) => "a list",
+        JsonValue::Object(_) => "a table",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::assets::DEFINITIONS_DIR;
+
+    const PLUGIN: &str = r#"
+        function GetProperties()
+            return {
+                { Name = "Count", Type = "integer", Value = 2, Min = 0, Max = 8 },
+                { Name = "Debug Print", Type = "enum", Choices = { "None", "All" }, Value = "None" },
+                { Name = "Model", Type = "string", Value = "X1" },
+            }
+        end
+        function GetControls(props)
+            if props["Debug Print"].Value == "All" then
+                return { { Name = "Log", ControlType = "Text" }, { Name = "Log", ControlType = "Text" } }
+            end
+            return { { Name = "Gain", ControlType = "Knob", Count = props.Count.Value } }
+        end
+        function GetControlLayout(props)
+            local layout = {}
+            for i = 1, props.Count.Value do
+                layout["Gain " .. i] = { Position = { 0, i * 36 }, Size = { 36, 36 } }
+            end
+            return layout, {}
+        end
+    "#;
+
+    #[test]
+    fn test_property_space() {
+        let list = property_list(&DEFINITIONS_DIR, PLUGIN, "plugin").unwrap();
+        let space = property_space(&list);
+        assert_eq!(
+            space,
+            [
+                (
+                    "Count".to_string(),
+                    vec![json!(2), json!(0), json!(1), json!(7), json!(8)]
+                ),
+                ("Debug Print".to_string(), vec![json!("None"), json!("All")]),
+                ("Model".to_string(), vec![json!("X1")]),
+                (
+                    "plugin_show_debug".to_string(),
+                    vec![json!(false), json!(true)]
+                ),
+            ]
+        );
+        assert_eq!(combination_count(&space), 20);
+        assert_eq!(property_space(&JsonValue::Null).len(), 1);
+    }
+
+    #[test]
+    fn test_combinations() {
+        let space: PropertySpace = vec![
+            ("A".to_string(), vec![json!(1), json!(2), json!(3)]),
+            ("B".to_string(), vec![json!(true), json!(false)]),
+            ("C".to_string(), vec![json!("x"), json!("y"), json!("z")]),
+        ];
+        let all = combinations(&space, 100);
+        assert_eq!(all.len(), 18);
+        assert_eq!(
+            all[0],
+            *json!({ "A": 1, "B": true, "C": "x" }).as_object().unwrap()
+        );
+        assert_eq!(
+            all[1],
+            *json!({ "A": 1, "B": true, "C": "y" }).as_object().unwrap()
+        );
+        assert_eq!(
+            all[17],
+            *json!({ "A": 3, "B": false, "C": "z" }).as_object().unwrap()
+        );
+
+        let sampled = combinations(&space, 8);
+        assert_eq!(sampled.len(), 8);
+        assert_eq!(sampled[0], all[0]);
+        // Each value on its own comes before the mixed ones.
+        assert_eq!(
+            sampled[1],
+            *json!({ "A": 2, "B": true, "C": "x" }).as_object().unwrap()
+        );
+        assert_eq!(sampled, combinations(&space, 8));
+        let distinct: HashSet<String> = sampled.iter().map(|c| format!("{:?}", c)).collect();
+        assert_eq!(distinct.len(), 8);
+    }
+
+    #[test]
+    fn test_check_combination() {
+        let check = |values: JsonValue| {
+            check_combination(
+                &DEFINITIONS_DIR,
+                PLUGIN,
+                "plugin",
+                values.as_object().unwrap(),
+            )
+            .unwrap()
+            .into_iter()
+            .map(|problem| problem.to_string())
+            .collect::<Vec<_>>()
+        };
+        assert!(check(json!({})).is_empty());
+        assert_eq!(
+            check(json!({ "Count": 1 })),
+            ["GetControlLayout lays out `Gain 1`, which isn't in GetControls"]
+        );
+        assert_eq!(
+            check(json!({ "Count": 0 })),
+            ["GetControls returned `Gain`: Count is 0, it should be at least 1"]
+        );
+        assert_eq!(
+            check(json!({ "Debug Print": "All", "Count": 2 })),
+            [
+                "GetControls returned `Log` more than once",
+                "GetControlLayout lays out `Gain 1`, which isn't in GetControls",
+                "GetControlLayout lays out `Gain 2`, which isn't in GetControls",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_problems() {
+        let result = |callback, result| CallbackResult {
+            callback,
+            page: None,
+            result,
+        };
+        let results = [
+            result(
+                "GetProperties",
+                Ok(json!([{ "Name": "Mode", "Type": "enum", "Choices": ["A"], "Value": "B" }])),
+            ),
+            result("RectifyProperties", Ok(JsonValue::Null)),
+            result(
+                "GetPins",
+                Ok(json!([{ "Name": "In", "Direction": "sideways" }, 3])),
+            ),
+            result("GetWiring", Ok(json!([["A"]]))),
+            result("GetComponents", Err("plugin:3: boom".to_string())),
+        ];
+        let problems: Vec<String> = problems(&results).iter().map(|p| p.to_string()).collect();
+        assert_eq!(
+            problems,
+            [
+                "GetProperties returned `Mode`: Value \"B\" isn't one of its Choices",
+                "RectifyProperties returned nil instead of the props table",
+                "GetPins returned `In`: Direction should be input or output",
+                "GetPins returned item 2 as a number",
+                "GetWiring returned wire 1: it should list two or more pins",
+                "GetComponents failed: plugin:3: boom",
+            ]
+        );
+    }
+}
@@ -1,4 +1,6 @@
 pub mod cli;
 pub mod config;
 pub mod files;
+pub mod license;
 pub mod lua;
+pub mod template;
@@ -0,0 +1,471 @@
+use std::{
+    error::Error,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use mlua::{Function, Lua, MultiValue, Table, Value};
+use regex::Regex;
+
+use super::{TemplateVars, BUILTIN_VARS};
+
+/// Optional file at the root of a template describing how to instantiate it.
+///
+/// ```lua
+/// return {
+///     prompts = {
+///         { name = "port", message = "Default TCP port", type = "integer", default = 23,
+///           validate = function(port) return port > 0 and port < 65536, "Not a valid port" end },
+///         { name = "family", message = "Device family", type = "choice", choices = { "DSP", "Amp" } },
+///     },
+///     exclude = { "docs", "*.md" },
+///     post_create = function(ctx) print("Created " .. ctx.name) end,
+/// }
+/// ```
+pub const MANIFEST_FILE: &str = "qplug-template.lua";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PromptType {
+    String,
+    Integer,
+    Number,
+    Boolean,
+    Choice(Vec<String>),
+}
+
+pub struct TemplatePrompt<'lua> {
+    pub name: String,
+    pub message: String,
+    pub kind: PromptType,
+    pub default: Option<String>,
+    pub validate: Option<Function<'lua>>,
+}
+
+pub struct TemplateManifest<'lua> {
+    pub prompts: Vec<TemplatePrompt<'lua>>,
+    pub exclude: Vec<String>,
+    pub post_create: Vec<Function<'lua>>,
+}
+
+impl<'lua> TemplatePrompt<'lua> {
+    fn from_table(table: Table<'lua>) -> mlua::Result<Self> {
+        let name: String = table.get("name")?;
+        if BUILTIN_VARS.contains(&name.as_str()) {
+            return Err(mlua::Error::RuntimeError(format!(
+                "prompt `{}` has the name of a built-in placeholder",
+                name
+            )));
+        }
+        let kind = match table
+            .get::<_, Option<String>>("type")?
+            .as_deref()
+            .unwrap_or("string")
+        {
+            "string" => PromptType::String,
+            "integer" => PromptType::Integer,
+            "number" => PromptType::Number,
+            "boolean" => PromptType::Boolean,
+            "choice" => PromptType::Choice(table.get("choices")?),
+            other => {
+                return Err(mlua::Error::RuntimeError(format!(
+                    "prompt `{}` has unknown type `{}`",
+                    name, other
+                )))
+            }
+        };
+        let default = match table.get::<_, Value>("default")? {
+            Value::Nil => None,
+            Value::Boolean(b) => Some(b.to_string()),
+            Value::Integer(i) => Some(i.to_string()),
+            Value::Number(n) => Some(n.to_string()),
+            Value::String(s) => Some(s.to_str()?.to_string()),
+            _ => {
+                return Err(mlua::Error::RuntimeError(format!(
+                    "prompt `{}` has an invalid default",
+                    name
+                )))
+            }
+        };
+
+        Ok(TemplatePrompt {
+            message: table
+                .get::<_, Option<String>>("message")?
+                .unwrap_or_else(|| name.to_owned()),
+            name,
+            kind,
+            default,
+            validate: table.get("validate")?,
+        })
+    }
+
+    /// The question shown to the user, including choices and the default.
+    pub fn question(&self) -> String {
+        let mut question = self.message.to_owned();
+        if let PromptType::Choice(choices) = &self.kind {
+            question = format!("{} ({})", question, choices.join(", "));
+        }
+        if let Some(default) = &self.default {
+            question = format!("{} [{}]", question, default);
+        }
+        format!("{}: ", question)
+    }
+
+    /// Turn raw input into a validated answer, falling back to the default on empty input.
+    pub fn resolve(&self, lua: &'lua Lua, input: &str) -> Result<String, String> {
+        let input = input.trim();
+        let input = match (input.is_empty(), &self.default) {
+            (true, Some(default)) => default.as_str(),
+            (true, None) => return Err(format!("A value for `{}` is required.", self.name)),
+            (false, _) => input,
+        };
+
+        let value: Value = match &self.kind {
+            PromptType::String => Value::String(lua.create_string(input).unwrap()),
+            PromptType::Integer => input
+                .parse::<i64>()
+                .map(Value::Integer)
+                .map_err(|_| format!("`{}` is not a whole number.", input))?,
+            PromptType::Number => input
+                .parse::<f64>()
+                .map(Value::Number)
+                .map_err(|_| format!("`{}` is not a number.", input))?,
+            PromptType::Boolean => match input.to_lowercase().as_str() {
+                "y" | "yes" | "true" => Value::Boolean(true),
+                "n" | "no" | "false" => Value::Boolean(false),
+                _ => return Err(format!("`{}` is not yes or no.", input)),
+            },
+            PromptType::Choice(choices) => {
+                // Accept either the choice itself or its 1-based position.
+                let choice = input
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|i| i.checked_sub(1))
+                    .and_then(|i| choices.get(i))
+                    .or_else(|| choices.iter().find(|c| c.as_str() == input))
+                    .ok_or_else(|| format!("`{}` is not one of: {}", input, choices.join(", ")))?;
+                Value::String(lua.create_string(choice).unwrap())
+            }
+        };
+
+        let answer = match &value {
+            Value::String(s) => s.to_str().unwrap_or_default().to_string(),
+            Value::Integer(i) => i.to_string(),
+            Value::Number(n) => n.to_string(),
+            Value::Boolean(b) => b.to_string(),
+            _ => unreachable!(),
+        };
+
+        if let Some(validate) = &self.validate {
+            let result: MultiValue = validate
+                .call(value)
+                .map_err(|e| format!("Validation of `{}` failed: {}", self.name, e))?;
+            let mut result = result.into_iter();
+            match result.next() {
+                Some(Value::Boolean(true)) => {}
+                Some(Value::String(message)) => {
+                    return Err(message.to_str().unwrap_or_default().to_string())
+                }
+                _ => {
+                    return Err(match result.next() {
+                        Some(Value::String(message)) => {
+                            message.to_str().unwrap_or_default().to_string()
+                        }
+                        _ => format!("`{}` is not a valid value for `{}`.", input, self.name),
+                    })
+                }
+            }
+        }
+
+        Ok(answer)
+    }
+}
+
+impl<'lua> TemplateManifest<'lua> {
+    pub fn load(lua: &'lua Lua, path: &Path) -> mlua::Result<Self> {
+        let table: Table = lua
+            .load(fs::read_to_string(path)?)
+            .set_name(path.to_string_lossy())
+            .call(())?;
+
+        let prompts = match table.get::<_, Option<Vec<Table>>>("prompts")? {
+            Some(prompts) => prompts
+                .into_iter()
+                .map(TemplatePrompt::from_table)
+                .collect::<mlua::Result<_>>()?,
+            None => Vec::new(),
+        };
+
+        // A single hook or a list of them.
+        let post_create = match table.get::<_, Value>("post_create")? {
+            Value::Nil => Vec::new(),
+            Value::Function(f) => vec![f],
+            Value::Table(t) => t.sequence_values().collect::<mlua::Result<_>>()?,
+            _ => {
+                return Err(mlua::Error::RuntimeError(
+                    "post_create must be a function or a list of functions".to_string(),
+                ))
+            }
+        };
+
+        Ok(TemplateManifest {
+            prompts,
+            exclude: table
+                .get::<_, Option<Vec<String>>>("exclude")?
+                .unwrap_or_default(),
+            post_create,
+        })
+    }
+
+    /// Load the manifest from a template directory, if it has one.
+    pub fn find(lua: &'lua Lua, template_dir: &Path) -> mlua::Result<Option<Self>> {
+        let path = template_dir.join(MANIFEST_FILE);
+        if path.is_file() {
+            TemplateManifest::load(lua, &path).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Ask every prompt, re-asking until the answer is valid.
+    pub fn ask<F>(&self, lua: &'lua Lua, mut input: F) -> Result<TemplateVars, Box<dyn Error>>
+    where
+        F: FnMut(&TemplatePrompt) -> io::Result<String>,
+    {
+        let mut answers = TemplateVars::new();
+        for prompt in &self.prompts {
+            let answer = loop {
+                match prompt.resolve(lua, &input(prompt)?) {
+                    Ok(answer) => break answer,
+                    Err(message) => eprintln!("{}", message),
+                }
+            };
+            answers.insert(prompt.name.to_owned(), answer);
+        }
+        Ok(answers)
+    }
+
+    /// Remove the manifest and every excluded file or directory from an instantiated template.
+    pub fn apply_excludes(&self, dir: &Path) -> io::Result<()> {
+        let patterns: Vec<Regex> = self.exclude.iter().map(|p| glob_to_regex(p)).collect();
+        remove_matching(dir, dir, &patterns)?;
+
+        let manifest = dir.join(MANIFEST_FILE);
+        if manifest.exists() {
+            fs::remove_file(manifest)?;
+        }
+        Ok(())
+    }
+
+    /// Run the `post_create` hooks with the new project's details.
+    pub fn run_post_create(
+        &self,
+        lua: &'lua Lua,
+        root_path: &Path,
+        plugin_path: &Path,
+        vars: &TemplateVars,
+    ) -> mlua::Result<()> {
+        let ctx = lua.create_table()?;
+        ctx.set("name", vars.get("name").cloned())?;
+        ctx.set("root", absolute(root_path).to_string_lossy())?;
+        ctx.set("plugin_src", absolute(plugin_path).to_string_lossy())?;
+        ctx.set("vars", lua.create_table_from(vars.clone())?)?;
+
+        for hook in &self.post_create {
+            hook.call::<_, ()>(ctx.clone())?;
+        }
+        Ok(())
+    }
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn remove_matching(root: &Path, dir: &Path, patterns: &[Regex]) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let relative = path
+            .strip_prefix(root)
+            .unwrap_or(&path)
+            .to_string_lossy()
+            .replace('\\', "/");
+
+        if patterns.iter().any(|p| p.is_match(&relative)) {
+            if path.is_dir() {
+                fs::remove_dir_all(&path)?;
+            } else {
+                fs::remove_file(&path)?;
+            }
+        } else if path.is_dir() {
+            remove_matching(root, &path, patterns)?;
+        }
+    }
+    Ok(())
+}
+
+/// Translate a gitignore-style glob into a regex over `/`-separated relative paths.
+/// Patterns without a `/` match a name at any depth; a leading `/` anchors to the template root.
+fn glob_to_regex(glob: &str) -> Regex {
+    let anchored = glob.contains('/');
+    let glob = glob.trim_start_matches('/');
+    let mut re = String::from(if anchored { "^" } else { "^(.*/)?" });
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    re.push_str("(.*/)?");
+                } else {
+                    re.push_str(".*");
+                }
+            }
+            '*' => re.push_str("[^/]*"),
+            '?' => re.push_str("[^/]"),
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    re.push('$');
+    Regex::new(&re).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    const MANIFEST: &str = r#"
+        return {
+            prompts = {
+                { name = "port", message = "Default TCP port", type = "integer", default = 23,
+                  validate = function(port) return port > 0 and port < 65536, "Not a valid port" end },
+                { name = "family", message = "Device family", type = "choice", choices = { "DSP", "Amp" } },
+                { name = "secure", type = "boolean", default = false },
+            },
+            exclude = { "docs", "*.md", "setup/**/*.tmp" },
+            post_create = function(ctx)
+                local f = io.open(ctx.root .. "/hook.txt", "w")
+                f:write(ctx.name .. ":" .. ctx.vars.port)
+                f:close()
+            end,
+        }
+    "#;
+
+    fn manifest_dir() -> tempfile::TempDir {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join(MANIFEST_FILE), MANIFEST).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_glob_to_regex() {
+        assert!(glob_to_regex("*.md").is_match("docs/README.md"));
+        assert!(glob_to_regex("docs").is_match("docs"));
+        assert!(!glob_to_regex("/docs").is_match("a/docs"));
+        assert!(glob_to_regex("setup/**/*.tmp").is_match("setup/a/b/c.tmp"));
+        assert!(glob_to_regex("setup/**/*.tmp").is_match("setup/c.tmp"));
+        assert!(!glob_to_regex("setup/*.tmp").is_match("setup/a/c.tmp"));
+    }
+
+    #[test]
+    fn test_ask() {
+        let dir = manifest_dir();
+        let lua = Lua::new();
+        let manifest = TemplateManifest::find(&lua, dir.path()).unwrap().unwrap();
+
+        // Invalid answers are asked again.
+        let mut inputs = vec!["70000", "", "3", "Amp", "yes"].into_iter();
+        let answers = manifest
+            .ask(&lua, |_| Ok(inputs.next().unwrap().to_string()))
+            .unwrap();
+
+        assert_eq!(answers["port"], "23");
+        assert_eq!(answers["family"], "Amp");
+        assert_eq!(answers["secure"], "true");
+        assert!(inputs.next().is_none());
+
+        // A required prompt stops asking once input runs out.
+        let mut inputs = vec!["", ""].into_iter();
+        let err = manifest.ask(&lua, |_| {
+            inputs
+                .next()
+                .map(str::to_string)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "closed"))
+        });
+        assert!(err.is_err());
+    }
+
+    #[test]
+    fn test_builtin_prompt_name() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join(MANIFEST_FILE),
+            "return { prompts = { { name = \"uuid\", message = \"Id\" } } }",
+        )
+        .unwrap();
+        let lua = Lua::new();
+        let Err(err) = TemplateManifest::find(&lua, dir.path()) else {
+            panic!("a prompt named `uuid` was accepted");
+        };
+        assert!(err
+            .to_string()
+            .contains("prompt `uuid` has the name of a built-in placeholder"));
+    }
+
+    #[test]
+    fn test_resolve_messages() {
+        let dir = manifest_dir();
+        let lua = Lua::new();
+        let manifest = TemplateManifest::find(&lua, dir.path()).unwrap().unwrap();
+
+        assert_eq!(
+            manifest.prompts[0].resolve(&lua, "0").unwrap_err(),
+            "Not a valid port"
+        );
+        assert!(manifest.prompts[0].resolve(&lua, "abc").is_err());
+        assert!(manifest.prompts[1].resolve(&lua, "").is_err());
+        assert_eq!(manifest.prompts[0].question(), "Default TCP port [23]: ");
+    }
+
+    #[test]
+    fn test_apply_excludes() {
+        let dir = manifest_dir();
+        let lua = Lua::new();
+        let manifest = TemplateManifest::find(&lua, dir.path()).unwrap().unwrap();
+        fs::create_dir_all(dir.path().join("docs")).unwrap();
+        fs::create_dir_all(dir.path().join("setup/nested")).unwrap();
+        fs::write(dir.path().join("docs/guide.txt"), "").unwrap();
+        fs::write(dir.path().join("README.md"), "").unwrap();
+        fs::write(dir.path().join("setup/nested/x.tmp"), "").unwrap();
+        fs::write(dir.path().join("setup/info.lua"), "").unwrap();
+
+        manifest.apply_excludes(dir.path()).unwrap();
+
+        assert!(!dir.path().join(MANIFEST_FILE).exists());
+        assert!(!dir.path().join("docs").exists());
+        assert!(!dir.path().join("README.md").exists());
+        assert!(!dir.path().join("setup/nested/x.tmp").exists());
+        assert!(dir.path().join("setup/info.lua").exists());
+    }
+
+    #[test]
+    fn test_post_create() {
+        let dir = manifest_dir();
+        let lua = Lua::new();
+        let manifest = TemplateManifest::find(&lua, dir.path()).unwrap().unwrap();
+        let vars = TemplateVars::from([
+            ("name".to_string(), "Amp".to_string()),
+            ("port".to_string(), "23".to_string()),
+        ]);
+
+        manifest
+            .run_post_create(&lua, dir.path(), &dir.path().join("plugin_src"), &vars)
+            .unwrap();
+
+        assert_eq!(
+            fs::read_to_string(dir.path().join("hook.txt")).unwrap(),
+            "Amp:23"
+        );
+    }
+}
@@ -0,0 +1,584 @@
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+use directories::BaseDirs;
+use git2::{build::CheckoutBuilder, Repository};
+use regex::{Captures, Regex};
+
+use crate::{
+    files::{hash, Extractable},
+    lua::parser::quote_string,
+};
+
+pub mod manifest;
+
+/// Values available to templates as `{{ key }}`.
+pub type TemplateVars = BTreeMap<String, String>;
+
+/// The placeholders qplug fills in itself. Manifest prompts can't reuse these names.
+pub const BUILTIN_VARS: [&str; 8] = [
+    "name",
+    "description",
+    "uuid",
+    "author",
+    "email",
+    "company",
+    "year",
+    "copyright",
+];
+
+/// Replace every `{{ key }}` in `text` with its value. Unknown keys are left untouched so Lua
+/// code that happens to look like a placeholder survives.
+pub fn render(text: &str, vars: &TemplateVars) -> String {
+    render_with(text, vars, |_, value| value.to_owned())
+}
+
+/// Like `render`, for Lua source. A value that lands inside a quoted string is escaped, so a
+/// plugin name with `"` or `\` in it still parses.
+pub fn render_lua(text: &str, vars: &TemplateVars) -> String {
+    render_with(text, vars, |before, value| match open_quote(before) {
+        Some(quote) => escape_lua(value, quote),
+        None => value.to_owned(),
+    })
+}
+
+fn render_with(text: &str, vars: &TemplateVars, fill: impl Fn(&str, &str) -> String) -> String {
+    let re = Regex::new(r"\{\{\s*([A-Za-z_][A-Za-z0-9_]*)\s*\}\}").unwrap();
+
+    re.replace_all(text, |cap: &Captures| match vars.get(&cap[1]) {
+        Some(value) => fill(&text[..cap.get(0).unwrap().start()], value),
+        None => cap[0].to_string(),
+    })
+    .to_string()
+}
+
+/// The quote of the string literal that `before` ends inside of, looking back to the start of
+/// its line. `None` outside strings and in comments.
+fn open_quote(before: &str) -> Option<char> {
+    let line = &before[before.rfind('\n').map_or(0, |i| i + 1)..];
+    let mut quote = None;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match quote {
+            Some(_) if c == '\\' => {
+                chars.next();
+            }
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '-' && chars.peek() == Some(&'-') => return None,
+            None => {}
+        }
+    }
+    quote
+}
+
+/// `value` escaped to sit between `quote`s in a Lua string.
+fn escape_lua(value: &str, quote: char) -> String {
+    let quoted = quote_string(value);
+    let inner = &quoted[1..quoted.len() - 1];
+    if quote == '\'' {
+        inner.replace('\'', "\\'")
+    } else {
+        inner.to_string()
+    }
+}
+
+/// Render file contents and file/directory names below `dir` in place. Binary files are skipped.
+pub fn render_dir(dir: &Path, vars: &TemplateVars) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        // Handle children first so renaming a directory doesn't invalidate their paths.
+        if path.is_dir() {
+            render_dir(&path, vars)?;
+        } else {
+            render_file(&path, vars)?;
+        }
+
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        let rendered_name = render(&file_name, vars);
+        if rendered_name != file_name {
+            fs::rename(&path, path.with_file_name(rendered_name))?;
+        }
+    }
+    Ok(())
+}
+
+fn render_file(path: &Path, vars: &TemplateVars) -> io::Result<()> {
+    let bytes = fs::read(path)?;
+    if bytes.contains(&0) {
+        return Ok(());
+    }
+    let Ok(text) = String::from_utf8(bytes) else {
+        return Ok(());
+    };
+
+    let rendered = if path.extension().is_some_and(|ext| ext == "lua") {
+        render_lua(&text, vars)
+    } else {
+        render(&text, vars)
+    };
+    if rendered != text {
+        fs::write(path, rendered)?;
+    }
+    Ok(())
+}
+
+/// A git-hosted template: `<url>[#<ref>][:<subdir>]`, e.g.
+/// `https://host/repo.git#v2.1:templates/tcp`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GitSource {
+    pub url: String,
+    pub reference: Option<String>,
+    pub subdir: Option<PathBuf>,
+}
+
+impl GitSource {
+    pub fn parse(source: &str) -> Self {
+        let (url, fragment) = match source.split_once('#') {
+            Some((url, fragment)) => (url, fragment),
+            None => (source, ""),
+        };
+        // Git ref names can't contain ':', so the first one starts the subdirectory.
+        let (reference, subdir) = match fragment.split_once(':') {
+            Some((reference, subdir)) => (reference, subdir),
+            None => (fragment, ""),
+        };
+
+        GitSource {
+            url: url.to_string(),
+            reference: Some(reference.to_string()).filter(|r| !r.is_empty()),
+            subdir: Some(subdir.trim_matches('/'))
+                .filter(|s| !s.is_empty())
+                .map(PathBuf::from),
+        }
+    }
+}
+
+/// Whether a configured template should be cloned rather than copied from disk.
+pub fn is_git_url(template: &str) -> bool {
+    ["http://", "https://", "ssh://", "git://", "file://", "git@"]
+        .iter()
+        .any(|prefix| template.starts_with(prefix))
+}
+
+/// Clone `source` into a scratch directory and copy the requested ref and subdirectory into
+/// `dest`, leaving the template's git metadata behind.
+pub fn clone_template(source: &GitSource, dest: &Path) -> Result<(), Box<dyn Error>> {
+    fetch_git_template(source, dest, None)
+}
+
+/// Like [`clone_template`], but refresh `cache` on success and fall back to it (with a warning)
+/// when the clone fails, e.g. when offline.
+pub fn fetch_git_template(
+    source: &GitSource,
+    dest: &Path,
+    cache: Option<&TemplateCache>,
+) -> Result<(), Box<dyn Error>> {
+    match checkout(source) {
+        Ok(checkout) => {
+            if let Some(cache) = cache {
+                if let Err(e) = cache.store(source, checkout.path()) {
+                    eprintln!("Warning: unable to cache template {}: {}", source.url, e);
+                }
+            }
+            copy_template(checkout.path(), source, dest)
+        }
+        Err(e) => match cache.and_then(|cache| cache.get(source)) {
+            Some(cached) => {
+                eprintln!(
+                    "Warning: unable to clone {} ({}). Using the cached copy from {}.",
+                    source.url,
+                    e,
+                    cached.display()
+                );
+                copy_template(&cached, source, dest)
+            }
+            None => Err(e),
+        },
+    }
+}
+
+/// Clone `source` and check out the requested ref.
+fn checkout(source: &GitSource) -> Result<tempfile::TempDir, Box<dyn Error>> {
+    let checkout = tempfile::tempdir()?;
+    let repo = Repository::clone(&source.url, checkout.path())?;
+
+    if let Some(reference) = &source.reference {
+        // Branches only exist as remote-tracking refs after a clone.
+        let object = repo
+            .revparse_single(&format!("origin/{}", reference))
+            .or_else(|_| repo.revparse_single(reference))
+            .map_err(|e| format!("Unknown ref `{}`: {}", reference, e.message()))?;
+        repo.checkout_tree(&object, Some(CheckoutBuilder::new().force()))?;
+        repo.set_head_detached(object.peel_to_commit()?.id())?;
+    }
+    Ok(checkout)
+}
+
+/// Copy the template (or its subdirectory) out of a checkout, without any `.git` directory.
+fn copy_template(root: &Path, source: &GitSource, dest: &Path) -> Result<(), Box<dyn Error>> {
+    let template_root = match &source.subdir {
+        Some(subdir) => {
+            if subdir
+                .components()
+                .any(|c| !matches!(c, Component::Normal(_)))
+            {
+                return Err(format!("Invalid template subdirectory `{}`", subdir.display()).into());
+            }
+            root.join(subdir)
+        }
+        None => root.to_path_buf(),
+    };
+    if !template_root.is_dir() {
+        return Err(format!(
+            "`{}` is not a directory in {}",
+            source.subdir.as_deref().unwrap_or(Path::new(".")).display(),
+            source.url
+        )
+        .into());
+    }
+
+    copy_without_git(&template_root, dest)?;
+    Ok(())
+}
+
+fn copy_without_git(source: &Path, dest: &Path) -> io::Result<()> {
+    source.extract(dest)?;
+    let git_dir = dest.join(".git");
+    if git_dir.exists() {
+        fs::remove_dir_all(git_dir)?;
+    }
+    Ok(())
+}
+
+/// Checkouts of git templates, keyed by URL and ref, so `qplug new` works offline.
+pub struct TemplateCache {
+    root: PathBuf,
+}
+
+impl TemplateCache {
+    pub fn new(root: PathBuf) -> Self {
+        TemplateCache { root }
+    }
+
+    /// The cache in the platform cache dir (e.g. `~/.cache/qplug/templates` on Linux).
+    pub fn user_cache() -> Option<Self> {
+        BaseDirs::new().map(|dirs| TemplateCache::new(dirs.cache_dir().join("qplug/templates")))
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// A readable name for `source`, with a hash of the URL and ref so that two sources that
+    /// read the same once sanitized (`a/b` and `a_b`) still get entries of their own.
+    fn key(source: &GitSource) -> String {
+        let id = match &source.reference {
+            Some(reference) => format!("{}@{}", source.url, reference),
+            None => source.url.to_owned(),
+        };
+        let readable: String = id
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || "-_.@".contains(c) {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        format!("{}-{}", readable, hash(id.as_bytes()))
+    }
+
+    fn entry(&self, source: &GitSource) -> PathBuf {
+        self.root.join(Self::key(source))
+    }
+
+    // The source is kept next to the entry rather than inside it so it never lands in a plugin.
+    fn source_file(&self, source: &GitSource) -> PathBuf {
+        self.root.join(format!("{}.source", Self::key(source)))
+    }
+
+    /// The cached checkout for `source`, if there is one.
+    pub fn get(&self, source: &GitSource) -> Option<PathBuf> {
+        Some(self.entry(source)).filter(|entry| entry.is_dir())
+    }
+
+    /// Replace the cached checkout for `source` with `checkout`.
+    pub fn store(&self, source: &GitSource, checkout: &Path) -> io::Result<()> {
+        let entry = self.entry(source);
+        if entry.exists() {
+            fs::remove_dir_all(&entry)?;
+        }
+        fs::create_dir_all(&self.root)?;
+        copy_without_git(checkout, &entry)?;
+
+        let mut id = source.url.to_owned();
+        if let Some(reference) = &source.reference {
+            id = format!("{}#{}", id, reference);
+        }
+        fs::write(self.source_file(source), id)
+    }
+
+    /// Re-clone `source` into the cache.
+    pub fn update(&self, source: &GitSource) -> Result<(), Box<dyn Error>> {
+        let checkout = checkout(source)?;
+        self.store(source, checkout.path())?;
+        Ok(())
+    }
+
+    /// Every template currently in the cache.
+    pub fn sources(&self) -> Vec<GitSource> {
+        let mut sources: Vec<GitSource> = fs::read_dir(&self.root)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "source"))
+            .filter_map(|path| fs::read_to_string(path).ok())
+            .map(|id| GitSource::parse(id.trim()))
+            .collect();
+        sources.sort_by(|a, b| (&a.url, &a.reference).cmp(&(&b.url, &b.reference)));
+        sources
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    /// Build a local repo with `templates/tcp/init.lua` tagged `v1`, then change it on `main`.
+    pub(crate) fn template_repo(dir: &Path) -> String {
+        let repo = Repository::init(dir).unwrap();
+        let signature = git2::Signature::now("qplug", "qplug@example.com").unwrap();
+        let file = dir.join("templates/tcp/init.lua");
+        fs::create_dir_all(file.parent().unwrap()).unwrap();
+
+        let mut parent: Option<git2::Oid> = None;
+        for (version, tag) in [("v1", Some("v1")), ("v2", None)] {
+            fs::write(&file, format!("-- {}", version)).unwrap();
+            fs::write(dir.join("README.md"), version).unwrap();
+            let mut index = repo.index().unwrap();
+            index
+                .add_all(["*"].iter(), git2::IndexAddOption::DEFAULT, None)
+                .unwrap();
+            let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+            let parents: Vec<git2::Commit> = parent
+                .iter()
+                .map(|id| repo.find_commit(*id).unwrap())
+                .collect();
+            let parents: Vec<&git2::Commit> = parents.iter().collect();
+            let commit = repo
+                .commit(
+                    Some("HEAD"),
+                    &signature,
+                    &signature,
+                    version,
+                    &tree,
+                    &parents,
+                )
+                .unwrap();
+            if let Some(tag) = tag {
+                repo.tag_lightweight(tag, &repo.find_object(commit, None).unwrap(), false)
+                    .unwrap();
+            }
+            parent = Some(commit);
+        }
+        format!("file://{}", dir.display())
+    }
+
+    fn vars() -> TemplateVars {
+        TemplateVars::from([
+            ("name".to_string(), "Amp".to_string()),
+            ("year".to_string(), "2024".to_string()),
+        ])
+    }
+
+    #[test]
+    fn test_render() {
+        assert_eq!(
+            render("-- {{ name }} ({{year}}) {{ unknown }}", &vars()),
+            "-- Amp (2024) {{ unknown }}"
+        );
+        // Nested Lua tables are not placeholders.
+        assert_eq!(render("t = {{1, 2}}", &vars()), "t = {{1, 2}}");
+    }
+
+    #[test]
+    fn test_render_lua() {
+        let vars = TemplateVars::from([("name".to_string(), "A \"B\" \\ C's".to_string())]);
+        assert_eq!(
+            render_lua("x = \"{{ name }}\"\ny = '{{ name }}' -- {{ name }}", &vars),
+            "x = \"A \\\"B\\\" \\\\ C's\"\ny = 'A \\\"B\\\" \\\\ C\\'s' -- A \"B\" \\ C's"
+        );
+        assert_eq!(
+            render_lua("x = \"\\\"\" .. \"{{ name }}\"", &vars),
+            "x = \"\\\"\" .. \"A \\\"B\\\" \\\\ C's\""
+        );
+
+        let lua = mlua::Lua::new();
+        let rendered = render_lua("return \"{{ name }}\", '{{ name }}'", &vars);
+        let (double, single): (String, String) = lua.load(&rendered).eval().unwrap();
+        assert_eq!(
+            (double.as_str(), single.as_str()),
+            (vars["name"].as_str(), vars["name"].as_str())
+        );
+    }
+
+    #[test]
+    fn test_parse_git_source() {
+        assert_eq!(
+            GitSource::parse("https://host/repo.git#v2.1:templates/tcp"),
+            GitSource {
+                url: "https://host/repo.git".to_string(),
+                reference: Some("v2.1".to_string()),
+                subdir: Some(PathBuf::from("templates/tcp")),
+            }
+        );
+        assert_eq!(
+            GitSource::parse("https://host/repo.git#:templates/tcp/"),
+            GitSource {
+                url: "https://host/repo.git".to_string(),
+                reference: None,
+                subdir: Some(PathBuf::from("templates/tcp")),
+            }
+        );
+        assert_eq!(
+            GitSource::parse("https://host/repo.git"),
+            GitSource {
+                url: "https://host/repo.git".to_string(),
+                reference: None,
+                subdir: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_clone_template_ref_and_subdir() {
+        let repo_dir = tempdir().unwrap();
+        let url = template_repo(repo_dir.path());
+        let dest = tempdir().unwrap();
+
+        clone_template(
+            &GitSource::parse(&format!("{}#v1:templates/tcp", url)),
+            dest.path(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            fs::read_to_string(dest.path().join("init.lua")).unwrap(),
+            "-- v1"
+        );
+        assert!(!dest.path().join("README.md").exists());
+        assert!(!dest.path().join(".git").exists());
+    }
+
+    #[test]
+    fn test_clone_template_strips_git() {
+        let repo_dir = tempdir().unwrap();
+        let url = template_repo(repo_dir.path());
+        let dest = tempdir().unwrap();
+
+        clone_template(&GitSource::parse(&url), dest.path()).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(dest.path().join("templates/tcp/init.lua")).unwrap(),
+            "-- v2"
+        );
+        assert!(!dest.path().join(".git").exists());
+    }
+
+    #[test]
+    fn test_clone_template_bad_subdir() {
+        let repo_dir = tempdir().unwrap();
+        let url = template_repo(repo_dir.path());
+        let dest = tempdir().unwrap();
+
+        assert!(
+            clone_template(&GitSource::parse(&format!("{}#:missing", url)), dest.path()).is_err()
+        );
+        assert!(clone_template(&GitSource::parse(&format!("{}#:../x", url)), dest.path()).is_err());
+    }
+
+    #[test]
+    fn test_cache_fallback() {
+        let repo_dir = tempdir().unwrap();
+        let url = template_repo(repo_dir.path());
+        let source = GitSource::parse(&format!("{}#v1:templates/tcp", url));
+        let cache_dir = tempdir().unwrap();
+        let cache = TemplateCache::new(cache_dir.path().to_path_buf());
+
+        let first = tempdir().unwrap();
+        fetch_git_template(&source, first.path(), Some(&cache)).unwrap();
+        assert!(cache.get(&source).is_some());
+        // Entries hold the whole checkout, so the subdirectory isn't part of the key.
+        assert_eq!(
+            cache.sources(),
+            vec![GitSource::parse(&format!("{}#v1", url))]
+        );
+
+        // The repo is gone, so only the cache can provide the template.
+        fs::remove_dir_all(repo_dir.path()).unwrap();
+        let second = tempdir().unwrap();
+        fetch_git_template(&source, second.path(), Some(&cache)).unwrap();
+        assert_eq!(
+            fs::read_to_string(second.path().join("init.lua")).unwrap(),
+            "-- v1"
+        );
+        assert!(!second.path().join(".git").exists());
+
+        let uncached = GitSource::parse(&format!("{}#v2", url));
+        assert!(fetch_git_template(&uncached, second.path(), Some(&cache)).is_err());
+    }
+
+    #[test]
+    fn test_cache_key() {
+        let key = |id: &str| TemplateCache::key(&GitSource::parse(id));
+        assert_ne!(key("https://host/a/b"), key("https://host/a_b"));
+        assert_ne!(key("https://host/a#v1"), key("https://host/a#v2"));
+        assert!(key("https://host/a#v1").starts_with("https___host_a@v1-"));
+    }
+
+    #[test]
+    fn test_cache_update() {
+        let repo_dir = tempdir().unwrap();
+        let url = template_repo(repo_dir.path());
+        let source = GitSource::parse(&url);
+        let cache_dir = tempdir().unwrap();
+        let cache = TemplateCache::new(cache_dir.path().to_path_buf());
+
+        cache.update(&source).unwrap();
+        let entry = cache.get(&source).unwrap();
+        assert_eq!(fs::read_to_string(entry.join("README.md")).unwrap(), "v2");
+        assert!(!entry.join(".git").exists());
+
+        fs::write(entry.join("README.md"), "stale").unwrap();
+        cache.update(&source).unwrap();
+        assert_eq!(fs::read_to_string(entry.join("README.md")).unwrap(), "v2");
+    }
+
+    #[test]
+    fn test_render_dir() {
+        let temp_dir = tempdir().unwrap();
+        let nested = temp_dir.path().join("{{ name }}_module");
+        fs::create_dir(&nested).unwrap();
+        fs::write(nested.join("{{name}}.lua"), "return \"{{ name }}\"").unwrap();
+        fs::write(temp_dir.path().join("logo.png"), b"\x89PNG\0{{ name }}").unwrap();
+
+        render_dir(temp_dir.path(), &vars()).unwrap();
+
+        let rendered = temp_dir.path().join("Amp_module/Amp.lua");
+        assert_eq!(fs::read_to_string(rendered).unwrap(), "return \"Amp\"");
+        assert_eq!(
+            fs::read(temp_dir.path().join("logo.png")).unwrap(),
+            b"\x89PNG\0{{ name }}"
+        );
+    }
+}